@@ -4,20 +4,23 @@ use std::sync::Arc;
 use crayon::application::Context;
 use crayon::bincode;
 use crayon::errors::*;
+use crayon::res::format::Format;
 use crayon::res::registry::Register;
 use crayon::res::ResourceSystemShared;
 use crayon::video::VideoSystemShared;
 
 use super::prefab::*;
 
-pub const MAGIC: [u8; 8] = [
-    'P' as u8, 'R' as u8, 'E' as u8, 'B' as u8, ' ' as u8, 0, 0, 1,
-];
+/// The binary format of prefabs, the upgrades of older versions should be registered here.
+pub fn format() -> Format {
+    Format::new("PrefabLoader", *b"PREB", 1)
+}
 
 #[derive(Clone)]
 pub struct PrefabLoader {
     video: Arc<VideoSystemShared>,
     res: Arc<ResourceSystemShared>,
+    format: Format,
 }
 
 impl PrefabLoader {
@@ -25,6 +28,7 @@ impl PrefabLoader {
         PrefabLoader {
             res: ctx.res.clone(),
             video: ctx.video.clone(),
+            format: format(),
         }
     }
 }
@@ -35,11 +39,8 @@ impl Register for PrefabLoader {
     type Value = Arc<Prefab>;
 
     fn load(&self, handle: Self::Handle, bytes: &[u8]) -> Result<Self::Intermediate> {
-        if &bytes[0..8] != &MAGIC[..] {
-            bail!("[PrefabLoader] MAGIC number not match.");
-        }
-
-        let mut file = Cursor::new(&bytes[8..]);
+        let bytes = self.format.decode(bytes)?;
+        let mut file = Cursor::new(&bytes[..]);
         let mut prefab: Prefab = bincode::deserialize_from(&mut file)?;
 
         for &v in &prefab.universe_meshes {
@@ -47,10 +48,6 @@ impl Register for PrefabLoader {
             prefab.meshes.push(mesh);
         }
 
-        // for &v in &prefab.meshes {
-        //     self.res.wait(v)?;
-        // }
-
         info!(
             "[PrefabLoader] loads {:?}. (Nodes: {}, Meshes: {})",
             handle,
@@ -73,4 +70,8 @@ impl Register for PrefabLoader {
             self.video.delete_mesh(v);
         }
     }
+
+    fn format(&self) -> Option<&Format> {
+        Some(&self.format)
+    }
 }
@@ -2,8 +2,9 @@ use std::sync::Arc;
 
 use crayon::application::{Context, Engine};
 use crayon::errors::*;
+use crayon::res::format::Upgrade;
 use crayon::res::location::Location;
-use crayon::res::registry::Registry;
+use crayon::res::registry::{Registry, RegistryEntry, Res};
 
 use assets::mesh_builder::WorldBuiltinMeshes;
 use assets::prefab::{Prefab, PrefabHandle};
@@ -68,4 +69,22 @@ impl WorldResourcesShared {
     pub fn delete_prefab(&self, handle: PrefabHandle) {
         self.prefabs.delete(handle);
     }
+
+    /// Enumerates all the prefabs, which is useful for debugging and leak reports.
+    #[inline]
+    pub fn prefab_entries(&self) -> Vec<RegistryEntry<PrefabHandle>> {
+        self.prefabs.entries()
+    }
+
+    #[inline]
+    pub fn own_prefab(&self, handle: PrefabHandle) -> Res<PrefabHandle> {
+        self.prefabs.own(handle)
+    }
+
+    /// Registers an upgrade which converts the prefab payload of `version` into
+    /// `version + 1`.
+    #[inline]
+    pub fn register_prefab_upgrade(&self, version: u32, upgrade: Upgrade) -> Result<()> {
+        self.prefabs.register_upgrade(version, upgrade)
+    }
 }
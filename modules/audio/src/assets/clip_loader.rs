@@ -1,19 +1,25 @@
 use std::io::Cursor;
 use std::sync::Arc;
 
+use crayon::res::format::Format;
 use lewton::inside_ogg::OggStreamReader;
 
 use super::super::Result;
 use super::clip::*;
 
-pub const MAGIC: [u8; 8] = [b'C', b'S', b'F', b'X', b' ', 0, 0, 1];
+/// The binary format of audio clips, the upgrades of older versions should be registered here.
+pub fn format() -> Format {
+    Format::new("AudioClipLoader", *b"CSFX", 1)
+}
 
 #[derive(Clone)]
-pub struct AudioClipLoader {}
+pub struct AudioClipLoader {
+    format: Format,
+}
 
 impl AudioClipLoader {
     pub(crate) fn new() -> Self {
-        AudioClipLoader {}
+        AudioClipLoader { format: format() }
     }
 }
 
@@ -23,11 +29,8 @@ impl ::crayon::res::registry::Register for AudioClipLoader {
     type Value = Arc<AudioClip>;
 
     fn load(&self, handle: Self::Handle, bytes: &[u8]) -> Result<Self::Intermediate> {
-        if &bytes[0..8] != MAGIC {
-            bail!("[AudioClipLoader] MAGIC number not match.");
-        }
-
-        let cursor = Cursor::new(&bytes[8..]);
+        let bytes = self.format.decode(bytes)?;
+        let cursor = Cursor::new(&bytes[..]);
         let mut stream_reader = OggStreamReader::new(cursor).unwrap();
 
         let mut clip = AudioClip {
@@ -56,4 +59,8 @@ impl ::crayon::res::registry::Register for AudioClipLoader {
     }
 
     fn detach(&self, _: Self::Handle, _: Self::Value) {}
+
+    fn format(&self) -> Option<&Format> {
+        Some(&self.format)
+    }
 }
@@ -12,8 +12,9 @@ pub mod source;
 use std::sync::Arc;
 
 use crayon::math::Vector3;
+use crayon::res::format::Upgrade;
 use crayon::res::prelude::{Location, ResourceSystemShared};
-use crayon::res::registry::Registry;
+use crayon::res::registry::{Registry, RegistryEntry, Res};
 
 use self::assets::{AudioClipHandle, AudioClipLoader};
 use self::mixer::MixerController;
@@ -113,6 +114,27 @@ impl AudioSystemShared {
         self.clips.delete(handle);
     }
 
+    /// Registers an upgrade which converts the `AudioClip` payload of `version` into
+    /// `version + 1`.
+    #[inline]
+    pub fn register_clip_upgrade(&self, version: u32, upgrade: Upgrade) -> Result<()> {
+        self.clips.register_upgrade(version, upgrade)
+    }
+
+    /// Enumerates all the `AudioClip` resources, which is useful for debugging and leak
+    /// reports.
+    #[inline]
+    pub fn clip_entries(&self) -> Vec<RegistryEntry<AudioClipHandle>> {
+        self.clips.entries()
+    }
+
+    /// Takes over the ownership of `AudioClip` resource, which will be deleted when the
+    /// returned `Res` and all its clones are dropped.
+    #[inline]
+    pub fn own_clip(&self, handle: AudioClipHandle) -> Res<AudioClipHandle> {
+        self.clips.own(handle)
+    }
+
     /// Plays a audio source, returning a `AudioSourceHandle` for it.
     #[inline]
     pub fn play<T>(&self, params: T) -> Result<AudioSourceHandle>
@@ -157,6 +157,7 @@ impl Engine {
             }
 
             self.time.advance();
+            self.res.advance();
             self.video.swap_frames();
 
             let (video_info, duration) = {
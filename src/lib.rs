@@ -28,6 +28,7 @@ extern crate crossbeam_deque;
 extern crate cgmath;
 extern crate gl;
 extern crate glutin;
+extern crate miniz_oxide;
 
 #[macro_use]
 extern crate failure;
@@ -35,7 +36,7 @@ extern crate failure;
 extern crate log;
 
 #[macro_use]
-extern crate serde;
+pub extern crate serde;
 pub extern crate bincode;
 pub extern crate uuid;
 
@@ -0,0 +1,23 @@
+use uuid::Uuid;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(
+        display = "Integrity check of {} ({}) failed, {}.",
+        uuid, filename, reason
+    )]
+    IntegrityViolated {
+        uuid: Uuid,
+        filename: String,
+        reason: String,
+    },
+    #[fail(
+        display = "[{}] Unsupported version {}, expected {}.",
+        format, version, expected
+    )]
+    UnsupportedVersion {
+        format: String,
+        version: u32,
+        expected: u32,
+    },
+}
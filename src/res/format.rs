@@ -0,0 +1,237 @@
+//! Versioned header which is shared by all the binary formats of resources.
+//!
+//! Every binary resource starts with a 8-bytes header, which consists of a 4-bytes tag, a
+//! space and a 24-bits big-endian version, like `VTEX 0,0,1`. The payload of older versions
+//! could be converted into the current version at load time by the registered upgrades.
+//!
+//! The upgrades could also be registered at runtime with `Format::register_upgrade`, or with
+//! `Registry::register_upgrade` for the formats of built-in loaders.
+//!
+//! ```rust
+//! use crayon::errors::*;
+//! use crayon::res::format::Format;
+//!
+//! // The version 2 appends a trailing byte to the payload of version 1.
+//! fn upgrade_v1(payload: &[u8]) -> Result<Vec<u8>> {
+//!     let mut payload = payload.to_vec();
+//!     payload.push(0);
+//!     Ok(payload)
+//! }
+//!
+//! let format = Format::new("ExampleLoader", *b"EXPL", 2).with_upgrade(1, upgrade_v1);
+//!
+//! let mut bytes = Format::new("ExampleLoader", *b"EXPL", 1).magic().to_vec();
+//! bytes.push(1);
+//! assert_eq!(&format.decode(&bytes).unwrap()[..], &[1, 0]);
+//! ```
+
+use std::borrow::Cow;
+use std::sync::{Arc, RwLock};
+
+use errors::*;
+
+/// The length of header in bytes.
+pub const HEADER_LEN: usize = 8;
+
+/// The maximum version which could be encoded in header.
+pub const MAX_VERSION: u32 = 0x00ff_ffff;
+
+/// Converts the payload of a version into the next version.
+pub type Upgrade = fn(&[u8]) -> Result<Vec<u8>>;
+
+/// The header of binary formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Header {
+    pub tag: [u8; 4],
+    pub version: u32,
+}
+
+impl Header {
+    pub fn new(tag: [u8; 4], version: u32) -> Self {
+        assert!(version <= MAX_VERSION);
+        Header {
+            tag: tag,
+            version: version,
+        }
+    }
+
+    /// Parses the header at the beginning of `bytes`.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_LEN || bytes[4] != b' ' {
+            return None;
+        }
+
+        let mut tag = [0; 4];
+        tag.copy_from_slice(&bytes[0..4]);
+
+        let version = bytes[5..8]
+            .iter()
+            .fold(0, |version, &v| (version << 8) | u32::from(v));
+
+        Some(Header::new(tag, version))
+    }
+
+    /// Encodes the header into bytes.
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        [
+            self.tag[0],
+            self.tag[1],
+            self.tag[2],
+            self.tag[3],
+            b' ',
+            (self.version >> 16) as u8,
+            (self.version >> 8) as u8,
+            self.version as u8,
+        ]
+    }
+}
+
+/// A binary format with the current version and the upgrades of older versions. The clones
+/// of a `Format` share the same upgrades.
+#[derive(Clone)]
+pub struct Format {
+    name: &'static str,
+    header: Header,
+    upgrades: Arc<RwLock<Vec<(u32, Upgrade)>>>,
+}
+
+impl Format {
+    /// Creates a new `Format`, the `name` is used as the prefix of error messages.
+    pub fn new(name: &'static str, tag: [u8; 4], version: u32) -> Self {
+        Format {
+            name: name,
+            header: Header::new(tag, version),
+            upgrades: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+
+    /// Registers an upgrade which converts the payload of `version` into `version + 1`.
+    pub fn with_upgrade(self, version: u32, upgrade: Upgrade) -> Self {
+        self.register_upgrade(version, upgrade).unwrap();
+        self
+    }
+
+    /// Registers an upgrade which converts the payload of `version` into `version + 1` at
+    /// runtime, the previous upgrade of `version` is replaced.
+    pub fn register_upgrade(&self, version: u32, upgrade: Upgrade) -> Result<()> {
+        if version >= self.header.version {
+            bail!(
+                "[{}] Could not register upgrade of version {}, expected older than {}.",
+                self.name,
+                version,
+                self.header.version
+            );
+        }
+
+        let mut upgrades = self.upgrades.write().unwrap();
+        upgrades.retain(|v| v.0 != version);
+        upgrades.push((version, upgrade));
+        Ok(())
+    }
+
+    /// Gets the header of current version.
+    #[inline]
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// Gets the encoded header of current version, which should be written before payload.
+    #[inline]
+    pub fn magic(&self) -> [u8; HEADER_LEN] {
+        self.header.to_bytes()
+    }
+
+    /// Checks the header of `bytes`, and returns the payload which has been upgraded to
+    /// current version.
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        let header = match Header::parse(bytes) {
+            Some(header) if header.tag == self.header.tag => header,
+            _ => bail!("[{}] MAGIC number not match.", self.name),
+        };
+
+        let mut payload = Cow::Borrowed(&bytes[HEADER_LEN..]);
+        let mut version = header.version;
+
+        while version < self.header.version {
+            let upgrade = {
+                let upgrades = self.upgrades.read().unwrap();
+                match upgrades.iter().find(|v| v.0 == version) {
+                    Some(v) => v.1,
+                    None => break,
+                }
+            };
+
+            payload = Cow::Owned(upgrade(&payload)?);
+            version += 1;
+        }
+
+        if version != self.header.version {
+            let err = ::res::errors::Error::UnsupportedVersion {
+                format: self.name.to_owned(),
+                version: header.version,
+                expected: self.header.version,
+            };
+
+            return Err(err.into());
+        }
+
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn upgrade(payload: &[u8]) -> Result<Vec<u8>> {
+        Ok(payload.iter().map(|v| v + 1).collect())
+    }
+
+    #[test]
+    fn header() {
+        let header = Header::new(*b"VTEX", 0x010203);
+        assert_eq!(&header.to_bytes(), b"VTEX \x01\x02\x03");
+        assert_eq!(Header::parse(&header.to_bytes()), Some(header));
+        assert_eq!(Header::parse(b"VTEX"), None);
+        assert_eq!(Header::parse(b"VTEX_\x00\x00\x01"), None);
+    }
+
+    #[test]
+    fn decode() {
+        let format = Format::new("Test", *b"TEST", 3)
+            .with_upgrade(1, upgrade)
+            .with_upgrade(2, upgrade);
+
+        let mut bytes = Header::new(*b"TEST", 3).to_bytes().to_vec();
+        bytes.push(1);
+        assert_eq!(&format.decode(&bytes).unwrap()[..], &[1]);
+
+        let mut bytes = Header::new(*b"TEST", 1).to_bytes().to_vec();
+        bytes.push(1);
+        assert_eq!(&format.decode(&bytes).unwrap()[..], &[3]);
+
+        let bytes = Header::new(*b"TEST", 0).to_bytes();
+        let err = format.decode(&bytes).unwrap_err().to_string();
+        assert_eq!(err, "[Test] Unsupported version 0, expected 3.");
+
+        let bytes = Header::new(*b"TEST", 4).to_bytes();
+        assert!(format.decode(&bytes).is_err());
+
+        let bytes = Header::new(*b"ABCD", 3).to_bytes();
+        assert!(format.decode(&bytes).is_err());
+    }
+
+    #[test]
+    fn register_upgrade() {
+        let format = Format::new("Test", *b"TEST", 2);
+        let shared = format.clone();
+
+        let mut bytes = Header::new(*b"TEST", 1).to_bytes().to_vec();
+        bytes.push(1);
+        assert!(format.decode(&bytes).is_err());
+
+        shared.register_upgrade(1, upgrade).unwrap();
+        assert_eq!(&format.decode(&bytes).unwrap()[..], &[2]);
+        assert!(shared.register_upgrade(2, upgrade).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+//! A `ResourceGroup` tracks the loading of a set of resources, which is useful for loading
+//! screens.
+
+use std::sync::Arc;
+
+use failure::Error;
+use uuid::Uuid;
+
+use errors::*;
+use sched::latch::{LatchProbe, LatchWaitProbe};
+
+use super::{Location, Promise, ResourceSystemShared};
+
+/// The aggregate progress of a `ResourceGroup`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ResourceGroupProgress {
+    /// The number of resources that finished loading, including the failed ones.
+    pub loaded: usize,
+    /// The number of resources in the group.
+    pub total: usize,
+    /// The number of bytes that have been read.
+    pub bytes: usize,
+}
+
+/// A set of resources whose loadings are tracked as a whole. It could be waited on via
+/// `ScheduleSystemShared::wait_until`.
+///
+/// ```rust,ignore
+/// let mut group = ResourceGroup::new(ctx.res.clone());
+/// let texture = group.submit_from("res:crate.png", |v| video.create_texture_from(v))?;
+/// let mesh = group.submit_from("res:cube.obj", |v| video.create_mesh_from(v))?;
+///
+/// // Somewhere in the loading screen.
+/// let progress = group.progress();
+/// ```
+pub struct ResourceGroup {
+    res: Arc<ResourceSystemShared>,
+    // The requested resources, and whether their errors have been taken.
+    items: Vec<(Uuid, Arc<Promise>, bool)>,
+}
+
+impl ResourceGroup {
+    /// Creates a new and empty `ResourceGroup`.
+    pub fn new(res: Arc<ResourceSystemShared>) -> Self {
+        ResourceGroup {
+            res: res,
+            items: Vec::new(),
+        }
+    }
+
+    /// Submits the request of resource at readable location. The `func` should request the
+    /// resource, e.g. `|v| video.create_texture_from(v)`, and its result is returned.
+    pub fn submit_from<'a, T, F, L>(&mut self, location: L, func: F) -> Result<T>
+    where
+        L: Into<Location<'a>>,
+        F: FnOnce(Location<'a>) -> Result<T>,
+    {
+        let location = location.into();
+        let uuid = self.res.redirect(location).ok_or_else(|| {
+            format_err!(
+                "Undefined virtual filesystem with identifier {}.",
+                location.vfs()
+            )
+        })?;
+
+        self.submit(uuid, |_| func(location))
+    }
+
+    /// Submits the request of resource `uuid`. The `func` should request the resource, e.g.
+    /// `|v| video.create_texture_from_uuid(v)`, and its result is returned.
+    pub fn submit<T, F>(&mut self, uuid: Uuid, func: F) -> Result<T>
+    where
+        F: FnOnce(Uuid) -> Result<T>,
+    {
+        let promise = Arc::new(Promise::new());
+        self.res.observe(uuid, promise.clone());
+        self.items.push((uuid, promise.clone(), false));
+
+        let rsp = func(uuid);
+
+        // The resource has not been requested in `func`, it might be loaded already or
+        // failed before requesting.
+        if self.res.unobserve(uuid, &promise) {
+            promise.set(match rsp {
+                Ok(_) => Ok(()),
+                Err(ref err) => Err(format_err!("{}", err)),
+            });
+        }
+
+        rsp
+    }
+
+    /// Gets the aggregate progress of this group.
+    pub fn progress(&self) -> ResourceGroupProgress {
+        let mut progress = ResourceGroupProgress::default();
+        progress.total = self.items.len();
+
+        for &(_, ref promise, _) in &self.items {
+            if promise.is_set() {
+                progress.loaded += 1;
+            }
+
+            progress.bytes += promise.bytes();
+        }
+
+        progress
+    }
+
+    /// Takes the errors of resources that failed to load so far, each error is taken only
+    /// once.
+    pub fn take_errors(&mut self) -> Vec<(Uuid, Arc<Error>)> {
+        let mut errors = Vec::new();
+
+        for &mut (uuid, ref promise, ref mut taken) in &mut self.items {
+            if !*taken && promise.is_set() {
+                *taken = true;
+                if let Err(err) = promise.result() {
+                    errors.push((uuid, err));
+                }
+            }
+        }
+
+        errors
+    }
+
+    /// Gets the length of this `ResourceGroup`.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if there is no resource in this `ResourceGroup`.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl LatchProbe for ResourceGroup {
+    fn is_set(&self) -> bool {
+        self.items.iter().all(|v| v.1.is_set())
+    }
+}
+
+impl LatchWaitProbe for ResourceGroup {
+    fn wait(&self) {
+        for v in &self.items {
+            v.1.wait();
+        }
+    }
+}
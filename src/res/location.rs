@@ -3,19 +3,43 @@ use utils::HashValue;
 
 /// A `Location` describes where the source data for a resource is located. If two
 /// `Location`s are completely identical, they identify the same resource.
+///
+/// One source file might produces many resources, the sub-resources could be addressed with
+/// an optional fragment, like `res:models/ship.fbx#Hull` or `res:models/ship.fbx#mesh/2`. The
+/// fragment is a part of the key in manifest, so every sub-resource must be registered with
+/// its full path. It never falls back to the file without fragment.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Location<'a> {
     vfs: &'a str,
+    path: &'a str,
     filename: &'a str,
+    fragment: Option<&'a str>,
 }
 
 impl<'a> Location<'a> {
     pub fn new(location: &'a str) -> Result<Self> {
-        let (vfs, filename) = Self::schema(location)?;
+        let (vfs, path) = Self::schema(location)?;
+
+        let (filename, fragment) = match path.find('#') {
+            Some(index) => {
+                let (filename, fragment) = path.split_at(index);
+                if fragment.len() <= 1 {
+                    bail!(
+                        "{} does not match location schema [vfs:filename#fragment].",
+                        location
+                    );
+                }
+
+                (filename, Some(&fragment[1..]))
+            }
+            None => (path, None),
+        };
 
         Ok(Location {
             vfs: vfs,
+            path: path,
             filename: filename,
+            fragment: fragment,
         })
     }
 
@@ -25,6 +49,20 @@ impl<'a> Location<'a> {
         self.filename
     }
 
+    /// Gets the name of sub-resource if any. It's informational only, the sub-resource is
+    /// looked up in manifest with `path`.
+    #[inline]
+    pub fn fragment(&self) -> Option<&str> {
+        self.fragment
+    }
+
+    /// Gets the filename along with the fragment, which is used to lookup the resource in
+    /// manifest.
+    #[inline]
+    pub fn path(&self) -> &str {
+        self.path
+    }
+
     /// Gets the identifier of the virtual filesystem.
     #[inline]
     pub fn vfs(&self) -> &str {
@@ -55,14 +93,14 @@ impl<'a> From<&'a str> for Location<'a> {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct HashValueLocation {
     vfs: HashValue<str>,
-    filename: HashValue<str>,
+    path: HashValue<str>,
 }
 
 impl<'a> From<Location<'a>> for HashValueLocation {
     fn from(location: Location<'a>) -> Self {
         HashValueLocation {
             vfs: location.vfs.into(),
-            filename: location.filename.into(),
+            path: location.path.into(),
         }
     }
 }
@@ -84,6 +122,29 @@ mod test {
         assert!(Location::new("crate.png").is_err());
     }
 
+    #[test]
+    fn fragment() {
+        let loc = Location::new("res:models/ship.fbx#Hull").unwrap();
+        assert_eq!(loc.vfs(), "res");
+        assert_eq!(loc.filename(), "models/ship.fbx");
+        assert_eq!(loc.fragment(), Some("Hull"));
+        assert_eq!(loc.path(), "models/ship.fbx#Hull");
+
+        let loc = Location::new("res:models/ship.fbx#mesh/2").unwrap();
+        assert_eq!(loc.filename(), "models/ship.fbx");
+        assert_eq!(loc.fragment(), Some("mesh/2"));
+
+        let loc = Location::new("res:models/ship.fbx").unwrap();
+        assert_eq!(loc.fragment(), None);
+        assert_eq!(loc.path(), "models/ship.fbx");
+
+        assert!(Location::new("res:models/ship.fbx#").is_err());
+        assert_ne!(
+            Location::new("res:ship.fbx#Hull").unwrap(),
+            Location::new("res:ship.fbx#Deck").unwrap()
+        );
+    }
+
     #[test]
     fn container() {
         let l1 = Location::new("res:1").unwrap();
@@ -47,6 +47,21 @@
 //! "res:textures/crate.png" => "/Applications/My Game/resources/textures/2943B9386A274730A50702A904F384D5"
 //! ```
 //!
+//! One source file might produces many resources, such as the meshes and materials of a model.
+//! Those sub-resources are addressed with an optional fragment, which is registered in the manifest
+//! along with the filename, like `Manifest::add("models/ship.fbx#Hull", uuid, &[])`. Every
+//! sub-resource must be registered explicitly, the location never falls back to the file without
+//! fragment:
+//!
+//! ```sh
+//! "res:models/ship.fbx#Hull"
+//! "res:models/ship.fbx#mesh/2"
+//! ```
+//!
+//! The resources in a virtual filesystem could be enumerated with `ResourceSystemShared::list`, by
+//! a plain prefix like `levels/forest/` or a glob like `levels/*/*.prefab`. It's useful to preload
+//! all the resources of a level.
+//!
 //! This makes it easier to load data from other places than the local hard disc, like web servers,
 //! communicating with HTTP REST services or implementing more exotic ways to load data.
 //!
@@ -56,14 +71,59 @@
 //! filesystem, or other places if extended by pluggable `VFS`.
 //!
 //! The `VFS` trait has a pretty simple interface, since it should focus on games that load
-//! data asynchronously. A trival `Directory` is provided to supports local host filesystem.
-//! And it should be easy to add features like compression and encrpytion.
+//! data asynchronously. A trival `Directory` is provided to supports local host filesystem,
+//! and `Package` reads resources from a single packed archive which could be built with
+//! `Package::pack`. And `MemoryFS` keeps everything in memory, which is handy for tests and
+//! procedurally generated content. `Http` fetches resources from web servers, and writes them
+//! through into an optional local cache directory.
+//!
+//! It should be easy to add features like compression and encrpytion by wrapping another `VFS`.
+//! For example, `Compressed` decompresses the files which are encoded with
+//! `vfs::compressed::compress` transparently.
+//!
+//! ## Layered Mounts
+//!
+//! Drives could be layered by mounting with the same identifier and different priorities, which
+//! makes it possible to ship DLC and hotfixes that override specific resources without rebuilding
+//! the base bundle.
+//!
+//! ```rust,ignore
+//! engine.res.mount("res", Directory::new("base")?)?;
+//! engine.res.mount_with_priority("res", Directory::new("patch")?, 1)?;
+//! ```
+//!
+//! The drive with higher priority is searched first for both readable location and UUID lookup,
+//! and drives with the same priority are searched in the order they were mounted. Drives could be
+//! unmounted and remounted at runtime with `ResourceSystem::unmount`.
 //!
 //! ## Manifest
 //!
 //! Every VFS should have a `Manifest` file which could be used to locate resources in actual path
 //! from general UUID or readable identifier. The `Manifest` file is generated after the build
-//! process of `crayon-cli`.
+//! process of `crayon-cli`. The manifest is reloaded when a lookup misses and the manifest has
+//! been modified, so resources added at runtime are visible as well. The modification is checked
+//! at most once per `VFS::refresh_interval`.
+//!
+//! The items in `Manifest` could optionally store the checksum and size of their contents, which
+//! are verified before the bytes are passed to the `Loader`. The corrupted resources fail with
+//! `errors::Error::IntegrityViolated`, which names the UUID and filename of resource.
+//!
+//! ## Binary Formats
+//!
+//! The binary resources like textures, meshes and manifests start with a versioned header, which
+//! is described by `format::Format`. The payload of older versions is converted into the current
+//! version by the upgrades registered with `Format::with_upgrade` at load time, and the unknown
+//! versions fail with `errors::Error::UnsupportedVersion`.
+//!
+//! ## Saving
+//!
+//! Save games, screenshots and user settings could be written into the virtual filesystems which
+//! implement `WritableVFS`, like `Directory`, with `ResourceSystemShared::save`. The saved files
+//! are added into the `Manifest` of filesystem, so they could be loaded back like any other
+//! resources. The writable filesystems could be mounted without `Manifest` with
+//! `ResourceSystem::mount_writable`, and the `Manifest` is created when the first file is
+//! saved. `Directory` writes into a temporary file and renames it at last, so the files are
+//! never left partially written.
 //!
 //! # Registry
 //!
@@ -92,6 +152,63 @@
 //! drop the ownership of the resource. And when the last ownership to a given resource is dropped,
 //! the corresponding resource is also destroyed.
 //!
+//! Instead of deleting the handles manually, the ownership could also be taken over by a `Res`
+//! with `Registry::own`, or functions like `VideoSystemShared::own_texture`. Cloning a `Res`
+//! increases the reference count, and dropping it decreases. The plain `Copy` handles are still
+//! available with `Res::handle` for the hot paths.
+//!
+//! On constrained targets, the unused resources could be kept in cache until the budget set by
+//! `Registry::set_budget` is exceeded, and the least-recently-used ones are evicted first.
+//! Requesting a cached resource again revives it without loading.
+//!
+//! The resources held by a `Registry` could be enumerated with `Registry::entries`, along with
+//! their filenames, reference counts, states and estimated sizes. It's useful to report leaks at
+//! shutdown.
+//!
+//! ## Errors
+//!
+//! The error of a failed loading is preserved, `Registry::state` returns it along with the loading
+//! state of resource. And the failed resources could be loaded again with `Registry::retry`.
+//!
+//! ## Dependencies
+//!
+//! Resources could depend on other resources, which are recorded in the `Manifest`. The loading
+//! of a resource is splitted into two phases: `Loader::load` parses the bytes and requests the
+//! dependencies, and `Loader::finish` is called after all the dependencies have been loaded. For
+//! example, the meshes of a prefab are ready before `PrefabLoader::attach` runs. The dependencies
+//! recorded in the `Manifest` are requested along with the resource, and the loading waits for
+//! them without blocking the worker threads. Circular dependencies are reported as errors when
+//! the resource is requested.
+//!
+//! Concurrent requests of the same resource share the in-flight loading and its `Promise`, the
+//! bytes are read once and passed to the `Loader` of every request. A loader which requests one
+//! of its ancestors recursively gets an error with the chain of requests.
+//!
+//! ## Priorities & Cancellation
+//!
+//! Requests could be made with a priority through `ResourceSystemShared::load_from_uuid_with_priority`,
+//! the queued requests with higher priority are loaded first. The queued requests which are no longer
+//! needed could be cancelled with `ResourceSystemShared::cancel` before the bytes are read, and
+//! `Registry::delete` aborts the pending loading of a not-yet-loaded handle as well.
+//!
+//! ## Resource Groups
+//!
+//! A `ResourceGroup` tracks the loading of a set of resources as a whole. It reports the aggregate
+//! progress and per-item errors, and could be waited on via `ScheduleSystemShared::wait_until`,
+//! which makes it handy for loading screens.
+//!
+//! # Hot Reload
+//!
+//! It's common to iterate on resources while the game is running. When hot-reload is enabled
+//! with `ResourceSystem::set_hot_reload`, the `ResourceSystem` polls the mounted filesystems
+//! periodically, and reloads the resources whose backing files have been modified through the
+//! same `Loader` that requested them. The `Registry` swaps the value in place, so the `Handle`s
+//! you are holding keeps valid.
+//!
+
+pub mod errors;
+pub mod format;
+pub mod group;
 
 pub mod location;
 use self::location::Location;
@@ -103,16 +220,26 @@ pub mod registry;
 pub mod vfs;
 
 pub mod prelude {
+    pub use super::group::{ResourceGroup, ResourceGroupProgress};
     pub use super::location::Location;
     pub use super::promise::Promise;
-    pub use super::vfs::Directory;
+    pub use super::registry::Res;
+    pub use super::vfs::{Compressed, Directory, Http, MemoryFS, Package};
     pub use super::{ResourceSystem, ResourceSystemShared};
 }
 
-use std::sync::{Arc, RwLock};
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::{BinaryHeap, VecDeque};
+use std::path::{Component, Path};
+use std::sync::atomic::{self, AtomicUsize};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 use uuid::Uuid;
 
-use self::vfs::{VFSDriver, VFS};
+use self::vfs::{VFSDriver, VFSInstance, VFS};
+
+use failure::Error;
 
 use errors::*;
 use sched::ScheduleSystemShared;
@@ -122,6 +249,12 @@ use utils::FastHashMap;
 pub struct ResourceSystem {
     driver: Arc<RwLock<VFSDriver>>,
     shared: Arc<ResourceSystemShared>,
+    hot_reload: Option<HotReload>,
+}
+
+struct HotReload {
+    interval: Duration,
+    last: Instant,
 }
 
 impl ResourceSystem {
@@ -133,34 +266,140 @@ impl ResourceSystem {
             driver: driver.clone(),
             sched: sched,
             bufs: Arc::new(RwLock::new(Vec::new())),
-            promises: Arc::new(RwLock::new(FastHashMap::default())),
+            requests: Arc::new(RwLock::new(FastHashMap::default())),
+            pendings: Arc::new(Mutex::new(BinaryHeap::new())),
+            seq: AtomicUsize::new(0),
+            observers: Mutex::new(FastHashMap::default()),
+            watches: RwLock::new(None),
+            failures: Arc::new(RwLock::new(Failures::default())),
         });
 
         Ok(ResourceSystem {
             driver: driver,
             shared: shared,
+            hot_reload: None,
         })
     }
 
     /// Mount a file-system drive with identifier.
     pub fn mount<T, F>(&mut self, name: T, vfs: F) -> Result<()>
+    where
+        T: AsRef<str>,
+        F: VFS + 'static,
+    {
+        self.mount_with_priority(name, vfs, 0)
+    }
+
+    /// Mount a file-system drive with identifier and priority. Drives could be layered by
+    /// mounting with the same identifier, the one with higher priority overrides the others
+    /// for both readable location and uuid lookup.
+    pub fn mount_with_priority<T, F>(&mut self, name: T, vfs: F, priority: i32) -> Result<()>
+    where
+        T: AsRef<str>,
+        F: VFS + 'static,
+    {
+        let name = name.as_ref();
+        info!(
+            "Mounts virtual file system {} with priority {}.",
+            name, priority
+        );
+        self.driver
+            .write()
+            .unwrap()
+            .mount_with_priority(name, vfs, priority)
+    }
+
+    /// Mount a writable file-system drive with identifier, like the directory of save games.
+    /// Unlike `mount`, it could be mounted without manifest.
+    pub fn mount_writable<T, F>(&mut self, name: T, vfs: F) -> Result<()>
     where
         T: AsRef<str>,
         F: VFS + 'static,
     {
         let name = name.as_ref();
-        info!("Mounts virtual file system {}.", name);
-        self.driver.write().unwrap().mount(name, vfs)
+        info!("Mounts writable virtual file system {}.", name);
+        self.driver.write().unwrap().mount_writable(name, vfs)
+    }
+
+    /// Unmount all the file-system drives with identifier. The resources that have been
+    /// loaded are not affected.
+    pub fn unmount<T: AsRef<str>>(&mut self, name: T) -> Result<()> {
+        let name = name.as_ref();
+        info!("Unmounts virtual file system {}.", name);
+        self.driver.write().unwrap().unmount(name)
+    }
+
+    /// Unmount the file-system drive with identifier and priority.
+    pub fn unmount_with_priority<T: AsRef<str>>(&mut self, name: T, priority: i32) -> Result<()> {
+        let name = name.as_ref();
+        info!(
+            "Unmounts virtual file system {} with priority {}.",
+            name, priority
+        );
+        self.driver
+            .write()
+            .unwrap()
+            .unmount_with_priority(name, priority)
     }
 
     /// Returns the multi-thread friendly parts of `ResourceSystem`.
     pub fn shared(&self) -> Arc<ResourceSystemShared> {
         self.shared.clone()
     }
+
+    /// Enables the hot-reload of resources, the mounted filesystems will be polled every
+    /// `interval`. Passes `None` to disable it.
+    ///
+    /// Notes that only the resources requested after enabling hot-reload are watched.
+    pub fn set_hot_reload<T>(&mut self, interval: T)
+    where
+        T: Into<Option<Duration>>,
+    {
+        self.hot_reload = interval.into().map(|interval| HotReload {
+            interval: interval,
+            last: Instant::now(),
+        });
+
+        let mut watches = self.shared.watches.write().unwrap();
+        if self.hot_reload.is_some() {
+            if watches.is_none() {
+                *watches = Some(FastHashMap::default());
+            }
+        } else {
+            *watches = None;
+        }
+    }
+
+    /// Advance to next frame, reloads the modified resources if hot-reload is enabled.
+    pub fn advance(&mut self) {
+        if let Some(ref mut hot_reload) = self.hot_reload {
+            if hot_reload.last.elapsed() >= hot_reload.interval {
+                hot_reload.last = Instant::now();
+                self.shared.reload_modified();
+            }
+        }
+    }
 }
 
 pub trait Loader: Send + Sync + 'static {
+    /// Parses the bytes of resource. The dependencies of resource should be requested here.
     fn load(&self, file: &[u8]) -> Result<()>;
+
+    /// Finishes the loading after all the dependencies have been loaded.
+    fn finish(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Aborts the loading when the request failed before `Loader::load` is called, e.g. the
+    /// file could not be read or the request has been cancelled.
+    fn abort(&self, _: Error) {}
+
+    /// Identifies the owner of loader, like the `Registry` which requests the resource. The
+    /// modifications of a resource are watched once per owner, see
+    /// `ResourceSystemShared::unwatch`.
+    fn owner(&self) -> usize {
+        0
+    }
 }
 
 pub struct ResourceSystemShared {
@@ -168,7 +407,104 @@ pub struct ResourceSystemShared {
     sched: Arc<ScheduleSystemShared>,
 
     bufs: Arc<RwLock<Vec<Vec<u8>>>>,
-    promises: Arc<RwLock<FastHashMap<Uuid, Arc<Promise>>>>,
+    requests: Arc<RwLock<FastHashMap<Uuid, Request>>>,
+    pendings: Arc<Mutex<BinaryHeap<Pending>>>,
+    seq: AtomicUsize,
+    observers: Mutex<FastHashMap<Uuid, Vec<Arc<Promise>>>>,
+    watches: RwLock<Option<FastHashMap<Uuid, Vec<Watch>>>>,
+    // The errors of finished requests, which are reported to the waiters arrived after
+    // the requests have been removed.
+    failures: Arc<RwLock<Failures>>,
+}
+
+// The maximum number of errors of finished requests which are preserved.
+const MAX_FAILURES: usize = 256;
+
+// The errors of finished requests, the oldest ones are dropped if there are more than
+// `MAX_FAILURES` of them.
+#[derive(Default)]
+struct Failures {
+    errors: FastHashMap<Uuid, Arc<Error>>,
+    order: VecDeque<Uuid>,
+}
+
+impl Failures {
+    fn get(&self, uuid: Uuid) -> Option<&Arc<Error>> {
+        self.errors.get(&uuid)
+    }
+
+    fn insert(&mut self, uuid: Uuid, err: Arc<Error>) {
+        if self.errors.insert(uuid, err).is_none() {
+            self.order.push_back(uuid);
+        }
+
+        while self.order.len() > MAX_FAILURES {
+            let uuid = self.order.pop_front().unwrap();
+            self.errors.remove(&uuid);
+        }
+    }
+
+    fn remove(&mut self, uuid: Uuid) {
+        if self.errors.remove(&uuid).is_some() {
+            self.order.retain(|&v| v != uuid);
+        }
+    }
+}
+
+// The in-flight loading of resource, requests of the same resource are deduplicated and
+// share the same `Promise`.
+struct Request {
+    promise: Arc<Promise>,
+    loaders: Vec<Arc<dyn Loader>>,
+    // The resource whose loader requested this one.
+    parent: Option<Uuid>,
+    vfs: Arc<VFSInstance>,
+    dependencies: Vec<Uuid>,
+    priority: i32,
+    seq: usize,
+    started: bool,
+    // The promises of `ResourceGroup`s which are tracking this resource.
+    observers: Vec<Arc<Promise>>,
+    // The number of in-flight dependencies, the loading is suspended until all of them
+    // finished.
+    waiting: usize,
+    suspended: Option<Suspended>,
+    // The requests which are waiting for this one.
+    dependents: Vec<Uuid>,
+}
+
+// The queued request, the one with higher priority is executed first, and the earlier one
+// goes first if they have the same priority.
+#[derive(PartialEq, Eq)]
+struct Pending {
+    priority: i32,
+    seq: usize,
+    uuid: Uuid,
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+thread_local! {
+    // The resources whose loaders are being executed on current thread.
+    static LOADING: RefCell<Vec<Uuid>> = RefCell::new(Vec::new());
+}
+
+struct Watch {
+    owner: usize,
+    loader: Arc<dyn Loader>,
+    ts: SystemTime,
 }
 
 impl ResourceSystemShared {
@@ -177,8 +513,72 @@ impl ResourceSystemShared {
         self.driver
             .read()
             .unwrap()
-            .vfs(location.vfs())
-            .and_then(|vfs| vfs.redirect(location.filename()))
+            .redirect(location.vfs(), location.path())
+    }
+
+    /// Saves `bytes` into file at location asynchronously, the virtual filesystem must be
+    /// writable. The file is added into the manifest of virtual filesystem if it has not been
+    /// there, so it could be loaded back with the same location.
+    pub fn save<T>(&self, location: Location, bytes: T) -> Result<Arc<Promise>>
+    where
+        T: Into<Vec<u8>>,
+    {
+        if location.fragment().is_some() {
+            bail!("Could not save into sub-resource {}.", location.path());
+        }
+
+        let filename = location.filename().to_owned();
+        let valid = Path::new(&filename).components().all(|v| match v {
+            Component::Normal(_) => true,
+            _ => false,
+        });
+
+        if !valid {
+            bail!("{} is not a valid file location.", filename);
+        }
+
+        let vfs = self
+            .driver
+            .read()
+            .unwrap()
+            .writable_vfs(location.vfs())
+            .ok_or_else(|| {
+                format_err!(
+                    "Undefined writable virtual filesystem with identifier {}.",
+                    location.vfs()
+                )
+            })?;
+
+        let bytes = bytes.into();
+        let promise = Arc::new(Promise::new());
+        let tx = promise.clone();
+
+        self.sched.spawn(move || {
+            let rsp = vfs.save(&filename, &bytes).map(|_| ());
+            if rsp.is_ok() {
+                tx.set_bytes(bytes.len());
+            }
+
+            tx.set(rsp);
+        });
+
+        Ok(promise)
+    }
+
+    /// Lists the readable filenames and uuids of resources in the drives with identifier
+    /// `vfs`, sorted by filename. The `pattern` could be a plain prefix like `levels/forest/`,
+    /// or a glob like `levels/*/*.prefab`.
+    pub fn list<T: AsRef<str>>(&self, vfs: T, pattern: &str) -> Vec<(String, Uuid)> {
+        self.driver.read().unwrap().list(vfs.as_ref(), pattern)
+    }
+
+    /// Gets the readable filename of resource `uuid` from the manifest of mounted drives.
+    pub fn filename(&self, uuid: Uuid) -> Option<String> {
+        self.driver
+            .read()
+            .unwrap()
+            .vfs_from_uuid(uuid)
+            .and_then(|vfs| vfs.filename(uuid))
     }
 
     /// Loads a resource at readable location asynchronously.
@@ -194,55 +594,539 @@ impl ResourceSystemShared {
     }
 
     /// Loads a resource with uuid asynchronously.
+    #[inline]
     pub fn load_from_uuid<T: Loader>(&self, loader: T, uuid: Uuid) -> Result<Arc<Promise>> {
-        let vfs = self
-            .driver
-            .read()
-            .unwrap()
-            .vfs_from_uuid(uuid)
-            .ok_or_else(|| format_err!("Undefined uuid with {}", uuid))?;
+        self.load_from_uuid_with_priority(loader, uuid, 0)
+    }
+
+    /// Loads a resource with uuid and priority asynchronously. The queued requests with
+    /// higher priority are executed first.
+    pub fn load_from_uuid_with_priority<T: Loader>(
+        &self,
+        loader: T,
+        uuid: Uuid,
+        priority: i32,
+    ) -> Result<Arc<Promise>> {
+        let ts = SystemTime::now();
+        let loader = Arc::new(loader);
+        let latch = self.load_with(loader.clone(), uuid, priority)?;
+
+        if let Some(ref mut watches) = *self.watches.write().unwrap() {
+            let watch = Watch {
+                owner: loader.owner(),
+                loader: loader,
+                ts: ts,
+            };
+
+            // Replaces the previous watch of the same owner.
+            let watches = watches.entry(uuid).or_insert_with(Vec::new);
+            watches.retain(|v| v.owner != watch.owner);
+            watches.push(watch);
+        }
 
-        let latch = {
-            let mut promises = self.promises.write().unwrap();
-            if promises.contains_key(&uuid) {
-                bail!("Circular reference of resource {} found!", uuid);
+        Ok(latch)
+    }
+
+    /// Cancels the queued loading of resource `uuid`, the promise is set with error. Returns
+    /// false if the loading has been started already.
+    #[inline]
+    pub fn cancel(&self, uuid: Uuid) -> bool {
+        match self.take_if(uuid, |_| true) {
+            Some(request) => {
+                self.abort(uuid, request);
+                true
             }
+            None => false,
+        }
+    }
+
+    /// Removes the queued request of resource `uuid` if there is only one request of it. The
+    /// removed request should be aborted with `abort` afterwards, which could be done after
+    /// releasing the locks that are held by callers.
+    fn take_unshared(&self, uuid: Uuid) -> Option<Request> {
+        self.take_if(uuid, |v| v.loaders.len() == 1)
+    }
+
+    fn take_if<F>(&self, uuid: Uuid, predicate: F) -> Option<Request>
+    where
+        F: FnOnce(&Request) -> bool,
+    {
+        let mut requests = self.requests.write().unwrap();
+        match requests.get(&uuid) {
+            Some(v) if !v.started && predicate(v) => {}
+            _ => return None,
+        }
+
+        requests.remove(&uuid)
+    }
+
+    fn abort(&self, uuid: Uuid, request: Request) {
+        info!("Cancels the loading of resource {}.", uuid);
+        let err = || format_err!("The loading of {} has been cancelled.", uuid);
+
+        for v in request.loaders {
+            v.abort(err());
+        }
+
+        for v in request.observers {
+            v.set(Err(err()));
+        }
 
-            let latch = Arc::new(Promise::new());
-            promises.insert(uuid, latch.clone());
-            latch
+        request.promise.set(Err(err()));
+    }
+
+    /// Observes the loading of resource `uuid` with `promise`, which is set when the current
+    /// or next request of `uuid` finished.
+    pub(crate) fn observe(&self, uuid: Uuid, promise: Arc<Promise>) {
+        let mut requests = self.requests.write().unwrap();
+        if let Some(request) = requests.get_mut(&uuid) {
+            request.observers.push(promise);
+            return;
+        }
+
+        let mut observers = self.observers.lock().unwrap();
+        observers.entry(uuid).or_insert_with(Vec::new).push(promise);
+    }
+
+    /// Stops observing the loading of resource `uuid` if no request has been made since
+    /// `ResourceSystemShared::observe`. Returns true if it is removed.
+    pub(crate) fn unobserve(&self, uuid: Uuid, promise: &Arc<Promise>) -> bool {
+        let mut observers = self.observers.lock().unwrap();
+
+        let (removed, empty) = match observers.get_mut(&uuid) {
+            Some(v) => {
+                let len = v.len();
+                v.retain(|v| !Arc::ptr_eq(v, promise));
+                (v.len() != len, v.is_empty())
+            }
+            None => (false, false),
         };
 
-        let tx = latch.clone();
-        let bufs = self.bufs.clone();
-        let promises = self.promises.clone();
+        if empty {
+            observers.remove(&uuid);
+        }
+
+        removed
+    }
 
-        self.sched.spawn(move || {
-            let mut bytes = bufs.write().unwrap().pop().unwrap_or(Vec::new());
-            let uri = vfs.locate(uuid).unwrap();
+    /// Stops watching the modifications of resource `uuid` which is requested by the loaders
+    /// of `owner`, see `Loader::owner`. The watches of other owners are not affected.
+    pub fn unwatch(&self, uuid: Uuid, owner: usize) {
+        if let Some(ref mut watches) = *self.watches.write().unwrap() {
+            let empty = match watches.get_mut(&uuid) {
+                Some(v) => {
+                    v.retain(|v| v.owner != owner);
+                    v.is_empty()
+                }
+                None => false,
+            };
+
+            if empty {
+                watches.remove(&uuid);
+            }
+        }
+    }
+
+    fn reload_modified(&self) {
+        let mut reloads = Vec::new();
+
+        {
+            let driver = self.driver.read().unwrap();
+            let requests = self.requests.read().unwrap();
 
-            if let Err(err) = vfs.read_to_end(&uri, &mut bytes) {
-                tx.set(Err(err));
+            if let Some(ref mut watches) = *self.watches.write().unwrap() {
+                for (&uuid, watches) in watches.iter_mut() {
+                    if requests.contains_key(&uuid) {
+                        continue;
+                    }
+
+                    let location = driver
+                        .vfs_from_uuid(uuid)
+                        .and_then(|vfs| vfs.locate(uuid).map(|uri| (vfs, uri)));
+
+                    if let Some((vfs, uri)) = location {
+                        for watch in watches.iter_mut() {
+                            if vfs.modified_since(&uri, watch.ts) {
+                                watch.ts = SystemTime::now();
+                                reloads.push((uuid, watch.loader.clone()));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for (uuid, loader) in reloads {
+            info!("Reloads modified resource {}.", uuid);
+
+            if let Err(err) = self.load_with(loader, uuid, 0) {
+                warn!("{:?}", err);
+            }
+        }
+    }
+
+    fn load_with(
+        &self,
+        loader: Arc<dyn Loader>,
+        uuid: Uuid,
+        priority: i32,
+    ) -> Result<Arc<Promise>> {
+        let parent = LOADING.with(|v| v.borrow().last().cloned());
+        self.request(loader, uuid, parent, priority)
+    }
+
+    fn request(
+        &self,
+        loader: Arc<dyn Loader>,
+        uuid: Uuid,
+        parent: Option<Uuid>,
+        priority: i32,
+    ) -> Result<Arc<Promise>> {
+        let mut prepared = None;
+        if !self.requests.read().unwrap().contains_key(&uuid) {
+            let vfs = self.vfs_from_uuid(uuid)?;
+            let dependencies = vfs.dependencies(uuid)?;
+
+            // Kicks off the dependencies before the resource itself.
+            for &v in &dependencies {
+                if !self.requests.read().unwrap().contains_key(&v) {
+                    self.request(Arc::new(Prefetch), v, None, priority)?;
+                }
+            }
+
+            prepared = Some((vfs, dependencies));
+        }
+
+        let (latch, seq) = {
+            let mut requests = self.requests.write().unwrap();
+
+            if requests.contains_key(&uuid) {
+                if let Some(chain) = Self::ancestors(&requests, uuid, parent) {
+                    let chain: Vec<_> = chain.iter().map(|v| v.to_string()).collect();
+                    bail!("Circular reference found: {}.", chain.join(" -> "));
+                }
+
+                // Shares the in-flight loading.
+                let request = requests.get_mut(&uuid).unwrap();
+                request.loaders.push(loader);
+
+                if request.started || request.priority >= priority {
+                    return Ok(request.promise.clone());
+                }
+
+                // Requeues with higher priority and a new sequence number, so the stale
+                // entry in queue will be skipped since its sequence number does not match.
+                request.priority = priority;
+                request.seq = self.seq.fetch_add(1, atomic::Ordering::Relaxed);
+                (request.promise.clone(), request.seq)
             } else {
-                tx.set(loader.load(&bytes));
+                let (vfs, dependencies) = match prepared {
+                    Some(v) => v,
+                    None => {
+                        let vfs = self.vfs_from_uuid(uuid)?;
+                        let dependencies = vfs.dependencies(uuid)?;
+                        (vfs, dependencies)
+                    }
+                };
+
+                let latch = Arc::new(Promise::new());
+                let seq = self.seq.fetch_add(1, atomic::Ordering::Relaxed);
+                let request = Request {
+                    promise: latch.clone(),
+                    loaders: vec![loader],
+                    parent: parent,
+                    vfs: vfs,
+                    dependencies: dependencies,
+                    priority: priority,
+                    seq: seq,
+                    started: false,
+                    observers: self
+                        .observers
+                        .lock()
+                        .unwrap()
+                        .remove(&uuid)
+                        .unwrap_or_default(),
+                    waiting: 0,
+                    suspended: None,
+                    dependents: Vec::new(),
+                };
+
+                requests.insert(uuid, request);
+                self.failures.write().unwrap().remove(uuid);
+                (latch, seq)
             }
+        };
+
+        let pending = Pending {
+            priority: priority,
+            seq: seq,
+            uuid: uuid,
+        };
+
+        self.pendings.lock().unwrap().push(pending);
 
-            promises.write().unwrap().remove(&uuid);
-            bytes.clear();
-            bufs.write().unwrap().push(bytes);
+        let jobs = Jobs {
+            sched: self.sched.clone(),
+            bufs: self.bufs.clone(),
+            requests: self.requests.clone(),
+            failures: self.failures.clone(),
+        };
+
+        let pendings = self.pendings.clone();
+
+        // Every spawned job executes the queued request with the highest priority, instead
+        // of the one which spawns it.
+        self.sched.spawn(move || {
+            let pending = pendings.lock().unwrap().pop();
+            if let Some(pending) = pending {
+                jobs.execute(pending);
+            }
         });
 
         Ok(latch)
     }
 
-    /// Blocks current thread until the loading process of resource `uuid` finished.
+    fn vfs_from_uuid(&self, uuid: Uuid) -> Result<Arc<VFSInstance>> {
+        self.driver
+            .read()
+            .unwrap()
+            .vfs_from_uuid(uuid)
+            .ok_or_else(|| format_err!("Undefined uuid with {}", uuid))
+    }
+
+    // Returns the chain of requests if `uuid` is an ancestor of the request `parent`.
+    fn ancestors(
+        requests: &FastHashMap<Uuid, Request>,
+        uuid: Uuid,
+        parent: Option<Uuid>,
+    ) -> Option<Vec<Uuid>> {
+        let mut chain = Vec::new();
+        let mut cursor = parent;
+
+        while let Some(v) = cursor {
+            chain.push(v);
+            if v == uuid {
+                chain.reverse();
+                chain.push(uuid);
+                return Some(chain);
+            }
+
+            cursor = requests.get(&v).and_then(|v| v.parent);
+        }
+
+        None
+    }
+
+    /// Blocks current thread until the loading process of resource `uuid` finished. The errors
+    /// of the latest failed resources are preserved for the waiters arrived after finished.
     pub fn wait_until(&self, uuid: Uuid) -> Result<()> {
-        let promise = self.promises.read().unwrap().get(&uuid).cloned();
-        if let Some(promise) = promise {
-            self.sched.wait_until(promise.as_ref());
-            promise.take()
-        } else {
-            Ok(())
+        let promise = {
+            let requests = self.requests.read().unwrap();
+            match requests.get(&uuid) {
+                Some(v) => v.promise.clone(),
+                // The loading has been finished, reports the preserved error.
+                None => {
+                    return match self.failures.read().unwrap().get(uuid) {
+                        Some(err) => Err(format_err!("{}", err)),
+                        None => Ok(()),
+                    };
+                }
+            }
+        };
+
+        self.sched.wait_until(promise.as_ref());
+        promise.result().map_err(|err| format_err!("{}", err))
+    }
+}
+
+// Reads the dependencies ahead of the resource which requires them. The bytes are passed to
+// the loaders which request the dependencies afterwards, if the reading is still in-flight.
+struct Prefetch;
+
+impl Loader for Prefetch {
+    fn load(&self, _: &[u8]) -> Result<()> {
+        Ok(())
+    }
+}
+
+// The loading which is suspended until its in-flight dependencies finished.
+struct Suspended {
+    bytes: Vec<u8>,
+    rsp: Result<()>,
+    loaded: Vec<Arc<dyn Loader>>,
+}
+
+// The parts of `ResourceSystemShared` which are used by the spawned jobs.
+#[derive(Clone)]
+struct Jobs {
+    sched: Arc<ScheduleSystemShared>,
+    bufs: Arc<RwLock<Vec<Vec<u8>>>>,
+    requests: Arc<RwLock<FastHashMap<Uuid, Request>>>,
+    failures: Arc<RwLock<Failures>>,
+}
+
+impl Jobs {
+    fn execute(&self, pending: Pending) {
+        let uuid = pending.uuid;
+
+        let (vfs, tx) = {
+            let mut requests = self.requests.write().unwrap();
+            match requests.get_mut(&uuid) {
+                // Skips the cancelled or requeued requests.
+                Some(ref mut request) if request.seq == pending.seq && !request.started => {
+                    request.started = true;
+                    (request.vfs.clone(), request.promise.clone())
+                }
+                _ => return,
+            }
+        };
+
+        let mut bytes = self.bufs.write().unwrap().pop().unwrap_or(Vec::new());
+        // The manifest might have been refreshed since the request was scheduled.
+        let rsp = match vfs.locate(uuid) {
+            Some(uri) => vfs
+                .read_to_end(&uri, &mut bytes)
+                .and_then(|_| vfs.verify(uuid, &bytes)),
+            None => Err(format_err!("Undefined uuid with {}.", uuid)),
+        };
+        tx.set_bytes(bytes.len());
+
+        let suspended = Suspended {
+            bytes: bytes,
+            rsp: rsp,
+            loaded: Vec::new(),
+        };
+
+        self.resume(uuid, suspended);
+    }
+
+    // Passes the bytes to the loaders of all the requests arrived so far. The loading is
+    // suspended if some of the dependencies are still in-flight, and it's resumed by the
+    // last of them instead of blocking the worker thread.
+    fn resume(&self, uuid: Uuid, state: Suspended) {
+        let Suspended {
+            bytes,
+            mut rsp,
+            mut loaded,
+        } = state;
+
+        let (tx, observers, dependents) = loop {
+            // Takes the loaders of all the requests arrived so far, and removes the
+            // request if there are none left to be finished.
+            let loaders = {
+                let mut requests = self.requests.write().unwrap();
+                let request = requests.get_mut(&uuid).unwrap();
+                if request.loaders.is_empty() && loaded.is_empty() {
+                    let request = requests.remove(&uuid).unwrap();
+                    break (request.promise, request.observers, request.dependents);
+                }
+
+                ::std::mem::replace(&mut request.loaders, Vec::new())
+            };
+
+            if let Err(ref err) = rsp {
+                for loader in loaders {
+                    loader.abort(format_err!("{}", err));
+                }
+
+                continue;
+            }
+
+            LOADING.with(|v| v.borrow_mut().push(uuid));
+
+            for loader in loaders {
+                match loader.load(&bytes) {
+                    Ok(_) => loaded.push(loader),
+                    Err(err) => {
+                        if rsp.is_ok() {
+                            rsp = Err(err);
+                        }
+                    }
+                }
+            }
+
+            LOADING.with(|v| v.borrow_mut().pop());
+
+            // Waits until all the dependencies requested by the loaders finished.
+            {
+                let mut requests = self.requests.write().unwrap();
+                let dependencies = requests[&uuid].dependencies.clone();
+
+                let mut waiting = 0;
+                for v in dependencies {
+                    if let Some(dependency) = requests.get_mut(&v) {
+                        dependency.dependents.push(uuid);
+                        waiting += 1;
+                    }
+                }
+
+                if waiting > 0 {
+                    let request = requests.get_mut(&uuid).unwrap();
+                    request.waiting = waiting;
+                    request.suspended = Some(Suspended {
+                        bytes: bytes,
+                        rsp: rsp,
+                        loaded: loaded,
+                    });
+
+                    return;
+                }
+            }
+
+            LOADING.with(|v| v.borrow_mut().push(uuid));
+
+            for loader in loaded.drain(..) {
+                if let Err(err) = loader.finish() {
+                    if rsp.is_ok() {
+                        rsp = Err(err);
+                    }
+                }
+            }
+
+            LOADING.with(|v| v.borrow_mut().pop());
+        };
+
+        let rsp = rsp.map_err(Arc::new);
+        if let Err(ref err) = rsp {
+            self.failures.write().unwrap().insert(uuid, err.clone());
+        }
+
+        for v in observers {
+            v.set_bytes(bytes.len());
+            v.set_shared(rsp.clone());
+        }
+
+        tx.set_shared(rsp);
+
+        let mut bytes = bytes;
+        bytes.clear();
+        self.bufs.write().unwrap().push(bytes);
+
+        for v in dependents {
+            self.wake(v);
+        }
+    }
+
+    // Resumes the loading of `uuid` if the last of its in-flight dependencies finished.
+    fn wake(&self, uuid: Uuid) {
+        let suspended = {
+            let mut requests = self.requests.write().unwrap();
+            let request = match requests.get_mut(&uuid) {
+                Some(v) => v,
+                None => return,
+            };
+
+            request.waiting -= 1;
+            if request.waiting > 0 {
+                return;
+            }
+
+            request.suspended.take()
+        };
+
+        if let Some(suspended) = suspended {
+            let jobs = self.clone();
+            self.sched.spawn(move || jobs.resume(uuid, suspended));
         }
     }
 }
@@ -1,16 +1,20 @@
-use std::sync::{Condvar, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+use failure::Error;
 
 use errors::*;
 use sched::latch::{LatchProbe, LatchWaitProbe};
 
 enum PromiseState {
     NotReady,
-    Ok(Result<()>),
+    Ok(::std::result::Result<(), Arc<Error>>),
 }
 
 pub struct Promise {
     m: Mutex<PromiseState>,
     v: Condvar,
+    bytes: AtomicUsize,
 }
 
 impl Promise {
@@ -19,11 +23,28 @@ impl Promise {
         Promise {
             m: Mutex::new(PromiseState::NotReady),
             v: Condvar::new(),
+            bytes: AtomicUsize::new(0),
         }
     }
 
+    /// Gets the number of bytes that have been read or written.
+    #[inline]
+    pub fn bytes(&self) -> usize {
+        self.bytes.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub(crate) fn set_bytes(&self, bytes: usize) {
+        self.bytes.store(bytes, Ordering::Release);
+    }
+
     #[inline]
     pub(crate) fn set(&self, v: Result<()>) {
+        self.set_shared(v.map_err(Arc::new));
+    }
+
+    #[inline]
+    pub(crate) fn set_shared(&self, v: ::std::result::Result<(), Arc<Error>>) {
         {
             let mut guard = self.m.lock().unwrap();
             *guard = PromiseState::Ok(v);
@@ -32,11 +53,14 @@ impl Promise {
         self.v.notify_all();
     }
 
+    /// Gets the result of promise, the same result is returned to every waiter of it.
+    ///
+    /// Notes that it panics if the promise has not been set, wait on it before calling.
     #[inline]
-    pub fn take(&self) -> Result<()> {
-        let mut guard = self.m.lock().unwrap();
-        if let PromiseState::Ok(v) = ::std::mem::replace(&mut *guard, PromiseState::Ok(Ok(()))) {
-            v
+    pub fn result(&self) -> ::std::result::Result<(), Arc<Error>> {
+        let guard = self.m.lock().unwrap();
+        if let PromiseState::Ok(ref v) = *guard {
+            v.clone()
         } else {
             unreachable!();
         }
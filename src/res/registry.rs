@@ -1,22 +1,72 @@
 //! The `Registry` is a standardized resources manager that defines a set of interface for creation,
 //! destruction, sharing and lifetime management. It is used in all the built-in crayon modules.
 
-use std::sync::{Arc, RwLock};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, RwLock};
+
+use failure::Error;
 use uuid::Uuid;
 
 use errors::*;
 use utils::{FastHashMap, HandleLike, ObjectPool};
 
+use super::format::{Format, Upgrade};
 use super::{Loader, Location, ResourceSystemShared};
 
 pub trait Register: Send + Sync {
     type Handle: Send + Sync;
-    type Intermediate;
+    type Intermediate: Send;
     type Value: Send + Sync;
 
+    /// Parses the bytes into intermediate representation, the dependencies should be
+    /// requested here.
     fn load(&self, handle: Self::Handle, bytes: &[u8]) -> Result<Self::Intermediate>;
+    /// Attachs the intermediate representation after all the dependencies have been loaded.
     fn attach(&self, handle: Self::Handle, item: Self::Intermediate) -> Result<Self::Value>;
     fn detach(&self, handle: Self::Handle, value: Self::Value);
+
+    /// Detaches the previous value of a resource which has been reloaded in place. It's
+    /// called after the new value has been attached with the same handle.
+    fn replace(&self, handle: Self::Handle, prev: Self::Value) {
+        self.detach(handle, prev);
+    }
+
+    /// Estimates the size of value in bytes, which is used for debugging and the budget of
+    /// cached resources.
+    fn size(&self, _: &Self::Value) -> Option<usize> {
+        None
+    }
+
+    /// Gets the binary format of resources, whose upgrades could be registered at runtime
+    /// with `Registry::register_upgrade`.
+    fn format(&self) -> Option<&Format> {
+        None
+    }
+}
+
+/// The loading state of a resource in `Registry`.
+#[derive(Debug, Clone)]
+pub enum ResourceState {
+    /// The resource is being loaded.
+    NotReady,
+    /// The resource is ready to use.
+    Ready,
+    /// The resource failed to load.
+    Failed(Arc<Error>),
+}
+
+/// The snapshot of a resource in `Registry`, which is useful for debugging and leak reports.
+#[derive(Debug, Clone)]
+pub struct RegistryEntry<H> {
+    pub handle: H,
+    pub uuid: Option<Uuid>,
+    /// The readable filename of resource in the manifest.
+    pub filename: Option<String>,
+    /// The reference count of resource.
+    pub rc: u32,
+    pub state: ResourceState,
+    /// The estimated size of resource in bytes, reported by `Register::size`.
+    pub size: Option<usize>,
 }
 
 // The `Registry` is a standardized resources manager that defines a set of interface for creation,
@@ -33,6 +83,9 @@ impl<H: HandleLike + 'static, R: Register<Handle = H> + Clone + 'static> Registr
         let payload = Payload {
             items: ObjectPool::new(),
             redirects: FastHashMap::default(),
+            budget: 0,
+            cached: 0,
+            unused: VecDeque::new(),
         };
 
         Registry {
@@ -50,6 +103,7 @@ impl<H: HandleLike + 'static, R: Register<Handle = H> + Clone + 'static> Registr
             rc: 1,
             uuid: None,
             state: AsyncState::NotReady,
+            cached: None,
         };
 
         let mut payload = self.payload.write().unwrap();
@@ -78,13 +132,48 @@ impl<H: HandleLike + 'static, R: Register<Handle = H> + Clone + 'static> Registr
         self.create_from_uuid(uuid)
     }
 
+    /// Creates a resource from readable location with priority, the queued requests with
+    /// higher priority are loaded first.
+    pub fn create_from_with_priority<'a, T>(&'a self, location: T, priority: i32) -> Result<H>
+    where
+        T: Into<Location<'a>>,
+    {
+        let location = location.into();
+
+        let uuid = self.res.redirect(location).ok_or_else(|| {
+            format_err!(
+                "Undefined virtual filesystem with identifier {}.",
+                location.vfs()
+            )
+        })?;
+
+        self.create_from_uuid_with_priority(uuid, priority)
+    }
+
     /// Creates a resource from Uuid.
+    #[inline]
     pub fn create_from_uuid(&self, uuid: Uuid) -> Result<H> {
+        self.create_from_uuid_with_priority(uuid, 0)
+    }
+
+    /// Creates a resource from Uuid with priority.
+    pub fn create_from_uuid_with_priority(&self, uuid: Uuid, priority: i32) -> Result<H> {
         let handle = {
             let mut payload = self.payload.write().unwrap();
 
             if let Some(&handle) = payload.redirects.get(&uuid) {
-                payload.items.get_mut(handle).unwrap().rc += 1;
+                let cached = {
+                    let entry = payload.items.get_mut(handle).unwrap();
+                    entry.rc += 1;
+                    entry.cached.take()
+                };
+
+                // Revives the unused resource from cache.
+                if let Some(size) = cached {
+                    payload.cached -= size;
+                    payload.unused.retain(|v| v.index() != handle.index());
+                }
+
                 return Ok(handle);
             }
 
@@ -92,6 +181,7 @@ impl<H: HandleLike + 'static, R: Register<Handle = H> + Clone + 'static> Registr
                 rc: 1,
                 uuid: Some(uuid),
                 state: AsyncState::NotReady,
+                cached: None,
             };
 
             let handle = payload.items.create(entry);
@@ -99,15 +189,12 @@ impl<H: HandleLike + 'static, R: Register<Handle = H> + Clone + 'static> Registr
             handle
         };
 
-        let loader = RegistryLoader {
-            handle: handle,
-            register: self.register.clone(),
-            payload: self.payload.clone(),
-        };
-
-        match self.res.load_from_uuid(loader, uuid) {
+        match self.load(handle, uuid, priority) {
             Err(err) => {
-                self.payload.write().unwrap().items.free(handle).unwrap();
+                let mut payload = self.payload.write().unwrap();
+                payload.items.free(handle).unwrap();
+                payload.redirects.remove(&uuid);
+
                 return Err(err);
             }
             _ => {}
@@ -116,20 +203,129 @@ impl<H: HandleLike + 'static, R: Register<Handle = H> + Clone + 'static> Registr
         Ok(handle)
     }
 
+    /// Retries the loading of a resource which failed to load.
+    pub fn retry(&self, handle: H) -> Result<()> {
+        let uuid = {
+            let mut payload = self.payload.write().unwrap();
+            let entry = payload
+                .items
+                .get_mut(handle)
+                .ok_or_else(|| format_err!("Undefined handle."))?;
+
+            let uuid = match (entry.uuid, &entry.state) {
+                (Some(uuid), &AsyncState::Err(_)) => uuid,
+                _ => bail!("Only the resources failed to load from uuid could be retried."),
+            };
+
+            entry.state = AsyncState::NotReady;
+            uuid
+        };
+
+        if let Err(err) = self.load(handle, uuid, 0) {
+            let mut payload = self.payload.write().unwrap();
+            if let Some(entry) = payload.items.get_mut(handle) {
+                entry.state = AsyncState::Err(Arc::new(format_err!("{}", err)));
+            }
+
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    fn load(&self, handle: H, uuid: Uuid, priority: i32) -> Result<()> {
+        let loader = RegistryLoader {
+            handle: handle,
+            res: self.res.clone(),
+            register: self.register.clone(),
+            payload: self.payload.clone(),
+            item: Mutex::new(None),
+        };
+
+        self.res
+            .load_from_uuid_with_priority(loader, uuid, priority)
+            .map(|_| ())
+    }
+
     /// Deletes a resource from registery.
     pub fn delete(&self, handle: H) {
-        let mut payload = self.payload.write().unwrap();
+        Self::release(&self.res, &self.payload, &self.register, handle);
+    }
+
+    /// Takes over the ownership of `handle`, which will be deleted when the returned `Res`
+    /// and all its clones are dropped.
+    pub fn own(&self, handle: H) -> Res<H> {
+        let releaser = RegistryReleaser {
+            res: self.res.clone(),
+            payload: self.payload.clone(),
+            register: self.register.clone(),
+        };
+
+        Res {
+            handle: handle,
+            releaser: Arc::new(releaser),
+        }
+    }
+
+    // Identifies the loaders of this registry, see `Loader::owner`.
+    fn owner(payload: &RwLock<Payload<H, R>>) -> usize {
+        payload as *const _ as usize
+    }
 
-        let disposed = payload
+    fn retain(payload: &RwLock<Payload<H, R>>, handle: H) {
+        if let Some(entry) = payload.write().unwrap().items.get_mut(handle) {
+            entry.rc += 1;
+        }
+    }
+
+    fn release(
+        res: &ResourceSystemShared,
+        payload: &RwLock<Payload<H, R>>,
+        register: &R,
+        handle: H,
+    ) {
+        let owner = Self::owner(payload);
+        let mut payload = payload.write().unwrap();
+        let budget = payload.budget;
+
+        let (released, disposed, cached) = payload
             .items
             .get_mut(handle)
+            .filter(|entry| entry.rc > 0)
             .map(|entry| {
                 entry.rc -= 1;
-                match entry.state {
-                    AsyncState::Ok(_) | AsyncState::Err => entry.rc == 0,
-                    _ => false,
-                }
-            }).unwrap_or(false);
+                let (disposed, size) = match entry.state {
+                    AsyncState::Ok(ref value) if entry.rc == 0 => (true, register.size(value)),
+                    AsyncState::Err(_) => (entry.rc == 0, None),
+                    _ => (false, None),
+                };
+
+                // Keeps the unused resources which are loaded from uuid in cache, if they
+                // could fit in the budget.
+                let cached = match size {
+                    Some(size) if disposed && entry.uuid.is_some() && size <= budget => {
+                        entry.cached = Some(size);
+                        Some(size)
+                    }
+                    _ => None,
+                };
+
+                (entry.rc == 0, disposed, cached)
+            }).unwrap_or((false, false, None));
+
+        if let Some(size) = cached {
+            payload.cached += size;
+            payload.unused.push_back(handle);
+            Self::evict(res, owner, &mut payload, register);
+            return;
+        }
+
+        let uuid = payload.items.get(handle).and_then(|v| v.uuid);
+        if released {
+            if let Some(uuid) = uuid {
+                res.unwatch(uuid, owner);
+            }
+        }
 
         if disposed {
             let entry = payload.items.free(handle).unwrap();
@@ -139,11 +335,80 @@ impl<H: HandleLike + 'static, R: Register<Handle = H> + Clone + 'static> Registr
             }
 
             if let AsyncState::Ok(value) = entry.state {
-                self.register.detach(handle, value);
+                register.detach(handle, value);
+            }
+        } else if released {
+            if let Some(uuid) = uuid {
+                // Aborts the pending loading instead of discarding it after finished, unless
+                // it is shared with other requests. The request is taken with payload locked,
+                // so the entry could not be revived by others before we free it.
+                if let Some(request) = res.take_unshared(uuid) {
+                    payload.items.free(handle).unwrap();
+                    payload.redirects.remove(&uuid);
+
+                    drop(payload);
+                    res.abort(uuid, request);
+                }
             }
         }
     }
 
+    /// Sets the budget in bytes of the unused resources which are kept in cache. The
+    /// least-recently-used resources are evicted if the budget is exceeded. Resources are
+    /// not cached by default.
+    ///
+    /// The size of resource is reported by `Register::size`, those without size are never
+    /// cached.
+    pub fn set_budget(&self, budget: usize) {
+        let owner = Self::owner(&self.payload);
+        let mut payload = self.payload.write().unwrap();
+        payload.budget = budget;
+        Self::evict(&self.res, owner, &mut payload, &self.register);
+    }
+
+    /// Gets the bytes of the unused resources which are kept in cache.
+    #[inline]
+    pub fn cached(&self) -> usize {
+        self.payload.read().unwrap().cached
+    }
+
+    /// Registers an upgrade which converts the payload of `version` into `version + 1` to
+    /// the binary format of `Register`. Returns error if the `Register` has no binary format.
+    pub fn register_upgrade(&self, version: u32, upgrade: Upgrade) -> Result<()> {
+        match self.register.format() {
+            Some(format) => format.register_upgrade(version, upgrade),
+            None => bail!("The register has no binary format to upgrade."),
+        }
+    }
+
+    fn evict(res: &ResourceSystemShared, owner: usize, payload: &mut Payload<H, R>, register: &R) {
+        while payload.cached > payload.budget {
+            let handle = payload.unused.pop_front().unwrap();
+            Self::free(res, owner, payload, register, handle);
+        }
+    }
+
+    // Frees the unused resource which has been removed from `Payload::unused`.
+    fn free(
+        res: &ResourceSystemShared,
+        owner: usize,
+        payload: &mut Payload<H, R>,
+        register: &R,
+        handle: H,
+    ) {
+        let entry = payload.items.free(handle).unwrap();
+        payload.cached -= entry.cached.unwrap_or(0);
+
+        if let Some(uuid) = entry.uuid {
+            payload.redirects.remove(&uuid);
+            res.unwatch(uuid, owner);
+        }
+
+        if let AsyncState::Ok(value) = entry.state {
+            register.detach(handle, value);
+        }
+    }
+
     /// Gets the underlying `uuid` of handle.
     #[inline]
     pub fn uuid(&self, handle: H) -> Option<Uuid> {
@@ -152,28 +417,100 @@ impl<H: HandleLike + 'static, R: Register<Handle = H> + Clone + 'static> Registr
             .unwrap()
             .items
             .get(handle)
+            .filter(|v| v.cached.is_none())
             .and_then(|v| v.uuid)
     }
 
+    /// Gets the loading state of resource.
+    pub fn state(&self, handle: H) -> Option<ResourceState> {
+        self.payload
+            .read()
+            .unwrap()
+            .items
+            .get(handle)
+            .filter(|v| v.cached.is_none())
+            .map(|v| match v.state {
+                AsyncState::Ok(_) => ResourceState::Ready,
+                AsyncState::Err(ref err) => ResourceState::Failed(err.clone()),
+                AsyncState::NotReady => ResourceState::NotReady,
+            })
+    }
+
     /// Blocks current thread until the loading process of resource finished.
     pub fn wait_until(&self, handle: H) -> Result<()> {
-        if let Some(uuid) = self.uuid(handle) {
-            self.res.wait_until(uuid)
-        } else {
-            Ok(())
+        let rsp = match self.uuid(handle) {
+            Some(uuid) => self.res.wait_until(uuid),
+            None => Ok(()),
+        };
+
+        // The loading might have been finished before waiting, reports the preserved error.
+        match self.state(handle) {
+            Some(ResourceState::Failed(err)) => Err(format_err!("{}", err)),
+            // The previous value is kept if we failed to reload it.
+            Some(ResourceState::Ready) => Ok(()),
+            _ => rsp,
+        }
+    }
+
+    /// Enumerates all the resources in this `Registry`, except the unused ones in cache.
+    pub fn entries(&self) -> Vec<RegistryEntry<H>> {
+        let mut entries: Vec<_> = {
+            let payload = self.payload.read().unwrap();
+            payload
+                .items
+                .iter()
+                .filter_map(|handle| {
+                    let entry = payload.items.get(handle).unwrap();
+                    if entry.cached.is_some() {
+                        return None;
+                    }
+
+                    let (state, size) = match entry.state {
+                        AsyncState::Ok(ref value) => {
+                            (ResourceState::Ready, self.register.size(value))
+                        }
+                        AsyncState::Err(ref err) => (ResourceState::Failed(err.clone()), None),
+                        AsyncState::NotReady => (ResourceState::NotReady, None),
+                    };
+
+                    Some(RegistryEntry {
+                        handle: handle,
+                        uuid: entry.uuid,
+                        filename: None,
+                        rc: entry.rc,
+                        state: state,
+                        size: size,
+                    })
+                }).collect()
+        };
+
+        // Looks up filenames after releasing the payload, since the manifest might be
+        // reloaded while we are holding it.
+        for v in &mut entries {
+            v.filename = v.uuid.and_then(|uuid| self.res.filename(uuid));
         }
+
+        entries
     }
 
-    /// Gets the length of this `Registry`.
+    /// Gets the length of this `Registry`, the unused resources in cache are not counted.
     #[inline]
     pub fn len(&self) -> usize {
-        self.payload.read().unwrap().items.len()
+        let payload = self.payload.read().unwrap();
+        payload.items.len() - payload.unused.len()
     }
 
-    /// Returns true if the `Registry` contains a resource associated with `handle`.
+    /// Returns true if the `Registry` contains a resource associated with `handle`. The unused
+    /// resources in cache are not contained until they are revived.
     #[inline]
     pub fn contains(&self, handle: H) -> bool {
-        self.payload.read().unwrap().items.is_alive(handle)
+        self.payload
+            .read()
+            .unwrap()
+            .items
+            .get(handle)
+            .map(|v| v.cached.is_none())
+            .unwrap_or(false)
     }
 
     /// Gets the registery value if available.
@@ -184,6 +521,7 @@ impl<H: HandleLike + 'static, R: Register<Handle = H> + Clone + 'static> Registr
             .unwrap()
             .items
             .get(handle)
+            .filter(|v| v.cached.is_none())
             .and_then(|v| match v.state {
                 AsyncState::Ok(ref value) => Some(value),
                 _ => None,
@@ -191,14 +529,92 @@ impl<H: HandleLike + 'static, R: Register<Handle = H> + Clone + 'static> Registr
     }
 }
 
+/// A reference-counted owning handle, the resource is deleted when the last `Res` of it is
+/// dropped. The plain handle could be obtained with `Res::handle` for hot paths.
+pub struct Res<H: HandleLike> {
+    handle: H,
+    releaser: Arc<dyn Releaser<H>>,
+}
+
+impl<H: HandleLike> Res<H> {
+    /// Gets the plain handle, which is valid as long as this `Res` is alive.
+    #[inline]
+    pub fn handle(&self) -> H {
+        self.handle
+    }
+}
+
+impl<H: HandleLike> Clone for Res<H> {
+    fn clone(&self) -> Self {
+        self.releaser.retain(self.handle);
+
+        Res {
+            handle: self.handle,
+            releaser: self.releaser.clone(),
+        }
+    }
+}
+
+impl<H: HandleLike> Drop for Res<H> {
+    fn drop(&mut self) {
+        self.releaser.release(self.handle);
+    }
+}
+
+impl<H: HandleLike + PartialEq> PartialEq for Res<H> {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle == other.handle
+    }
+}
+
+impl<H: HandleLike + Eq> Eq for Res<H> {}
+
+impl<H: HandleLike + ::std::fmt::Debug> ::std::fmt::Debug for Res<H> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "Res({:?})", self.handle)
+    }
+}
+
+/// Manages the reference count of handles which are owned by `Res`.
+pub trait Releaser<H>: Send + Sync {
+    /// Increases the reference count of `handle`.
+    fn retain(&self, handle: H);
+    /// Decreases the reference count of `handle`, and deletes it if there are no others.
+    fn release(&self, handle: H);
+}
+
+struct RegistryReleaser<H: HandleLike, R: Register<Handle = H>> {
+    res: Arc<ResourceSystemShared>,
+    payload: Arc<RwLock<Payload<H, R>>>,
+    register: R,
+}
+
+impl<H, R> Releaser<H> for RegistryReleaser<H, R>
+where
+    H: HandleLike + 'static,
+    R: Register<Handle = H> + Clone + 'static,
+{
+    fn retain(&self, handle: H) {
+        Registry::<H, R>::retain(&self.payload, handle);
+    }
+
+    fn release(&self, handle: H) {
+        Registry::<H, R>::release(&self.res, &self.payload, &self.register, handle);
+    }
+}
+
 struct Payload<H: HandleLike, R: Register<Handle = H>> {
     items: ObjectPool<H, Entry<R::Value>>,
     redirects: FastHashMap<Uuid, H>,
+    budget: usize,
+    cached: usize,
+    // The unused resources in cache, from the least-recently-used to the most.
+    unused: VecDeque<H>,
 }
 
 enum AsyncState<T> {
     Ok(T),
-    Err,
+    Err(Arc<Error>),
     NotReady,
 }
 
@@ -206,21 +622,61 @@ struct Entry<T> {
     rc: u32,
     uuid: Option<Uuid>,
     state: AsyncState<T>,
+    // The size of resource if it's unused and kept in cache.
+    cached: Option<usize>,
 }
 
 struct RegistryLoader<H: HandleLike, R: Register<Handle = H>> {
     handle: H,
+    res: Arc<ResourceSystemShared>,
     register: R,
     payload: Arc<RwLock<Payload<H, R>>>,
+    item: Mutex<Option<R::Intermediate>>,
 }
 
-impl<H: HandleLike + 'static, R: Register<Handle = H> + 'static> Loader for RegistryLoader<H, R> {
+impl<H, R> Loader for RegistryLoader<H, R>
+where
+    H: HandleLike + 'static,
+    R: Register<Handle = H> + Clone + 'static,
+{
     fn load(&self, bytes: &[u8]) -> Result<()> {
-        let rsp = self.register.load(self.handle, bytes);
+        match self.register.load(self.handle, bytes) {
+            Ok(item) => {
+                *self.item.lock().unwrap() = Some(item);
+                Ok(())
+            }
+            Err(err) => self.update(Err(err)),
+        }
+    }
+
+    fn abort(&self, err: Error) {
+        let _ = self.update(Err(err));
+    }
 
+    fn owner(&self) -> usize {
+        &*self.payload as *const _ as usize
+    }
+
+    fn finish(&self) -> Result<()> {
+        let item = self.item.lock().unwrap().take().unwrap();
+        self.update(Ok(item))
+    }
+}
+
+impl<H, R> RegistryLoader<H, R>
+where
+    H: HandleLike + 'static,
+    R: Register<Handle = H> + Clone + 'static,
+{
+    fn update(&self, rsp: Result<R::Intermediate>) -> Result<()> {
         {
             let mut payload = self.payload.write().unwrap();
-            let disposed = payload.items.get(self.handle).unwrap().rc <= 0;
+            let disposed = match payload.items.get(self.handle) {
+                // The unused resources in cache are reloaded in place.
+                Some(entry) => entry.rc == 0 && entry.cached.is_none(),
+                // The resource has been deleted before a reloading finished.
+                None => return Ok(()),
+            };
 
             if disposed {
                 let entry = payload.items.free(self.handle).unwrap();
@@ -233,27 +689,66 @@ impl<H: HandleLike + 'static, R: Register<Handle = H> + 'static> Loader for Regi
                     self.register.detach(self.handle, value);
                 }
             } else {
-                match rsp {
-                    Ok(item) => match self.register.attach(self.handle, item) {
-                        Ok(value) => {
-                            payload.items.get_mut(self.handle).unwrap().state =
-                                AsyncState::Ok(value);
-                        }
-                        Err(err) => {
-                            warn!("{:?}", err);
-                            payload.items.get_mut(self.handle).unwrap().state = AsyncState::Err;
-                            return Err(err);
+                let entry = payload.items.get_mut(self.handle).unwrap();
+
+                let rsp = rsp.and_then(|item| self.register.attach(self.handle, item));
+
+                let resized = match rsp {
+                    Ok(value) => {
+                        // The unused resource in cache might be resized by reloading.
+                        let resized = entry.cached.map(|_| self.register.size(&value));
+
+                        // Detaches the previous value if we are reloading it in place.
+                        let prev = ::std::mem::replace(&mut entry.state, AsyncState::Ok(value));
+                        if let AsyncState::Ok(value) = prev {
+                            self.register.replace(self.handle, value);
                         }
-                    },
+
+                        resized
+                    }
                     Err(err) => {
                         warn!("{:?}", err);
-                        payload.items.get_mut(self.handle).unwrap().state = AsyncState::Err;
-                        return Err(err);
+
+                        let rsp = format_err!("{}", err);
+
+                        // Keeps the previous value if we failed to reload it.
+                        match entry.state {
+                            AsyncState::Ok(_) => {}
+                            _ => entry.state = AsyncState::Err(Arc::new(err)),
+                        }
+
+                        return Err(rsp);
                     }
+                };
+
+                if let Some(size) = resized {
+                    self.resize(&mut payload, size);
                 }
             }
         }
 
         Ok(())
     }
+
+    // Updates the size of the unused resource in cache, it's evicted if it could not be
+    // kept in cache any longer.
+    fn resize(&self, payload: &mut Payload<H, R>, size: Option<usize>) {
+        let owner = &*self.payload as *const _ as usize;
+        let prev = payload.items.get_mut(self.handle).unwrap().cached.take();
+        payload.cached -= prev.unwrap();
+
+        match size {
+            Some(size) => {
+                payload.items.get_mut(self.handle).unwrap().cached = Some(size);
+                payload.cached += size;
+            }
+            None => {
+                let handle = self.handle;
+                payload.unused.retain(|v| v.index() != handle.index());
+                Registry::free(&self.res, owner, payload, &self.register, handle);
+            }
+        }
+
+        Registry::evict(&self.res, owner, payload, &self.register);
+    }
 }
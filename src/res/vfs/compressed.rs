@@ -0,0 +1,172 @@
+//! A transparent compression layer for any virtual filesystem.
+//!
+//! Every compressed file starts with a small header, which consists of the `MAGIC` number,
+//! the codec and the length of uncompressed bytes. Files without this header are passed
+//! through untouched, so its perfectly fine to mix compressed and plain resources.
+//!
+//! The writable extension of the wrapped virtual filesystem is forwarded, the files are written
+//! as is without compression.
+
+use std::io::Cursor;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use bincode;
+use miniz_oxide;
+
+use errors::*;
+
+use super::{WritableVFS, VFS};
+
+pub const MAGIC: [u8; 8] = [
+    'C' as u8, 'M' as u8, 'P' as u8, 'R' as u8, ' ' as u8, 0, 0, 1,
+];
+
+/// The length of compression header in bytes.
+pub const HEADER_LEN: usize = 17;
+
+// The maximum compression ratio of DEFLATE.
+const MAX_DEFLATE_RATIO: u64 = 1032;
+
+/// The compression algorithms.
+#[repr(u8)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Codec {
+    /// The raw DEFLATE format, which is described in RFC 1951.
+    Deflate = 1,
+}
+
+impl Codec {
+    fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            1 => Some(Codec::Deflate),
+            _ => None,
+        }
+    }
+}
+
+/// Compresses `bytes` with `codec`, and prepends the compression header.
+pub fn compress(codec: Codec, bytes: &[u8]) -> Vec<u8> {
+    let payload = match codec {
+        // A single empty block, since `miniz_oxide` never finishes compressing empty input.
+        Codec::Deflate if bytes.is_empty() => vec![0x03, 0x00],
+        Codec::Deflate => miniz_oxide::deflate::compress_to_vec(bytes, 6),
+    };
+
+    let mut buf = Vec::with_capacity(HEADER_LEN + payload.len());
+    buf.extend_from_slice(&MAGIC);
+    buf.push(codec as u8);
+    bincode::serialize_into(&mut buf, &(bytes.len() as u64)).unwrap();
+    buf.extend_from_slice(&payload);
+    buf
+}
+
+/// Decompresses `bytes` which starts with the compression header.
+pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>> {
+    if !is_compressed(bytes) {
+        bail!("[Compressed] MAGIC number not match.");
+    }
+
+    let len: u64 = bincode::deserialize(&bytes[9..HEADER_LEN])?;
+    let payload = &bytes[HEADER_LEN..];
+
+    match Codec::from_u8(bytes[8]) {
+        Some(Codec::Deflate) => inflate(payload, len),
+        None => bail!("[Compressed] Undefined codec {}.", bytes[8]),
+    }
+}
+
+// Inflates `payload` into a buffer with `len` bytes and an extra one which detects the
+// overflow, the decompression stops once the buffer is full. So a forged header could never
+// make us allocate more than it claims.
+fn inflate(payload: &[u8], len: u64) -> Result<Vec<u8>> {
+    use miniz_oxide::inflate::core::{self, inflate_flags, DecompressorOxide};
+    use miniz_oxide::inflate::TINFLStatus;
+
+    if len > payload.len() as u64 * MAX_DEFLATE_RATIO {
+        bail!(
+            "[Compressed] Uncompressed length {} in header exceeds the limit of DEFLATE.",
+            len
+        );
+    }
+
+    let mut buf = vec![0; len as usize + 1];
+    let mut decomp = Box::<DecompressorOxide>::default();
+    let flags = inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+
+    let (status, _, n) = {
+        let mut cursor = Cursor::new(&mut buf[..]);
+        core::decompress(&mut decomp, payload, &mut cursor, flags)
+    };
+
+    match status {
+        TINFLStatus::Done if n as u64 == len => {
+            buf.truncate(n);
+            Ok(buf)
+        }
+        TINFLStatus::Done | TINFLStatus::HasMoreOutput => bail!(
+            "[Compressed] Uncompressed length does not match the header {}.",
+            len
+        ),
+        _ => bail!("[Compressed] Failed to inflate, {:?}.", status),
+    }
+}
+
+/// Returns true if `bytes` starts with the compression header.
+#[inline]
+pub fn is_compressed(bytes: &[u8]) -> bool {
+    bytes.len() >= HEADER_LEN && &bytes[0..8] == &MAGIC[..]
+}
+
+/// A wrapping virtual filesystem that decompresses files before the bytes reach the
+/// `Loader`.
+pub struct Compressed<V: VFS> {
+    vfs: V,
+}
+
+impl<V: VFS> Compressed<V> {
+    pub fn new(vfs: V) -> Self {
+        Compressed { vfs: vfs }
+    }
+}
+
+impl<V: VFS> VFS for Compressed<V> {
+    fn read_to_end(&self, location: &Path, buf: &mut Vec<u8>) -> Result<usize> {
+        let start = buf.len();
+        let len = self.vfs.read_to_end(location, buf)?;
+
+        if !is_compressed(&buf[start..]) {
+            return Ok(len);
+        }
+
+        let bytes = decompress(&buf[start..])?;
+        buf.truncate(start);
+        buf.extend_from_slice(&bytes);
+        Ok(bytes.len())
+    }
+
+    #[inline]
+    fn is_dir(&self, location: &Path) -> bool {
+        self.vfs.is_dir(location)
+    }
+
+    #[inline]
+    fn exists(&self, location: &Path) -> bool {
+        self.vfs.exists(location)
+    }
+
+    #[inline]
+    fn modified_since(&self, location: &Path, ts: SystemTime) -> bool {
+        self.vfs.modified_since(location, ts)
+    }
+
+    #[inline]
+    fn refresh_interval(&self) -> Duration {
+        self.vfs.refresh_interval()
+    }
+
+    #[inline]
+    fn writable(&self) -> Option<&dyn WritableVFS> {
+        self.vfs.writable()
+    }
+}
@@ -1,11 +1,15 @@
 use std::fs;
-use std::io::Read;
-use std::path::{Path, PathBuf};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::SystemTime;
 
 use errors::*;
 
-use super::VFS;
+use super::{WritableVFS, VFS};
+
+// The sequence of temporary files, which makes concurrent writes never collide.
+static SEQ: AtomicUsize = AtomicUsize::new(0);
 
 pub struct Directory {
     root: PathBuf,
@@ -23,6 +27,19 @@ impl Directory {
             bail!("Disk file-system must be associated with a readable directory.");
         }
     }
+
+    // Resolves the writable location relative to root. Absolute paths and parent components
+    // are rejected, so nothing outside the root could be modified.
+    fn resolve(&self, location: &Path) -> Result<PathBuf> {
+        for v in location.components() {
+            match v {
+                Component::Normal(_) => {}
+                _ => bail!("{:?} is not a valid file location.", location),
+            }
+        }
+
+        Ok(self.root.join(location))
+    }
 }
 
 impl VFS for Directory {
@@ -42,7 +59,63 @@ impl VFS for Directory {
     }
 
     fn modified_since(&self, location: &Path, ts: SystemTime) -> bool {
-        let metadata = self.root.join(location).metadata().unwrap();
-        ts == metadata.modified().unwrap()
+        self.root
+            .join(location)
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| modified > ts)
+            .unwrap_or(false)
+    }
+
+    fn writable(&self) -> Option<&dyn WritableVFS> {
+        Some(self)
+    }
+}
+
+impl WritableVFS for Directory {
+    /// Writes into a temporary file first, and renames it to the destination after all the
+    /// bytes have been flushed. So the destination is never left partially written.
+    fn write(&self, location: &Path, bytes: &[u8]) -> Result<()> {
+        let path = self.resolve(location)?;
+        let filename = path
+            .file_name()
+            .ok_or_else(|| format_err!("{:?} is not a valid file location.", location))?
+            .to_string_lossy()
+            .into_owned();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let seq = SEQ.fetch_add(1, Ordering::Relaxed);
+        let tmp = path.with_file_name(format!(".{}.{}.tmp", filename, seq));
+
+        let rsp = fs::File::create(&tmp)
+            .and_then(|mut file| {
+                file.write_all(bytes)?;
+                file.sync_all()
+            }).and_then(|_| fs::rename(&tmp, &path));
+
+        if rsp.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+
+        Ok(rsp?)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let from = self.resolve(from)?;
+        let to = self.resolve(to)?;
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::rename(from, to)?;
+        Ok(())
+    }
+
+    fn remove(&self, location: &Path) -> Result<()> {
+        fs::remove_file(self.resolve(location)?)?;
+        Ok(())
     }
 }
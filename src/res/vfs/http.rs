@@ -0,0 +1,251 @@
+//! A virtual filesystem that fetches resources from web servers with a minimal HTTP/1.1
+//! client.
+//!
+//! Since resources are addressed by their UUIDs, they are immutable and could be written
+//! through into a local cache directory safely. The cached files are written atomically, so
+//! an interrupted fetch never leaves a partial file in cache, and the fetched bytes are used
+//! even if the cache could not be written. The manifest is always fetched from server first,
+//! and the cached one is used only if the server is unreachable.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::str;
+use std::time::{Duration, SystemTime};
+
+use errors::*;
+
+use super::manifest;
+use super::{Directory, WritableVFS, VFS};
+
+/// The timeout of reading and writing to the server.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A virtual filesystem that fetches the manifest and UUID-addressed resources from a base
+/// URL, like `http://localhost:8080/resources`.
+pub struct Http {
+    host: String,
+    base: String,
+    cache: Option<Directory>,
+}
+
+impl Http {
+    /// Creates a new `Http` with base URL.
+    pub fn new<T: AsRef<str>>(url: T) -> Result<Self> {
+        let url = url.as_ref();
+        info!("Creates http based virtual file system at {}.", url);
+
+        let (host, base) = Self::parse(url)?;
+        Ok(Http {
+            host: host,
+            base: base,
+            cache: None,
+        })
+    }
+
+    /// Creates a new `Http` with base URL, and writes the fetched files through into local
+    /// `cache` directory.
+    pub fn with_cache<T1, T2>(url: T1, cache: T2) -> Result<Self>
+    where
+        T1: AsRef<str>,
+        T2: Into<PathBuf>,
+    {
+        let cache = cache.into();
+        fs::create_dir_all(&cache)?;
+
+        let mut http = Self::new(url)?;
+        http.cache = Some(Directory::new(cache)?);
+        Ok(http)
+    }
+
+    fn parse(url: &str) -> Result<(String, String)> {
+        if !url.starts_with("http://") {
+            bail!(
+                "[Http] {} does not match url schema [http://host[:port][/path]].",
+                url
+            );
+        }
+
+        let url = &url["http://".len()..];
+        let (host, base) = match url.find('/') {
+            Some(index) => url.split_at(index),
+            None => (url, ""),
+        };
+
+        if host.is_empty() {
+            bail!("[Http] Undefined host in url {}.", url);
+        }
+
+        let host = if host.contains(':') {
+            host.to_owned()
+        } else {
+            format!("{}:80", host)
+        };
+
+        Ok((host, base.trim_end_matches('/').to_owned()))
+    }
+
+    fn path(&self, location: &Path) -> Result<String> {
+        let location = location
+            .to_str()
+            .ok_or_else(|| format_err!("[Http] {:?} is not a valid url path.", location))?;
+
+        Ok(format!("{}/{}", self.base, location.replace('\\', "/")))
+    }
+
+    fn request(&self, method: &str, location: &Path) -> Result<(u32, Vec<u8>)> {
+        let mut stream = TcpStream::connect(self.host.as_str())?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nAccept-Encoding: identity\r\nConnection: close\r\n\r\n",
+            method,
+            self.path(location)?,
+            self.host
+        )?;
+        stream.flush()?;
+
+        let mut rsp = Vec::new();
+        stream.read_to_end(&mut rsp)?;
+
+        let pos = rsp
+            .windows(4)
+            .position(|v| v == b"\r\n\r\n")
+            .ok_or_else(|| format_err!("[Http] Malformed response header."))?;
+
+        let header = str::from_utf8(&rsp[..pos])?;
+        let mut lines = header.split("\r\n");
+
+        // Status-Line = HTTP-Version SP Status-Code SP Reason-Phrase
+        let status = lines
+            .next()
+            .and_then(|v| v.split_whitespace().nth(1))
+            .and_then(|v| v.parse::<u32>().ok())
+            .ok_or_else(|| format_err!("[Http] Malformed response status line."))?;
+
+        let mut len = None;
+        let mut chunked = false;
+        for line in lines {
+            if let Some(index) = line.find(':') {
+                let (k, v) = line.split_at(index);
+                let v = v[1..].trim();
+
+                if k.eq_ignore_ascii_case("Content-Length") {
+                    len = Some(v.parse::<usize>()?);
+                } else if k.eq_ignore_ascii_case("Transfer-Encoding") {
+                    chunked = v.eq_ignore_ascii_case("chunked");
+                }
+            }
+        }
+
+        let body = &rsp[pos + 4..];
+        let body = if method == "HEAD" {
+            Vec::new()
+        } else if chunked {
+            Self::dechunk(body)?
+        } else if let Some(len) = len {
+            if body.len() < len {
+                bail!("[Http] Response body is truncated.");
+            }
+
+            body[..len].to_vec()
+        } else {
+            body.to_vec()
+        };
+
+        Ok((status, body))
+    }
+
+    fn dechunk(mut body: &[u8]) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+
+        loop {
+            let pos = body
+                .windows(2)
+                .position(|v| v == b"\r\n")
+                .ok_or_else(|| format_err!("[Http] Malformed chunk."))?;
+
+            let size = str::from_utf8(&body[..pos])?;
+            let size = size.split(';').next().unwrap().trim();
+            let size = usize::from_str_radix(size, 16)?;
+
+            body = &body[pos + 2..];
+            if size == 0 {
+                return Ok(buf);
+            }
+
+            if body.len() < size + 2 {
+                bail!("[Http] Response body is truncated.");
+            }
+
+            buf.extend_from_slice(&body[..size]);
+            body = &body[size + 2..];
+        }
+    }
+
+    fn fetch(&self, location: &Path, buf: &mut Vec<u8>) -> Result<usize> {
+        let (status, body) = self.request("GET", location)?;
+        if status != 200 {
+            bail!(
+                "[Http] Failed to fetch {:?}, status code {}.",
+                location,
+                status
+            );
+        }
+
+        // The cache is best-effort, the download still succeeds if we failed to write it.
+        if let Some(ref cache) = self.cache {
+            if let Err(err) = cache.write(location, &body) {
+                warn!("[Http] Failed to cache {:?}. {:?}", location, err);
+            }
+        }
+
+        buf.extend_from_slice(&body);
+        Ok(body.len())
+    }
+}
+
+impl VFS for Http {
+    fn read_to_end(&self, location: &Path, buf: &mut Vec<u8>) -> Result<usize> {
+        if let Some(ref cache) = self.cache {
+            if location == Path::new(manifest::NAME) {
+                match self.fetch(location, buf) {
+                    Ok(len) => return Ok(len),
+                    Err(err) => {
+                        warn!("{:?}", err);
+                        return cache.read_to_end(location, buf);
+                    }
+                }
+            }
+
+            if cache.exists(location) {
+                return cache.read_to_end(location, buf);
+            }
+        }
+
+        self.fetch(location, buf)
+    }
+
+    fn is_dir(&self, _: &Path) -> bool {
+        false
+    }
+
+    fn exists(&self, location: &Path) -> bool {
+        if let Some(ref cache) = self.cache {
+            if cache.exists(location) {
+                return true;
+            }
+        }
+
+        self.request("HEAD", location)
+            .map(|(status, _)| status == 200)
+            .unwrap_or(false)
+    }
+
+    fn modified_since(&self, _: &Path, _: SystemTime) -> bool {
+        false
+    }
+}
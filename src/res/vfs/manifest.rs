@@ -1,18 +1,47 @@
 //! Manifest for all the AssetBundles in the build.
+//!
+//! Besides the manifest generated by `crayon-cli`, it could also be authored from Rust with
+//! `Manifest::add` and serialized with `Manifest::save_to`.
+//!
+//! The items could optionally store the checksum and size of their contents, which are
+//! verified after the bytes have been read and before they are passed to the `Loader`.
 
-use std::io::Read;
+use std::io::{Read, Write};
 use std::path::PathBuf;
 
 use bincode;
 use uuid::Uuid;
 
 use errors::*;
+use res::format::Format;
 use utils::{DataBuffer, DataBufferPtr, FastHashMap, HashValue};
 
 pub const NAME: &'static str = ".MANIFEST";
-pub const MAGIC: [u8; 8] = [
-    'M' as u8, 'N' as u8, 'F' as u8, 'T' as u8, ' ' as u8, 0, 0, 1,
-];
+
+/// The binary format of manifest.
+pub fn format() -> Format {
+    Format::new("ManifestLoader", *b"MNFT", 2).with_upgrade(1, upgrade_v1)
+}
+
+// Upgrades the manifest without integrity checks.
+fn upgrade_v1(payload: &[u8]) -> Result<Vec<u8>> {
+    let v1: ManifestV1 = bincode::deserialize(payload)?;
+
+    let mut manifest = Manifest::new();
+    manifest.buf = v1.buf;
+    manifest.items = v1
+        .items
+        .into_iter()
+        .map(|v| ManifestItem {
+            filename: v.filename,
+            dependencies: v.dependencies,
+            uuid: v.uuid,
+            hash: None,
+            size: None,
+        }).collect();
+
+    Ok(bincode::serialize(&manifest)?)
+}
 
 /// A manifest item in the build.
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
@@ -20,6 +49,23 @@ pub struct ManifestItem {
     pub filename: DataBufferPtr<str>,
     pub dependencies: DataBufferPtr<[usize]>,
     pub uuid: Uuid,
+    /// The `checksum` of contents.
+    pub hash: Option<u64>,
+    /// The size of contents in bytes.
+    pub size: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestItemV1 {
+    filename: DataBufferPtr<str>,
+    dependencies: DataBufferPtr<[usize]>,
+    uuid: Uuid,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestV1 {
+    items: Vec<ManifestItemV1>,
+    buf: DataBuffer,
 }
 
 /// Manifest for all the resources in the build.
@@ -44,16 +90,12 @@ impl Manifest {
         }
     }
 
-    pub fn load_from(mut file: &mut dyn Read) -> Result<Manifest> {
-        let mut buf = [0; 16];
-        file.read_exact(&mut buf[0..8])?;
+    pub fn load_from(file: &mut dyn Read) -> Result<Manifest> {
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
 
-        // MAGIC: [u8; 8]
-        if &buf[0..8] != &MAGIC[..] {
-            bail!("[ManifestLoader] MAGIC number not match.");
-        }
-
-        let mut manifest: Manifest = bincode::deserialize_from(&mut file)?;
+        let payload = format().decode(&bytes)?;
+        let mut manifest: Manifest = bincode::deserialize(&payload)?;
 
         manifest.uuids.clear();
         manifest.filenames.clear();
@@ -65,8 +107,87 @@ impl Manifest {
             manifest.filenames.insert(filename.into(), index);
         }
 
+        for v in &manifest.items {
+            for &index in manifest.buf.as_slice(v.dependencies) {
+                if index >= manifest.items.len() {
+                    bail!("[ManifestLoader] Undefined dependency of {}.", v.uuid);
+                }
+            }
+        }
+
         Ok(manifest)
     }
+
+    /// Adds a resource with readable `filename` and `uuid` into manifest. The `dependencies`
+    /// must have been added before.
+    pub fn add<T>(&mut self, filename: T, uuid: Uuid, dependencies: &[Uuid]) -> Result<()>
+    where
+        T: AsRef<str>,
+    {
+        self.insert(filename.as_ref(), uuid, dependencies, None)
+    }
+
+    /// Adds a resource along with the checksum and size of its contents `bytes`, which are
+    /// verified when the resource is loaded.
+    pub fn add_with_integrity<T>(
+        &mut self,
+        filename: T,
+        uuid: Uuid,
+        dependencies: &[Uuid],
+        bytes: &[u8],
+    ) -> Result<()>
+    where
+        T: AsRef<str>,
+    {
+        let integrity = (checksum(bytes), bytes.len() as u64);
+        self.insert(filename.as_ref(), uuid, dependencies, Some(integrity))
+    }
+
+    fn insert(
+        &mut self,
+        filename: &str,
+        uuid: Uuid,
+        dependencies: &[Uuid],
+        integrity: Option<(u64, u64)>,
+    ) -> Result<()> {
+        if self.uuids.contains_key(&uuid) {
+            bail!("[Manifest] {} has been added already.", uuid);
+        }
+
+        if self.filenames.contains_key(&filename.into()) {
+            bail!("[Manifest] {} has been added already.", filename);
+        }
+
+        let mut indices = Vec::with_capacity(dependencies.len());
+        for v in dependencies {
+            match self.uuids.get(v) {
+                Some(&index) => indices.push(index),
+                None => bail!("[Manifest] Undefined dependency {} of {}.", v, filename),
+            }
+        }
+
+        let index = self.items.len();
+        let item = ManifestItem {
+            filename: self.buf.extend_from_str(filename),
+            dependencies: self.buf.extend_from_slice(&indices),
+            uuid: uuid,
+            hash: integrity.map(|v| v.0),
+            size: integrity.map(|v| v.1),
+        };
+
+        self.items.push(item);
+        self.uuids.insert(uuid, index);
+        self.filenames.insert(filename.into(), index);
+        Ok(())
+    }
+
+    /// Serializes the manifest with the header of `format()`, which could be loaded with
+    /// `Manifest::load_from`.
+    pub fn save_to(&self, mut file: &mut dyn Write) -> Result<()> {
+        file.write_all(&format().magic())?;
+        bincode::serialize_into(&mut file, self)?;
+        Ok(())
+    }
 }
 
 impl Manifest {
@@ -83,7 +204,7 @@ impl Manifest {
     #[inline]
     pub fn locate(&self, uuid: Uuid) -> Option<PathBuf> {
         if self.uuids.contains_key(&uuid) {
-            Some(format!("{:X}", uuid.to_simple()).into())
+            Some(path(uuid))
         } else {
             None
         }
@@ -94,6 +215,14 @@ impl Manifest {
         self.uuids.contains_key(&uuid)
     }
 
+    /// Gets the readable filename of resource `uuid`.
+    #[inline]
+    pub fn filename(&self, uuid: Uuid) -> Option<&str> {
+        self.uuids
+            .get(&uuid)
+            .map(|&index| self.buf.as_str(self.items[index].filename))
+    }
+
     #[inline]
     pub fn dependencies(&self, uuid: Uuid) -> Option<Dependencies> {
         self.uuids.get(&uuid).map(|&index| Dependencies {
@@ -102,6 +231,209 @@ impl Manifest {
             items: self.items.as_ref(),
         })
     }
+
+    /// Verifies the contents of resource `uuid` with the checksum and size in manifest if
+    /// there are any, returns `errors::Error::IntegrityViolated` if they do not match.
+    pub fn verify(&self, uuid: Uuid, bytes: &[u8]) -> Result<()> {
+        let item = match self.uuids.get(&uuid) {
+            Some(&index) => &self.items[index],
+            None => return Ok(()),
+        };
+
+        let reason = match (item.size, item.hash) {
+            (Some(size), _) if size != bytes.len() as u64 => {
+                format!("expected {} bytes but got {}", size, bytes.len())
+            }
+            (_, Some(hash)) if hash != checksum(bytes) => "checksum not match".to_owned(),
+            _ => return Ok(()),
+        };
+
+        let err = ::res::errors::Error::IntegrityViolated {
+            uuid: uuid,
+            filename: self.buf.as_str(item.filename).to_owned(),
+            reason: reason,
+        };
+
+        Err(err.into())
+    }
+
+    /// Gets an iterator over the readable filenames and uuids of all the resources.
+    #[inline]
+    pub fn iter(&self) -> Iter {
+        Iter {
+            index: 0,
+            manifest: self,
+        }
+    }
+
+    /// Finds the circular dependencies which are reachable from resource `uuid`. The
+    /// returned chain starts and ends with the same resource.
+    pub fn find_cycle(&self, uuid: Uuid) -> Option<Vec<Uuid>> {
+        let index = *self.uuids.get(&uuid)?;
+        let mut marks = vec![Mark::Unvisited; self.items.len()];
+        let mut chain = Vec::new();
+
+        if self.visit(index, &mut marks, &mut chain) {
+            Some(chain.into_iter().map(|v| self.items[v].uuid).collect())
+        } else {
+            None
+        }
+    }
+
+    /// Finds the circular dependencies of all the resources in one pass. Returns the chains
+    /// of cycles, keyed by the resources which could reach them.
+    pub fn find_cycles(&self) -> FastHashMap<Uuid, Vec<Uuid>> {
+        let mut marks = vec![Mark::Unvisited; self.items.len()];
+        let mut reaches = vec![None; self.items.len()];
+        let mut cycles = Vec::new();
+        let mut chain = Vec::new();
+
+        for index in 0..self.items.len() {
+            self.reach(index, &mut marks, &mut reaches, &mut cycles, &mut chain);
+        }
+
+        reaches
+            .iter()
+            .enumerate()
+            .filter_map(|(index, v)| v.map(|v| (self.items[index].uuid, cycles[v].clone())))
+            .collect()
+    }
+
+    // Returns the index of cycle in `cycles` which is reachable from `index`.
+    fn reach(
+        &self,
+        index: usize,
+        marks: &mut [Mark],
+        reaches: &mut [Option<usize>],
+        cycles: &mut Vec<Vec<Uuid>>,
+        chain: &mut Vec<usize>,
+    ) -> Option<usize> {
+        match marks[index] {
+            Mark::Visited => return reaches[index],
+            Mark::Visiting => {
+                let start = chain.iter().position(|&v| v == index).unwrap();
+                let mut cycle: Vec<_> =
+                    chain[start..].iter().map(|&v| self.items[v].uuid).collect();
+                cycle.push(self.items[index].uuid);
+                cycles.push(cycle);
+                return Some(cycles.len() - 1);
+            }
+            Mark::Unvisited => {}
+        }
+
+        marks[index] = Mark::Visiting;
+        chain.push(index);
+
+        let mut reach = None;
+        for &v in self.buf.as_slice(self.items[index].dependencies) {
+            reach = self.reach(v, marks, reaches, cycles, chain);
+            if reach.is_some() {
+                break;
+            }
+        }
+
+        chain.pop();
+        marks[index] = Mark::Visited;
+        reaches[index] = reach;
+        reach
+    }
+
+    fn visit(&self, index: usize, marks: &mut [Mark], chain: &mut Vec<usize>) -> bool {
+        match marks[index] {
+            Mark::Visited => return false,
+            Mark::Visiting => {
+                let start = chain.iter().position(|&v| v == index).unwrap();
+                chain.drain(..start);
+                chain.push(index);
+                return true;
+            }
+            Mark::Unvisited => {}
+        }
+
+        marks[index] = Mark::Visiting;
+        chain.push(index);
+
+        for &v in self.buf.as_slice(self.items[index].dependencies) {
+            if self.visit(v, marks, chain) {
+                return true;
+            }
+        }
+
+        chain.pop();
+        marks[index] = Mark::Visited;
+        false
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Mark {
+    Unvisited,
+    Visiting,
+    Visited,
+}
+
+/// Gets the actual path of resource `uuid`, which is the hex representation of UUID.
+#[inline]
+pub fn path(uuid: Uuid) -> PathBuf {
+    format!("{:X}", uuid.to_simple()).into()
+}
+
+/// Computes the checksum of contents with 64-bit FNV-1a, which is stored in manifest.
+pub fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &v| {
+        (hash ^ u64::from(v)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Returns true if `filename` matches the `pattern`, which could be a plain prefix like
+/// `levels/forest/`, or a glob like `levels/*/*.prefab`. In globs, `?` matches any single
+/// character and `*` matches any sequence of characters except `/`, while `**` matches
+/// across directories.
+pub fn matches(pattern: &str, filename: &str) -> bool {
+    if pattern.contains(|v| v == '*' || v == '?') {
+        let pattern: Vec<_> = pattern.chars().collect();
+        let filename: Vec<_> = filename.chars().collect();
+        glob(&pattern, &filename)
+    } else {
+        filename.starts_with(pattern)
+    }
+}
+
+fn glob(pattern: &[char], filename: &[char]) -> bool {
+    match pattern.first() {
+        None => filename.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            (0..filename.len() + 1).any(|i| glob(&pattern[2..], &filename[i..]))
+        }
+        Some('*') => {
+            let len = filename
+                .iter()
+                .position(|&v| v == '/')
+                .unwrap_or(filename.len());
+
+            (0..len + 1).any(|i| glob(&pattern[1..], &filename[i..]))
+        }
+        Some('?') => match filename.first() {
+            Some(&v) if v != '/' => glob(&pattern[1..], &filename[1..]),
+            _ => false,
+        },
+        Some(&v) => filename.first() == Some(&v) && glob(&pattern[1..], &filename[1..]),
+    }
+}
+
+pub struct Iter<'a> {
+    index: usize,
+    manifest: &'a Manifest,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, Uuid);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.manifest.items.get(self.index)?;
+        self.index += 1;
+        Some((self.manifest.buf.as_str(item.filename), item.uuid))
+    }
 }
 
 pub struct Dependencies<'a> {
@@ -116,9 +448,53 @@ impl<'a> Iterator for Dependencies<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         if self.index < self.dependencies.len() {
             self.index += 1;
-            Some(self.items[self.index - 1].uuid)
+            Some(self.items[self.dependencies[self.index - 1]].uuid)
         } else {
             None
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use res::format::Header;
+
+    #[test]
+    fn upgrade() {
+        let u1 = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+        let u2 = Uuid::parse_str("427EB273E77446B8B7010ACE1B7652EE").unwrap();
+
+        let mut manifest = Manifest::new();
+        manifest.add("a", u1, &[]).unwrap();
+        manifest.add("b", u2, &[u1]).unwrap();
+
+        let v1 = ManifestV1 {
+            items: manifest
+                .items
+                .iter()
+                .map(|v| ManifestItemV1 {
+                    filename: v.filename,
+                    dependencies: v.dependencies,
+                    uuid: v.uuid,
+                }).collect(),
+            buf: manifest.buf.clone(),
+        };
+
+        let mut bytes = Header::new(*b"MNFT", 1).to_bytes().to_vec();
+        bincode::serialize_into(&mut bytes, &v1).unwrap();
+
+        let manifest = Manifest::load_from(&mut &bytes[..]).unwrap();
+        assert_eq!(manifest.redirect("b"), Some(u2));
+        let dependencies: Vec<_> = manifest.dependencies(u2).unwrap().collect();
+        assert_eq!(dependencies, vec![u1]);
+        assert!(manifest.items.iter().all(|v| v.hash.is_none()));
+
+        let bytes = Header::new(*b"MNFT", 3).to_bytes();
+        let err = Manifest::load_from(&mut &bytes[..]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[ManifestLoader] Unsupported version 3, expected 2."
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! An in-memory virtual filesystem, which is useful for tests and procedurally generated
+//! content.
+
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use uuid::Uuid;
+
+use errors::*;
+use utils::FastHashMap;
+
+use super::manifest::{self, Manifest};
+use super::VFS;
+
+struct MemoryFile {
+    filename: String,
+    bytes: Vec<u8>,
+    ts: SystemTime,
+}
+
+struct MemoryFSInternal {
+    files: FastHashMap<Uuid, MemoryFile>,
+    uuids: Vec<Uuid>,
+    manifest: Vec<u8>,
+    ts: SystemTime,
+}
+
+/// A virtual filesystem that keeps everything in memory. The `Manifest` is synthesized
+/// automatically when files are inserted.
+///
+/// `MemoryFS` is cheap to clone, all the clones share the same storage. So you could keep
+/// a clone after mounting it, and insert content generated at runtime.
+#[derive(Clone)]
+pub struct MemoryFS {
+    internal: Arc<RwLock<MemoryFSInternal>>,
+}
+
+impl MemoryFS {
+    /// Creates a new and empty `MemoryFS`.
+    pub fn new() -> Self {
+        let mut internal = MemoryFSInternal {
+            files: FastHashMap::default(),
+            uuids: Vec::new(),
+            manifest: Vec::new(),
+            ts: SystemTime::now(),
+        };
+
+        internal.synthesize().unwrap();
+
+        MemoryFS {
+            internal: Arc::new(RwLock::new(internal)),
+        }
+    }
+
+    /// Inserts a file with readable `filename` and `uuid`. The bytes will be replaced if
+    /// there is a file with the same `uuid` already.
+    pub fn insert<T1, T2>(&self, filename: T1, uuid: Uuid, bytes: T2) -> Result<()>
+    where
+        T1: Into<String>,
+        T2: Into<Vec<u8>>,
+    {
+        let filename = filename.into();
+        let mut internal = self.internal.write().unwrap();
+
+        for (&k, v) in &internal.files {
+            if v.filename == filename && k != uuid {
+                bail!(
+                    "[MemoryFS] {} has been inserted with {} already.",
+                    filename,
+                    k
+                );
+            }
+        }
+
+        let file = MemoryFile {
+            filename: filename,
+            bytes: bytes.into(),
+            ts: SystemTime::now(),
+        };
+
+        if internal.files.insert(uuid, file).is_none() {
+            internal.uuids.push(uuid);
+        }
+
+        internal.synthesize()
+    }
+
+    /// Removes the file with `uuid`.
+    pub fn remove(&self, uuid: Uuid) -> Result<()> {
+        let mut internal = self.internal.write().unwrap();
+
+        if internal.files.remove(&uuid).is_some() {
+            internal.uuids.retain(|&v| v != uuid);
+            internal.synthesize()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl MemoryFSInternal {
+    fn synthesize(&mut self) -> Result<()> {
+        let mut manifest = Manifest::new();
+        for uuid in &self.uuids {
+            manifest.add(self.files[uuid].filename.as_str(), *uuid, &[])?;
+        }
+
+        self.manifest.clear();
+        manifest.save_to(&mut self.manifest)?;
+        self.ts = SystemTime::now();
+        Ok(())
+    }
+
+    fn uuid(location: &Path) -> Option<Uuid> {
+        location.to_str().and_then(|v| Uuid::parse_str(v).ok())
+    }
+}
+
+impl VFS for MemoryFS {
+    fn read_to_end(&self, location: &Path, buf: &mut Vec<u8>) -> Result<usize> {
+        let internal = self.internal.read().unwrap();
+
+        let bytes = if location == Path::new(manifest::NAME) {
+            &internal.manifest
+        } else {
+            MemoryFSInternal::uuid(location)
+                .and_then(|uuid| internal.files.get(&uuid))
+                .map(|file| &file.bytes)
+                .ok_or_else(|| format_err!("[MemoryFS] {:?} does not exists.", location))?
+        };
+
+        buf.extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn is_dir(&self, _: &Path) -> bool {
+        false
+    }
+
+    fn exists(&self, location: &Path) -> bool {
+        if location == Path::new(manifest::NAME) {
+            return true;
+        }
+
+        let internal = self.internal.read().unwrap();
+        MemoryFSInternal::uuid(location)
+            .map(|uuid| internal.files.contains_key(&uuid))
+            .unwrap_or(false)
+    }
+
+    fn modified_since(&self, location: &Path, ts: SystemTime) -> bool {
+        let internal = self.internal.read().unwrap();
+
+        if location == Path::new(manifest::NAME) {
+            return internal.ts > ts;
+        }
+
+        MemoryFSInternal::uuid(location)
+            .and_then(|uuid| internal.files.get(&uuid))
+            .map(|file| file.ts > ts)
+            .unwrap_or(false)
+    }
+
+    // The modification of manifest is tracked in memory, which is cheap to check.
+    fn refresh_interval(&self) -> Duration {
+        Duration::from_secs(0)
+    }
+}
@@ -1,18 +1,30 @@
+pub mod compressed;
+pub use self::compressed::Compressed;
+
 pub mod directory;
 pub use self::directory::Directory;
 
+pub mod http;
+pub use self::http::Http;
+
 pub mod manifest;
 pub use self::manifest::Manifest;
 
+pub mod memory;
+pub use self::memory::MemoryFS;
+
+pub mod package;
+pub use self::package::Package;
+
 use std::io::Cursor;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use std::time::SystemTime;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 
 use uuid::Uuid;
 
 use errors::*;
-use utils::{FastHashMap, HashValue};
+use utils::{FastHashMap, FastHashSet, HashValue};
 
 pub trait VFS: Send + Sync + 'static {
     /// Opens a readable file at location.
@@ -26,24 +38,72 @@ pub trait VFS: Send + Sync + 'static {
 
     /// Returns true if the file has been modified since `ts`.
     fn modified_since(&self, location: &Path, ts: SystemTime) -> bool;
+
+    /// Gets the minimum interval between checks of manifest modification, which are made
+    /// when a filename or uuid could not be found in the manifest.
+    fn refresh_interval(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+
+    /// Gets the writable extension of this virtual filesystem if it supports.
+    fn writable(&self) -> Option<&dyn WritableVFS> {
+        None
+    }
+}
+
+/// The optional extension of `VFS` which supports writing files, like save games and user
+/// settings. The writable virtual filesystem could be mounted without manifest with
+/// `VFSDriver::mount_writable`.
+pub trait WritableVFS: VFS {
+    /// Writes `bytes` into file at location, the file will be replaced if it exists already.
+    fn write(&self, location: &Path, bytes: &[u8]) -> Result<()>;
+
+    /// Renames a file, the destination will be replaced if it exists already.
+    fn rename(&self, from: &Path, to: &Path) -> Result<()>;
+
+    /// Removes a file.
+    fn remove(&self, location: &Path) -> Result<()>;
 }
 
 pub struct VFSInstance {
     vfs: Box<dyn VFS>,
-    manifest: Manifest,
+    // The manifest along with the time it was read, and the circular dependencies in it
+    // which are found when the manifest is loaded.
+    manifest: RwLock<(Manifest, SystemTime, FastHashMap<Uuid, Vec<Uuid>>)>,
+    checked: Mutex<Option<Instant>>,
 }
 
 impl VFSInstance {
     pub fn new<T: VFS>(vfs: T) -> Result<Self> {
-        let mut buf = Vec::new();
-        vfs.read_to_end(manifest::NAME.as_ref(), &mut buf)?;
+        let ts = SystemTime::now();
+        let manifest = Self::load_manifest(&vfs)?;
+        Ok(Self::with_manifest(vfs, manifest, ts))
+    }
 
-        let instance = VFSInstance {
-            vfs: Box::new(vfs),
-            manifest: manifest::Manifest::load_from(&mut Cursor::new(&buf))?,
+    /// Creates a instance of writable virtual filesystem, which starts with an empty manifest
+    /// if there is no manifest yet.
+    pub fn writable<T: VFS>(vfs: T) -> Result<Self> {
+        if vfs.writable().is_none() {
+            bail!("Virtual file system is not writable.");
+        }
+
+        let ts = SystemTime::now();
+        let manifest = if vfs.exists(manifest::NAME.as_ref()) {
+            Self::load_manifest(&vfs)?
+        } else {
+            Manifest::new()
         };
 
-        Ok(instance)
+        Ok(Self::with_manifest(vfs, manifest, ts))
+    }
+
+    fn with_manifest<T: VFS>(vfs: T, manifest: Manifest, ts: SystemTime) -> Self {
+        let cycles = manifest.find_cycles();
+        VFSInstance {
+            vfs: Box::new(vfs),
+            manifest: RwLock::new((manifest, ts, cycles)),
+            checked: Mutex::new(None),
+        }
     }
 
     #[inline]
@@ -51,17 +111,147 @@ impl VFSInstance {
     where
         T: AsRef<str>,
     {
-        self.manifest.redirect(filename)
+        let redirect = || self.manifest.read().unwrap().0.redirect(filename.as_ref());
+        redirect().or_else(|| {
+            if self.refresh_on_miss() {
+                redirect()
+            } else {
+                None
+            }
+        })
     }
 
     #[inline]
     pub fn locate(&self, uuid: Uuid) -> Option<PathBuf> {
-        self.manifest.locate(uuid)
+        self.manifest.read().unwrap().0.locate(uuid)
+    }
+
+    /// Verifies the contents of resource `uuid` with the checksum and size in manifest.
+    #[inline]
+    pub fn verify(&self, uuid: Uuid, bytes: &[u8]) -> Result<()> {
+        self.manifest.read().unwrap().0.verify(uuid, bytes)
+    }
+
+    /// Lists the readable filenames and uuids of resources which match the `pattern`, see
+    /// `manifest::matches` for details.
+    pub fn list(&self, pattern: &str) -> Vec<(String, Uuid)> {
+        self.manifest
+            .read()
+            .unwrap()
+            .0
+            .iter()
+            .filter(|&(filename, _)| manifest::matches(pattern, filename))
+            .map(|(filename, uuid)| (filename.to_owned(), uuid))
+            .collect()
+    }
+
+    /// Gets the readable filename of resource `uuid`.
+    #[inline]
+    pub fn filename(&self, uuid: Uuid) -> Option<String> {
+        self.manifest
+            .read()
+            .unwrap()
+            .0
+            .filename(uuid)
+            .map(|v| v.to_owned())
+    }
+
+    /// Gets the dependencies of resource `uuid`. Returns error if there are circular
+    /// dependencies.
+    pub fn dependencies(&self, uuid: Uuid) -> Result<Vec<Uuid>> {
+        let manifest = self.manifest.read().unwrap();
+
+        if let Some(chain) = manifest.2.get(&uuid) {
+            let chain: Vec<_> = chain.iter().map(|v| v.to_string()).collect();
+            bail!("Circular dependencies found: {}.", chain.join(" -> "));
+        }
+
+        Ok(manifest
+            .0
+            .dependencies(uuid)
+            .map(|v| v.collect())
+            .unwrap_or_default())
     }
 
     #[inline]
     pub fn contains(&self, uuid: Uuid) -> bool {
-        self.manifest.contains(uuid)
+        let contains = || self.manifest.read().unwrap().0.contains(uuid);
+        contains() || (self.refresh_on_miss() && contains())
+    }
+
+    /// Reloads the manifest if it has been modified since last time we read it, this
+    /// makes resources which are added at runtime visible. Returns true if the manifest
+    /// has been reloaded.
+    pub fn refresh(&self) -> bool {
+        let ts = SystemTime::now();
+        let prev = self.manifest.read().unwrap().1;
+
+        if !self.vfs.modified_since(manifest::NAME.as_ref(), prev) {
+            return false;
+        }
+
+        match Self::load_manifest(&*self.vfs) {
+            Ok(manifest) => {
+                let cycles = manifest.find_cycles();
+                *self.manifest.write().unwrap() = (manifest, ts, cycles);
+                true
+            }
+            Err(err) => {
+                warn!("Failed to refresh manifest. {:?}", err);
+                false
+            }
+        }
+    }
+
+    /// Saves `bytes` into the file of readable `filename`, which is added into manifest if it
+    /// has not been there. Returns the uuid of file.
+    pub fn save(&self, filename: &str, bytes: &[u8]) -> Result<Uuid> {
+        let vfs = self
+            .vfs
+            .writable()
+            .ok_or_else(|| format_err!("Virtual file system is not writable."))?;
+
+        // Holds the manifest until finished, so concurrent saves never lose entries.
+        let mut manifest = self.manifest.write().unwrap();
+        let (uuid, added) = match manifest.0.redirect(filename) {
+            Some(uuid) => (uuid, false),
+            None => (random_uuid(), true),
+        };
+
+        vfs.write(&manifest::path(uuid), bytes)?;
+
+        if added {
+            manifest.0.add(filename, uuid, &[])?;
+
+            let mut buf = Vec::new();
+            manifest.0.save_to(&mut buf)?;
+            vfs.write(manifest::NAME.as_ref(), &buf)?;
+            manifest.1 = SystemTime::now();
+        }
+
+        Ok(uuid)
+    }
+
+    // Misses could be frequent, so we check the manifest at most once per `refresh_interval`.
+    fn refresh_on_miss(&self) -> bool {
+        {
+            let mut checked = self.checked.lock().unwrap();
+            if let Some(ts) = *checked {
+                if ts.elapsed() < self.vfs.refresh_interval() {
+                    return false;
+                }
+            }
+
+            *checked = Some(Instant::now());
+        }
+
+        self.refresh()
+    }
+
+    fn load_manifest<T: VFS + ?Sized>(vfs: &T) -> Result<Manifest> {
+        let mut buf = Vec::new();
+        vfs.read_to_end(manifest::NAME.as_ref(), &mut buf)?;
+        manifest::Manifest::load_from(&mut Cursor::new(&buf))
     }
 }
 
@@ -85,54 +275,228 @@ impl VFS for VFSInstance {
     fn modified_since(&self, location: &Path, ts: SystemTime) -> bool {
         self.vfs.modified_since(location, ts)
     }
+
+    #[inline]
+    fn refresh_interval(&self) -> Duration {
+        self.vfs.refresh_interval()
+    }
+
+    #[inline]
+    fn writable(&self) -> Option<&dyn WritableVFS> {
+        self.vfs.writable()
+    }
+}
+
+// Generates a random uuid for the files which are saved at runtime. The `RandomState` is
+// seeded randomly, and every new instance has different keys.
+fn random_uuid() -> Uuid {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut bytes = [0; 16];
+    for (i, v) in bytes.chunks_mut(8).enumerate() {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_usize(i);
+        let hash = hasher.finish();
+
+        for (j, b) in v.iter_mut().enumerate() {
+            *b = (hash >> (j * 8)) as u8;
+        }
+    }
+
+    uuid::Builder::from_bytes(bytes)
+        .set_variant(uuid::Variant::RFC4122)
+        .set_version(uuid::Version::Random)
+        .build()
+}
+
+struct Mount {
+    name: HashValue<str>,
+    priority: i32,
+    vfs: Arc<VFSInstance>,
 }
 
 pub struct VFSDriver {
-    mounts: FastHashMap<HashValue<str>, Arc<VFSInstance>>,
+    // Mounts sorted by priority in descending order, the earlier mounted one goes first
+    // if they have the same priority.
+    mounts: Vec<Mount>,
 }
 
 impl VFSDriver {
     /// Create a new file-system driver.
     pub fn new() -> Self {
-        VFSDriver {
-            mounts: FastHashMap::default(),
-        }
+        VFSDriver { mounts: Vec::new() }
     }
 
     /// Mount a file-system drive with identifier.
+    #[inline]
     pub fn mount<T, F>(&mut self, name: T, vfs: F) -> Result<()>
     where
         T: Into<HashValue<str>>,
         F: VFS + 'static,
     {
-        let hash = name.into();
-        if self.mounts.get(&hash).is_some() {
+        self.mount_with_priority(name, vfs, 0)
+    }
+
+    /// Mount a file-system drive with identifier and priority. Drives with the same
+    /// identifier are layered, the one with higher priority overrides the others.
+    pub fn mount_with_priority<T, F>(&mut self, name: T, vfs: F, priority: i32) -> Result<()>
+    where
+        T: Into<HashValue<str>>,
+        F: VFS + 'static,
+    {
+        self.insert(name.into(), priority, || VFSInstance::new(vfs))
+    }
+
+    /// Mount a writable file-system drive with identifier, which could be mounted without
+    /// manifest. The manifest is created when the first file is saved into it.
+    pub fn mount_writable<T, F>(&mut self, name: T, vfs: F) -> Result<()>
+    where
+        T: Into<HashValue<str>>,
+        F: VFS + 'static,
+    {
+        self.insert(name.into(), 0, || VFSInstance::writable(vfs))
+    }
+
+    fn insert<F>(&mut self, hash: HashValue<str>, priority: i32, instance: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<VFSInstance>,
+    {
+        if self
+            .mounts
+            .iter()
+            .any(|v| v.name == hash && v.priority == priority)
+        {
             bail!(
-                "Virtual file system with identifier {:?} has been mounted already.",
-                hash
+                "Virtual file system with identifier {:?} and priority {} has been mounted already.",
+                hash,
+                priority
             );
         }
 
-        self.mounts.insert(hash, Arc::new(VFSInstance::new(vfs)?));
+        let mount = Mount {
+            name: hash,
+            priority: priority,
+            vfs: Arc::new(instance()?),
+        };
+
+        let index = self
+            .mounts
+            .iter()
+            .position(|v| v.priority < priority)
+            .unwrap_or(self.mounts.len());
+
+        self.mounts.insert(index, mount);
         Ok(())
     }
 
-    /// Gets vfs instance which contains resource with `uuid`.
-    pub fn vfs_from_uuid(&self, uuid: Uuid) -> Option<Arc<VFSInstance>> {
-        for v in self.mounts.values() {
-            if v.contains(uuid) {
-                return Some(v.clone());
+    /// Unmount all the file-system drives with identifier.
+    pub fn unmount<T>(&mut self, name: T) -> Result<()>
+    where
+        T: Into<HashValue<str>>,
+    {
+        let hash = name.into();
+        let len = self.mounts.len();
+        self.mounts.retain(|v| v.name != hash);
+
+        if self.mounts.len() == len {
+            bail!("Undefined virtual file system with identifier {:?}.", hash);
+        }
+
+        Ok(())
+    }
+
+    /// Unmount the file-system drive with identifier and priority.
+    pub fn unmount_with_priority<T>(&mut self, name: T, priority: i32) -> Result<()>
+    where
+        T: Into<HashValue<str>>,
+    {
+        let hash = name.into();
+        match self
+            .mounts
+            .iter()
+            .position(|v| v.name == hash && v.priority == priority)
+        {
+            Some(index) => {
+                self.mounts.remove(index);
+                Ok(())
             }
+            None => bail!(
+                "Undefined virtual file system with identifier {:?} and priority {}.",
+                hash,
+                priority
+            ),
         }
+    }
 
-        None
+    /// Redirects a readable filename in the drives with identifier `fs` into uuid. The
+    /// drive with higher priority is searched first.
+    pub fn redirect<T1, T2>(&self, fs: T1, filename: T2) -> Option<Uuid>
+    where
+        T1: Into<HashValue<str>>,
+        T2: AsRef<str>,
+    {
+        let hash = fs.into();
+        self.mounts
+            .iter()
+            .filter(|v| v.name == hash)
+            .filter_map(|v| v.vfs.redirect(filename.as_ref()))
+            .next()
+    }
+
+    /// Lists the resources which match the `pattern` in the drives with identifier `fs`. If
+    /// there are resources with the same filename, the one in the drive with higher priority
+    /// is returned.
+    pub fn list<T>(&self, fs: T, pattern: &str) -> Vec<(String, Uuid)>
+    where
+        T: Into<HashValue<str>>,
+    {
+        let hash = fs.into();
+        let mut items = Vec::new();
+        let mut filenames = FastHashSet::default();
+
+        for v in self.mounts.iter().filter(|v| v.name == hash) {
+            for (filename, uuid) in v.vfs.list(pattern) {
+                if filenames.insert(filename.clone()) {
+                    items.push((filename, uuid));
+                }
+            }
+        }
+
+        items.sort();
+        items
+    }
+
+    /// Gets vfs instance which contains resource with `uuid`. The drive with higher
+    /// priority is searched first.
+    pub fn vfs_from_uuid(&self, uuid: Uuid) -> Option<Arc<VFSInstance>> {
+        self.mounts
+            .iter()
+            .find(|v| v.vfs.contains(uuid))
+            .map(|v| v.vfs.clone())
+    }
+
+    /// Gets writable vfs with specified identifier `fs` and the highest priority.
+    pub fn writable_vfs<T>(&self, fs: T) -> Option<Arc<VFSInstance>>
+    where
+        T: Into<HashValue<str>>,
+    {
+        let hash = fs.into();
+        self.mounts
+            .iter()
+            .find(|v| v.name == hash && v.vfs.writable().is_some())
+            .map(|v| v.vfs.clone())
     }
 
-    /// Gets vfs with specified identifier `fs`.
+    /// Gets vfs with specified identifier `fs` and the highest priority.
     pub fn vfs<T>(&self, fs: T) -> Option<Arc<VFSInstance>>
     where
         T: Into<HashValue<str>>,
     {
-        self.mounts.get(&fs.into()).map(|vfs| vfs.clone())
+        let hash = fs.into();
+        self.mounts
+            .iter()
+            .find(|v| v.name == hash)
+            .map(|v| v.vfs.clone())
     }
 }
@@ -0,0 +1,158 @@
+//! A single-file packed archive of resources, which is friendly for shipping builds.
+//!
+//! The layout of a package is:
+//!
+//! ```sh
+//! MAGIC | HEADER_LEN: u64 | HEADER | .MANIFEST | RESOURCE_0 | RESOURCE_1 | ...
+//! ```
+//!
+//! The header serves as a index that maps the UUID of resources into the actual
+//! ranges in the data section. The `MAGIC` is the versioned header of `format()`, and the
+//! upgrades of older versions are applied to the serialized `HEADER`.
+
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use bincode;
+use uuid::Uuid;
+
+use errors::*;
+use res::format::{Format, Header, HEADER_LEN};
+use utils::FastHashMap;
+
+use super::manifest::{self, Manifest};
+use super::VFS;
+
+/// The binary format of package.
+pub fn format() -> Format {
+    Format::new("Package", *b"PACK", 1)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+struct PackageRange {
+    offset: u64,
+    len: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct PackageHeader {
+    manifest: PackageRange,
+    items: Vec<(Uuid, PackageRange)>,
+}
+
+/// A virtual filesystem that reads resources from a single packed archive.
+pub struct Package {
+    file: Mutex<fs::File>,
+    base: u64,
+    manifest: PackageRange,
+    items: FastHashMap<Uuid, PackageRange>,
+}
+
+impl Package {
+    /// Opens a packed archive at `path`.
+    pub fn new<T: Into<PathBuf>>(path: T) -> Result<Self> {
+        let path = path.into();
+        info!("Creates package based virtual file system at {:?}.", path);
+
+        let mut file = fs::File::open(&path)?;
+        let format = format();
+
+        let mut bytes = vec![0; HEADER_LEN];
+        file.read_exact(&mut bytes)?;
+
+        // Checks the tag before reading anything else.
+        match Header::parse(&bytes) {
+            Some(header) if header.tag == format.header().tag => {}
+            _ => bail!("[Package] MAGIC number not match."),
+        }
+
+        let len: u64 = bincode::deserialize_from(&mut file)?;
+        (&mut file).take(len).read_to_end(&mut bytes)?;
+        let header: PackageHeader = bincode::deserialize(&format.decode(&bytes)?)?;
+
+        Ok(Package {
+            file: Mutex::new(file),
+            base: (HEADER_LEN + 8) as u64 + len,
+            manifest: header.manifest,
+            items: header.items.into_iter().collect(),
+        })
+    }
+
+    /// Packs all the resources in `vfs` into a single archive.
+    pub fn pack<T: VFS + ?Sized>(vfs: &T, file: &mut dyn Write) -> Result<()> {
+        let mut bytes = Vec::new();
+        vfs.read_to_end(manifest::NAME.as_ref(), &mut bytes)?;
+
+        let manifest = Manifest::load_from(&mut &bytes[..])?;
+
+        let mut header = PackageHeader {
+            manifest: PackageRange {
+                offset: 0,
+                len: bytes.len() as u64,
+            },
+            items: Vec::new(),
+        };
+
+        for v in &manifest.items {
+            let location = manifest.locate(v.uuid).unwrap();
+            let len = vfs.read_to_end(&location, &mut bytes)?;
+
+            let range = PackageRange {
+                offset: (bytes.len() - len) as u64,
+                len: len as u64,
+            };
+
+            header.items.push((v.uuid, range));
+        }
+
+        file.write_all(&format().magic())?;
+        bincode::serialize_into(&mut *file, &bincode::serialized_size(&header)?)?;
+        bincode::serialize_into(&mut *file, &header)?;
+        file.write_all(&bytes)?;
+        Ok(())
+    }
+
+    fn range(&self, location: &Path) -> Option<PackageRange> {
+        if location == Path::new(manifest::NAME) {
+            return Some(self.manifest);
+        }
+
+        location
+            .to_str()
+            .and_then(|v| Uuid::parse_str(v).ok())
+            .and_then(|uuid| self.items.get(&uuid).cloned())
+    }
+}
+
+impl VFS for Package {
+    fn read_to_end(&self, location: &Path, buf: &mut Vec<u8>) -> Result<usize> {
+        let range = self
+            .range(location)
+            .ok_or_else(|| format_err!("[Package] {:?} does not exists.", location))?;
+
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(self.base + range.offset))?;
+
+        let len = (&mut *file).take(range.len).read_to_end(buf)?;
+        if len as u64 != range.len {
+            bail!("[Package] {:?} is truncated.", location);
+        }
+
+        Ok(len)
+    }
+
+    fn is_dir(&self, _: &Path) -> bool {
+        false
+    }
+
+    fn exists(&self, location: &Path) -> bool {
+        self.range(location).is_some()
+    }
+
+    fn modified_since(&self, _: &Path, _: SystemTime) -> bool {
+        false
+    }
+}
@@ -229,7 +229,7 @@ impl Watcher {
     #[inline]
     fn wait(&self) {
         let guard = self.0.lock().unwrap();
-        let _ = self.1.wait(guard).unwrap();
+        drop(self.1.wait(guard).unwrap());
     }
 
     #[inline]
@@ -30,6 +30,7 @@ impl DataBuffer {
         let data =
             unsafe { slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) };
 
+        self.align(mem::align_of::<T>());
         self.0.extend_from_slice(data);
 
         DataBufferPtr {
@@ -46,6 +47,7 @@ impl DataBuffer {
     {
         let len = mem::size_of::<T>().wrapping_mul(slice.len());
         let u8_slice = unsafe { slice::from_raw_parts(slice.as_ptr() as *const u8, len) };
+        self.align(mem::align_of::<T>());
         self.0.extend_from_slice(u8_slice);
         DataBufferPtr {
             position: (self.0.len() - len) as u32,
@@ -67,6 +69,13 @@ impl DataBuffer {
         }
     }
 
+    // Pads the buffer, so the objects could be referenced without unaligned reads. Notes
+    // that the heap allocation of buffer is always aligned well enough for plain types.
+    fn align(&mut self, align: usize) {
+        let len = (self.0.len() + align - 1) / align * align;
+        self.0.resize(len, 0);
+    }
+
     /// Returns reference to object indicated by `DataBufferPtr`.
     #[inline]
     pub fn as_ref<T>(&self, ptr: DataBufferPtr<T>) -> &T
@@ -156,6 +165,10 @@ mod test {
         let slice_arr = buffer.extend(&arr);
         assert_eq!(*buffer.as_ref(slice_arr), arr);
 
+        let text = "unaligned";
+        let slice_text = buffer.extend_from_str(text);
+        assert_eq!(text, buffer.as_str(slice_text));
+
         let slice_arr_1_2 = buffer.extend_from_slice(&arr[0..2]);
         assert_eq!(buffer.as_slice(slice_arr_1_2), &arr[0..2]);
 
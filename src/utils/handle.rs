@@ -11,7 +11,7 @@ pub type HandleIndex = u32;
 /// is recycled when an `Handle` is freed to save address. However, this
 /// means that you could end up with two different `Handle` with identical
 /// indices. We solve this by introducing `version`.
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Handle {
     index: HandleIndex,
     version: HandleIndex,
@@ -132,6 +132,26 @@ macro_rules! impl_handle {
             }
         }
 
+        impl $crate::serde::Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+            where
+                S: $crate::serde::Serializer,
+            {
+                $crate::serde::Serialize::serialize(&self.0, serializer)
+            }
+        }
+
+        impl<'de> $crate::serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: $crate::serde::Deserializer<'de>,
+            {
+                let handle: $crate::utils::handle::Handle =
+                    $crate::serde::Deserialize::deserialize(deserializer)?;
+                Ok($name(handle))
+            }
+        }
+
         impl ::std::ops::Deref for $name {
             type Target = $crate::utils::handle::Handle;
             fn deref(&self) -> &$crate::utils::handle::Handle {
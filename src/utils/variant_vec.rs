@@ -201,9 +201,9 @@ macro_rules! impl_array(
                     assert!(len <= $size);
 
                     let mut vec: Vec<Self::Item> = Vec::with_capacity($size);
+                    ::std::ptr::copy_nonoverlapping(self.as_mut_ptr(), vec.as_mut_ptr(), len);
                     vec.set_len(len);
 
-                    ::std::ptr::copy_nonoverlapping(self.as_mut_ptr(), vec.as_mut_ptr(), 0);
                     ::std::mem::forget(self);
 
                     vec
@@ -85,7 +85,7 @@ impl MeshParams {
 }
 
 /// Mesh index.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum MeshIndex {
     SubMesh(usize),
     Ptr(usize, usize),
@@ -3,23 +3,29 @@ use std::io::Cursor;
 use std::sync::Arc;
 
 use errors::*;
+use res::format::Format;
 
 use super::super::backends::frame::Command;
 use super::super::DoubleFrame;
 use super::mesh::*;
 
-pub const MAGIC: [u8; 8] = [
-    'V' as u8, 'M' as u8, 'S' as u8, 'H' as u8, ' ' as u8, 0, 0, 1,
-];
+/// The binary format of meshes, the upgrades of older versions should be registered here.
+pub fn format() -> Format {
+    Format::new("MeshLoader", *b"VMSH", 1)
+}
 
 #[derive(Clone)]
 pub struct MeshLoader {
     frames: Arc<DoubleFrame>,
+    format: Format,
 }
 
 impl MeshLoader {
     pub(crate) fn new(frames: Arc<DoubleFrame>) -> Self {
-        MeshLoader { frames: frames }
+        MeshLoader {
+            frames: frames,
+            format: format(),
+        }
     }
 }
 
@@ -29,11 +35,8 @@ impl ::res::registry::Register for MeshLoader {
     type Value = MeshParams;
 
     fn load(&self, handle: Self::Handle, bytes: &[u8]) -> Result<Self::Intermediate> {
-        if &bytes[0..8] != &MAGIC[..] {
-            bail!("[MeshLoader] MAGIC number not match.");
-        }
-
-        let mut file = Cursor::new(&bytes[8..]);
+        let bytes = self.format.decode(bytes)?;
+        let mut file = Cursor::new(&bytes[..]);
         let params: MeshParams = bincode::deserialize_from(&mut file)?;
         let data = bincode::deserialize_from(&mut file)?;
 
@@ -59,4 +62,16 @@ impl ::res::registry::Register for MeshLoader {
         let cmd = Command::DeleteMesh(handle);
         self.frames.front().cmds.push(cmd);
     }
+
+    fn replace(&self, _: Self::Handle, _: Self::Value) {
+        // The backend replaces the mesh which is created with the same handle.
+    }
+
+    fn size(&self, params: &Self::Value) -> Option<usize> {
+        Some(params.vertex_buffer_len() + params.index_buffer_len())
+    }
+
+    fn format(&self) -> Option<&Format> {
+        Some(&self.format)
+    }
 }
@@ -16,8 +16,9 @@ pub mod prelude {
     };
 
     pub use super::texture::{
-        RenderTextureFormat, RenderTextureHandle, RenderTextureParams, TextureData, TextureFilter,
-        TextureFormat, TextureHandle, TextureHint, TextureParams, TextureWrap,
+        CubeFace, RenderTextureFormat, RenderTextureHandle, RenderTextureParams, TextureData,
+        TextureFilter, TextureFormat, TextureHandle, TextureHint, TextureKind, TextureParams,
+        TextureWrap,
     };
 
     pub use super::mesh::{
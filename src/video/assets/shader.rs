@@ -15,7 +15,7 @@ impl_handle!(ShaderHandle);
 
 /// A `ShaderParams` encapusulate all the informations we need to configurate
 /// OpenGL before real drawing, like shaders, render states, etc.
-#[derive(Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ShaderParams {
     pub attributes: AttributeLayout,
     pub uniforms: UniformVariableLayout,
@@ -107,7 +107,7 @@ impl FromStr for Attribute {
 }
 
 // AttributeLayout defines an layout of attributes into program.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub struct AttributeLayout {
     len: u8,
     elements: [(Attribute, u8, bool); MAX_VERTEX_ATTRIBUTES],
@@ -212,7 +212,7 @@ impl AttributeLayoutBuilder {
 }
 
 /// Specify whether front- or back-facing polygons can be culled.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum CullFace {
     Nothing,
     Front,
@@ -220,14 +220,14 @@ pub enum CullFace {
 }
 
 /// Define front- and back-facing polygons.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum FrontFaceOrder {
     Clockwise,
     CounterClockwise,
 }
 
 /// A pixel-wise comparison function.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Comparison {
     Never,
     Less,
@@ -241,7 +241,7 @@ pub enum Comparison {
 
 /// Specifies how incoming RGBA values (source) and the RGBA in framebuffer (destination)
 /// are combined.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Equation {
     /// Adds source and destination. Source and destination are multiplied
     /// by blending parameters before addition.
@@ -255,7 +255,7 @@ pub enum Equation {
 }
 
 /// Blend values.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum BlendValue {
     SourceColor,
     SourceAlpha,
@@ -264,7 +264,7 @@ pub enum BlendValue {
 }
 
 /// Blend factors.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum BlendFactor {
     Zero,
     One,
@@ -273,7 +273,7 @@ pub enum BlendFactor {
 }
 
 /// A struct that encapsulate all the necessary render states.
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 pub struct RenderState {
     pub cull_face: CullFace,
     pub front_face_order: FrontFaceOrder,
@@ -299,9 +299,12 @@ impl Default for RenderState {
 }
 
 /// Uniform variable type.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
 pub enum UniformVariableType {
     Texture,
+    CubeTexture,
+    Texture3D,
+    TextureArray,
     RenderTexture,
     I32,
     F32,
@@ -315,9 +318,12 @@ pub enum UniformVariableType {
 
 /// Uniform variable for video program object. Each matrix based `UniformVariable`
 /// is assumed to be supplied in row major order with a optional transpose.
-#[derive(Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub enum UniformVariable {
     Texture(TextureHandle),
+    CubeTexture(TextureHandle),
+    Texture3D(TextureHandle),
+    TextureArray(TextureHandle),
     RenderTexture(RenderTextureHandle),
     I32(i32),
     F32(f32),
@@ -334,6 +340,9 @@ impl UniformVariable {
         match *self {
             UniformVariable::RenderTexture(_) => UniformVariableType::RenderTexture,
             UniformVariable::Texture(_) => UniformVariableType::Texture,
+            UniformVariable::CubeTexture(_) => UniformVariableType::CubeTexture,
+            UniformVariable::Texture3D(_) => UniformVariableType::Texture3D,
+            UniformVariable::TextureArray(_) => UniformVariableType::TextureArray,
             UniformVariable::I32(_) => UniformVariableType::I32,
             UniformVariable::F32(_) => UniformVariableType::F32,
             UniformVariable::Vector2f(_) => UniformVariableType::Vector2f,
@@ -443,7 +452,7 @@ impl Into<UniformVariable> for [f32; 4] {
 }
 
 // UniformVariableLayout defines an layout of uniforms in program.
-#[derive(Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct UniformVariableLayout {
     variables: FastHashMap<HashValue<str>, (String, UniformVariableType)>,
 }
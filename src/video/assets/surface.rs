@@ -10,7 +10,7 @@ use video::MAX_FRAMEBUFFER_ATTACHMENTS;
 /// Likes clearing, MSAA resolves, etc.. The `RenderTarget` is the window framebuffer as
 /// default, but you can specify `RenderTarget` with `SurfaceParams::set_attachments`
 /// manually also.
-#[derive(Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub struct SurfaceParams {
     pub(crate) colors: [Option<RenderTextureHandle>; MAX_FRAMEBUFFER_ATTACHMENTS],
     pub(crate) depth_stencil: Option<RenderTextureHandle>,
@@ -80,7 +80,7 @@ impl SurfaceParams {
 /// Defines a rectangle, called the scissor box, in window coordinates. The test is
 /// initially disabled. While the test is enabled, only pixels that lie within the
 /// scissor box can be modified by drawing commands.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SurfaceScissor {
     Enable {
         /// Specify the lower left corner of the scissor box, in pixels. Initially (0, 0).
@@ -96,7 +96,7 @@ pub enum SurfaceScissor {
 /// in window coordinates to normalized window coordinates.
 ///
 /// NDC(normalized device coordinates) to normalized window coordinates.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SurfaceViewport {
     /// Specify the lower left corner of the viewport rectangle, in pixels. Initially (0, 0).
     pub position: math::Vector2<i32>,
@@ -1,5 +1,5 @@
-//! Immutable or dynamic 2D texture. A texture is a container of one or more images. It
-//! can be the source of a texture access from a Shader.
+//! Immutable or dynamic 2D texture, cube map, 3D texture and 2D texture array. A texture is a
+//! container of one or more images. It can be the source of a texture access from a Shader.
 use math;
 use video::errors::{Error, Result};
 
@@ -18,6 +18,10 @@ pub struct TextureParams {
     pub format: TextureFormat,
     /// Sets the dimensions of texture.
     pub dimensions: math::Vector2<u32>,
+    /// Sets the kind of texture.
+    pub kind: TextureKind,
+    /// Sets the depth of 3D texture, or the number of layers of texture array.
+    pub depth: u32,
 }
 
 impl Default for TextureParams {
@@ -28,16 +32,57 @@ impl Default for TextureParams {
             filter: TextureFilter::Linear,
             hint: TextureHint::Immutable,
             dimensions: math::Vector2::new(0, 0),
+            kind: TextureKind::Texture2D,
+            depth: 1,
         }
     }
 }
 
 impl TextureParams {
+    /// Returns the number of images in each mipmap level, e.g. six faces of cube map.
+    pub fn layers(&self) -> u32 {
+        match self.kind {
+            TextureKind::Texture2D => 1,
+            TextureKind::Cube => 6,
+            TextureKind::Texture3D | TextureKind::Array2D => self.depth,
+        }
+    }
+
     pub fn validate(&self, data: Option<&TextureData>) -> Result<()> {
+        if self.kind == TextureKind::Cube && self.dimensions.x != self.dimensions.y {
+            return Err(Error::TextureInvalid(
+                "The faces of cube map must be square.".into(),
+            ));
+        }
+
+        if self.layers() == 0 {
+            return Err(Error::TextureInvalid(
+                "The depth of texture must be at least 1.".into(),
+            ));
+        }
+
         if let Some(buf) = data {
-            let len = self.format.size(self.dimensions);
-            if buf.bytes.len() > 0 && buf.bytes[0].len() > len as usize {
-                return Err(Error::OutOfBounds);
+            let layers = self.layers() as usize;
+            if buf.bytes.len() % layers != 0 {
+                return Err(Error::TextureInvalid(format!(
+                    "The number of images {} does not match with {} layers.",
+                    buf.bytes.len(),
+                    layers
+                )));
+            }
+
+            let len = self.format.size(self.dimensions) as usize;
+            let levels = buf.bytes.len() / layers;
+            if self.kind == TextureKind::Texture3D && levels > 1 {
+                return Err(Error::TextureInvalid(
+                    "The mipmaps of 3D texture are not supported.".into(),
+                ));
+            }
+
+            for v in buf.bytes.iter().step_by(levels.max(1)) {
+                if v.len() > len {
+                    return Err(Error::OutOfBounds);
+                }
             }
         }
 
@@ -45,9 +90,59 @@ impl TextureParams {
     }
 }
 
+/// The kind of texture.
+#[repr(u8)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TextureKind {
+    /// A two-dimensional texture.
+    Texture2D,
+    /// A cube map which consists of six square faces, it's sampled with a direction
+    /// vector instead of texture coordinates.
+    Cube,
+    /// A three-dimensional texture which consists of `depth` slices, it's sampled with
+    /// three texture coordinates and interpolated between slices.
+    Texture3D,
+    /// An array of `depth` two-dimensional layers with the same dimensions, it's sampled
+    /// with texture coordinates and the index of layer.
+    Array2D,
+}
+
+/// The faces of cube map, in the order of layers.
+#[repr(u8)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl CubeFace {
+    /// All the faces in the order of layers.
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    /// Returns the index of layer.
+    #[inline]
+    pub fn layer(&self) -> u32 {
+        *self as u32
+    }
+}
+
 /// Continuous texture data of different mipmap levels.
 ///
-/// Notes that mipmaps are stored in order from largest size to smallest size
+/// Notes that mipmaps are stored in order from largest size to smallest size. For texture
+/// with multiple layers like cube map, the mipmaps of layers are stored one after another,
+/// so there are `levels * layers` images. The slices of 3D texture are stored as layers,
+/// and they have no mipmaps.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TextureData {
     pub bytes: Vec<Box<[u8]>>,
@@ -57,7 +152,7 @@ pub struct TextureData {
 /// be used as a render target. If the `sampler` field is true, it can also be ther
 /// source of a texture access from a __shader__.
 ///
-#[derive(Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub struct RenderTextureParams {
     pub format: RenderTextureFormat,
     pub wrap: TextureWrap,
@@ -129,7 +224,7 @@ pub enum TextureWrap {
 /// floating point, multiplies by the signed scale factor, adds the signed bias, and
 /// clamps to the range [0,1].
 #[repr(u8)]
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum RenderTextureFormat {
     RGB8,
     RGBA4,
@@ -3,43 +3,109 @@ use std::io::Cursor;
 use std::sync::Arc;
 
 use errors::*;
+use math;
+use res::format::Format;
 
 use super::super::backends::frame::Command;
 use super::super::DoubleFrame;
 use super::texture::*;
 
-pub const MAGIC: [u8; 8] = [
-    'V' as u8, 'T' as u8, 'E' as u8, 'X' as u8, ' ' as u8, 0, 0, 1,
-];
+/// The binary format of textures, the upgrades of older versions should be registered here.
+pub fn format() -> Format {
+    Format::new("TextureLoader", *b"VTEX", 3)
+        .with_upgrade(1, upgrade_v1)
+        .with_upgrade(2, upgrade_v2)
+}
+
+// Upgrades the textures without kind, which are always 2D textures.
+fn upgrade_v1(payload: &[u8]) -> Result<Vec<u8>> {
+    let mut file = Cursor::new(payload);
+    let v1: TextureParamsV1 = bincode::deserialize_from(&mut file)?;
+    let data: TextureData = bincode::deserialize_from(&mut file)?;
+
+    let params = TextureParamsV2 {
+        hint: v1.hint,
+        wrap: v1.wrap,
+        filter: v1.filter,
+        format: v1.format,
+        dimensions: v1.dimensions,
+        kind: TextureKind::Texture2D,
+    };
+
+    let mut bytes = bincode::serialize(&params)?;
+    bincode::serialize_into(&mut bytes, &data)?;
+    Ok(bytes)
+}
+
+// Upgrades the textures without depth, which are always 2D textures or cube maps.
+fn upgrade_v2(payload: &[u8]) -> Result<Vec<u8>> {
+    let mut file = Cursor::new(payload);
+    let v2: TextureParamsV2 = bincode::deserialize_from(&mut file)?;
+    let data: TextureData = bincode::deserialize_from(&mut file)?;
+
+    let params = TextureParams {
+        hint: v2.hint,
+        wrap: v2.wrap,
+        filter: v2.filter,
+        format: v2.format,
+        dimensions: v2.dimensions,
+        kind: v2.kind,
+        depth: 1,
+    };
+
+    let mut bytes = bincode::serialize(&params)?;
+    bincode::serialize_into(&mut bytes, &data)?;
+    Ok(bytes)
+}
+
+#[derive(Serialize, Deserialize)]
+struct TextureParamsV1 {
+    hint: TextureHint,
+    wrap: TextureWrap,
+    filter: TextureFilter,
+    format: TextureFormat,
+    dimensions: math::Vector2<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TextureParamsV2 {
+    hint: TextureHint,
+    wrap: TextureWrap,
+    filter: TextureFilter,
+    format: TextureFormat,
+    dimensions: math::Vector2<u32>,
+    kind: TextureKind,
+}
 
 #[derive(Clone)]
 pub struct TextureLoader {
     frames: Arc<DoubleFrame>,
+    format: Format,
 }
 
 impl TextureLoader {
     pub(crate) fn new(frames: Arc<DoubleFrame>) -> Self {
-        TextureLoader { frames: frames }
+        TextureLoader {
+            frames: frames,
+            format: format(),
+        }
     }
 }
 
 impl ::res::registry::Register for TextureLoader {
     type Handle = TextureHandle;
     type Intermediate = (TextureParams, Option<TextureData>);
-    type Value = ();
+    type Value = TextureParams;
 
     fn load(&self, handle: Self::Handle, bytes: &[u8]) -> Result<Self::Intermediate> {
-        if &bytes[0..8] != &MAGIC[..] {
-            bail!("[TextureLoader] MAGIC number not match.");
-        }
-
-        let mut file = Cursor::new(&bytes[8..]);
+        let bytes = self.format.decode(bytes)?;
+        let mut file = Cursor::new(&bytes[..]);
         let params: TextureParams = bincode::deserialize_from(&mut file)?;
         let data = bincode::deserialize_from(&mut file)?;
 
         info!(
-            "[TextureLoader] loads {:?} ({}x{} - {:?}).",
-            handle, params.dimensions.x, params.dimensions.y, params.format
+            "[TextureLoader] loads {:?} ({}x{} - {:?} - {:?}).",
+            handle, params.dimensions.x, params.dimensions.y, params.format, params.kind
         );
 
         Ok((params, Some(data)))
@@ -52,11 +118,19 @@ impl ::res::registry::Register for TextureLoader {
         let task = Command::CreateTexture(handle, item.0, item.1);
         frame.cmds.push(task);
 
-        Ok(())
+        Ok(item.0)
     }
 
     fn detach(&self, handle: Self::Handle, _: Self::Value) {
         let cmd = Command::DeleteTexture(handle);
         self.frames.front().cmds.push(cmd);
     }
+
+    fn replace(&self, _: Self::Handle, _: Self::Value) {
+        // The backend replaces the texture which is created with the same handle.
+    }
+
+    fn format(&self) -> Option<&Format> {
+        Some(&self.format)
+    }
 }
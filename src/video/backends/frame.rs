@@ -1,10 +1,11 @@
-use std::sync::{Mutex, MutexGuard, RwLock};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 
 use errors::*;
 use math;
 use utils::{data_buf, hash_value};
 
 use super::super::assets::prelude::*;
+use super::super::readback::{Readback, ReadbackTarget};
 use super::Visitor;
 
 type VarsPtr = data_buf::DataBufferPtr<[(hash_value::HashValue<str>, UniformVariable)]>;
@@ -24,7 +25,7 @@ pub enum Command {
     DeleteShader(ShaderHandle),
 
     CreateTexture(TextureHandle, TextureParams, Option<TextureData>),
-    UpdateTexture(TextureHandle, math::Aabb2<u32>, BytesPtr),
+    UpdateTexture(TextureHandle, u32, math::Aabb2<u32>, BytesPtr),
     DeleteTexture(TextureHandle),
 
     CreateRenderTexture(RenderTextureHandle, RenderTextureParams),
@@ -34,6 +35,8 @@ pub enum Command {
     UpdateVertexBuffer(MeshHandle, usize, BytesPtr),
     UpdateIndexBuffer(MeshHandle, usize, BytesPtr),
     DeleteMesh(MeshHandle),
+
+    ReadPixels(ReadbackTarget, math::Aabb2<u32>, Arc<Readback>),
 }
 
 #[derive(Debug, Clone)]
@@ -55,97 +58,187 @@ impl Frame {
     }
 
     /// Dispatch frame tasks and draw calls to the backend context.
+    ///
+    /// The pending readbacks of this frame are always fulfilled, with errors if the frame
+    /// failed to dispatch.
     pub fn dispatch(
         &mut self,
         visitor: &mut Visitor,
         dimensions: math::Vector2<u32>,
     ) -> Result<(u32, u32)> {
         unsafe {
-            visitor.advance()?;
+            if let Err(err) = visitor.advance() {
+                self.abort(&err);
+                return Err(err);
+            }
 
             let (mut dc, mut tris) = (0, 0);
-            for v in self.cmds.drain(..) {
-                match v {
-                    Command::Bind(surface) => {
-                        visitor.bind(surface, dimensions)?;
-                    }
+            let mut readbacks = Vec::new();
+            let mut failure = None;
 
-                    Command::Draw(shader, mesh, mesh_index, ptr) => {
-                        let vars = self.bufs.as_slice(ptr);
-                        dc += 1;
-                        tris += visitor.draw(shader, mesh, mesh_index, vars)?;
-                    }
+            {
+                let bufs = &self.bufs;
+                let mut cmds = self.cmds.drain(..);
 
-                    Command::UpdateScissor(scissor) => {
-                        visitor.update_surface_scissor(scissor)?;
+                for v in &mut cmds {
+                    if let Command::ReadPixels(target, area, readback) = v {
+                        readbacks.push((target, area, readback));
+                        continue;
                     }
 
-                    Command::UpdateViewport(view) => {
-                        visitor.update_surface_viewport(view)?;
+                    match Frame::dispatch_command(visitor, bufs, v, dimensions) {
+                        Ok(Some(v)) => {
+                            dc += 1;
+                            tris += v;
+                        }
+                        Ok(None) => {}
+                        Err(err) => {
+                            failure = Some(err);
+                            break;
+                        }
                     }
+                }
 
-                    Command::CreateSurface(handle, params) => {
-                        visitor.create_surface(handle, params)?;
+                // Collects the readbacks of the commands which would never be dispatched.
+                for v in cmds {
+                    if let Command::ReadPixels(target, area, readback) = v {
+                        readbacks.push((target, area, readback));
                     }
+                }
+            }
 
-                    Command::DeleteSurface(handle) => {
-                        visitor.delete_surface(handle)?;
-                    }
+            if let Some(err) = failure {
+                for (_, _, readback) in readbacks {
+                    reject(&readback, &err);
+                }
 
-                    Command::CreateShader(handle, params, vs, fs) => {
-                        visitor.create_shader(handle, params, &vs, &fs)?;
-                    }
+                self.bufs.clear();
+                return Err(err);
+            }
 
-                    Command::DeleteShader(handle) => {
-                        visitor.delete_shader(handle)?;
-                    }
+            // The pixels are read after all the draw calls of this frame.
+            for (target, area, readback) in readbacks {
+                readback.set(visitor.read_pixels(target, area));
+            }
 
-                    Command::CreateTexture(handle, params, data) => {
-                        visitor.create_texture(handle, params, data)?;
-                    }
+            visitor.flush()?;
+            self.bufs.clear();
+            Ok((dc, tris))
+        }
+    }
 
-                    Command::UpdateTexture(handle, area, ptr) => {
-                        let data = self.bufs.as_slice(ptr);
-                        visitor.update_texture(handle, area, data)?;
-                    }
+    // Dispatches a command, returns the number of triangles if it's a draw call.
+    unsafe fn dispatch_command(
+        visitor: &mut dyn Visitor,
+        bufs: &data_buf::DataBuffer,
+        v: Command,
+        dimensions: math::Vector2<u32>,
+    ) -> Result<Option<u32>> {
+        match v {
+            Command::Bind(surface) => {
+                visitor.bind(surface, dimensions)?;
+            }
 
-                    Command::DeleteTexture(handle) => {
-                        visitor.delete_texture(handle)?;
-                    }
+            Command::Draw(shader, mesh, mesh_index, ptr) => {
+                let vars = bufs.as_slice(ptr);
+                let tris = visitor.draw(shader, mesh, mesh_index, vars)?;
+                return Ok(Some(tris));
+            }
 
-                    Command::CreateRenderTexture(handle, params) => {
-                        visitor.create_render_texture(handle, params)?;
-                    }
+            Command::UpdateScissor(scissor) => {
+                visitor.update_surface_scissor(scissor)?;
+            }
 
-                    Command::DeleteRenderTexture(handle) => {
-                        visitor.delete_render_texture(handle)?;
-                    }
+            Command::UpdateViewport(view) => {
+                visitor.update_surface_viewport(view)?;
+            }
 
-                    Command::CreateMesh(handle, params, data) => {
-                        visitor.create_mesh(handle, params, data)?;
-                    }
+            Command::CreateSurface(handle, params) => {
+                visitor.create_surface(handle, params)?;
+            }
 
-                    Command::UpdateVertexBuffer(handle, offset, ptr) => {
-                        let data = self.bufs.as_slice(ptr);
-                        visitor.update_vertex_buffer(handle, offset, data)?;
-                    }
+            Command::DeleteSurface(handle) => {
+                visitor.delete_surface(handle)?;
+            }
 
-                    Command::UpdateIndexBuffer(handle, offset, ptr) => {
-                        let data = self.bufs.as_slice(ptr);
-                        visitor.update_index_buffer(handle, offset, data)?;
-                    }
+            Command::CreateShader(handle, params, vs, fs) => {
+                visitor.create_shader(handle, params, &vs, &fs)?;
+            }
 
-                    Command::DeleteMesh(handle) => {
-                        visitor.delete_mesh(handle)?;
-                    }
-                }
+            Command::DeleteShader(handle) => {
+                visitor.delete_shader(handle)?;
             }
 
-            visitor.flush()?;
-            self.bufs.clear();
-            Ok((dc, tris))
+            Command::CreateTexture(handle, params, data) => {
+                visitor.create_texture(handle, params, data)?;
+            }
+
+            Command::UpdateTexture(handle, layer, area, ptr) => {
+                let data = bufs.as_slice(ptr);
+                visitor.update_texture(handle, layer, area, data)?;
+            }
+
+            Command::DeleteTexture(handle) => {
+                visitor.delete_texture(handle)?;
+            }
+
+            Command::CreateRenderTexture(handle, params) => {
+                visitor.create_render_texture(handle, params)?;
+            }
+
+            Command::DeleteRenderTexture(handle) => {
+                visitor.delete_render_texture(handle)?;
+            }
+
+            Command::CreateMesh(handle, params, data) => {
+                visitor.create_mesh(handle, params, data)?;
+            }
+
+            Command::UpdateVertexBuffer(handle, offset, ptr) => {
+                let data = bufs.as_slice(ptr);
+                visitor.update_vertex_buffer(handle, offset, data)?;
+            }
+
+            Command::UpdateIndexBuffer(handle, offset, ptr) => {
+                let data = bufs.as_slice(ptr);
+                visitor.update_index_buffer(handle, offset, data)?;
+            }
+
+            Command::DeleteMesh(handle) => {
+                visitor.delete_mesh(handle)?;
+            }
+
+            Command::ReadPixels(_, _, _) => unreachable!(),
         }
+
+        Ok(None)
     }
+
+    // Drops all the commands, and fulfills the pending readbacks with `err`.
+    fn abort(&mut self, err: &::failure::Error) {
+        for v in self.cmds.drain(..) {
+            if let Command::ReadPixels(_, _, readback) = v {
+                reject(&readback, err);
+            }
+        }
+
+        self.bufs.clear();
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        for v in self.cmds.drain(..) {
+            if let Command::ReadPixels(_, _, readback) = v {
+                readback.set(Err(format_err!("The frame is dropped before dispatching.")));
+            }
+        }
+    }
+}
+
+fn reject(readback: &Readback, err: &::failure::Error) {
+    let err = format_err!("Failed to dispatch frame, errors: {}", err);
+    readback.set(Err(err));
 }
 
 pub(crate) struct DoubleFrame {
@@ -279,6 +279,48 @@ impl TextureFormat {
     }
 }
 
+impl From<TextureKind> for GLenum {
+    fn from(kind: TextureKind) -> Self {
+        match kind {
+            TextureKind::Texture2D => gl::TEXTURE_2D,
+            TextureKind::Cube => gl::TEXTURE_CUBE_MAP,
+            TextureKind::Texture3D => gl::TEXTURE_3D,
+            TextureKind::Array2D => gl::TEXTURE_2D_ARRAY,
+        }
+    }
+}
+
+/// Gets the target of a image in texture, e.g. one of the faces of cube map. The layers of
+/// 3D texture and texture array share the same target, which are addressed by z offset.
+pub fn texture_image_target(kind: TextureKind, layer: u32) -> GLenum {
+    match kind {
+        TextureKind::Texture2D => gl::TEXTURE_2D,
+        TextureKind::Cube => gl::TEXTURE_CUBE_MAP_POSITIVE_X + layer,
+        TextureKind::Texture3D => gl::TEXTURE_3D,
+        TextureKind::Array2D => gl::TEXTURE_2D_ARRAY,
+    }
+}
+
+impl TextureKind {
+    pub fn is_support(&self, capabilities: &Capabilities) -> bool {
+        match *self {
+            TextureKind::Texture3D | TextureKind::Array2D => {
+                capabilities.version >= Version::GL(3, 0)
+                    || capabilities.version >= Version::ES(3, 0)
+            }
+            _ => true,
+        }
+    }
+
+    /// Checks if the layers of texture are stored as a volume.
+    pub fn is_volume(&self) -> bool {
+        match *self {
+            TextureKind::Texture3D | TextureKind::Array2D => true,
+            _ => false,
+        }
+    }
+}
+
 impl From<TextureWrap> for GLenum {
     fn from(wrap: TextureWrap) -> Self {
         match wrap {
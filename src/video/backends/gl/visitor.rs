@@ -8,6 +8,7 @@ use utils::hash::{FastHashMap, FastHashSet};
 use utils::hash_value::HashValue;
 
 use super::super::super::assets::prelude::*;
+use super::super::super::readback::ReadbackTarget;
 use super::super::super::MAX_UNIFORM_TEXTURE_SLOTS;
 use super::super::{UniformVar, Visitor};
 use super::capabilities::{Capabilities, Version};
@@ -103,7 +104,7 @@ struct GLVisitorMutInternal {
     binded_frame_surfaces: FastHashSet<SurfaceHandle>,
     binded_shader: Option<GLuint>,
     binded_texture_index: usize,
-    binded_textures: [Option<GLuint>; MAX_UNIFORM_TEXTURE_SLOTS],
+    binded_textures: [Option<(GLenum, GLuint)>; MAX_UNIFORM_TEXTURE_SLOTS],
     vaos: FastHashMap<(GLuint, GLuint), GLuint>,
 }
 
@@ -344,31 +345,71 @@ impl Visitor for GLVisitor {
             );
         }
 
+        if !params.kind.is_support(&self.capabilities) {
+            bail!(
+                "The GL Context does not support the texture kind {:?}.",
+                params.kind
+            );
+        }
+
+        let is_compression = params.format.is_compression();
+        if is_compression && params.kind.is_volume() {
+            bail!("Compressed {:?} texture is not supported.", params.kind);
+        }
+
+        // Replaces the texture which has been reloaded in place.
+        if self.textures.get(handle).is_some() {
+            self.delete_texture(handle)?;
+        }
+
         let mut id = 0;
         gl::GenTextures(1, &mut id);
         assert!(id != 0);
 
         let (internal_format, format, pixel_type) =
             types::texture_format(params.format, &self.capabilities);
-        let is_compression = params.format.is_compression();
+        let target = params.kind.into();
         let mut allocated = false;
 
         if let Some(mut data) = data {
-            let len = data.bytes.len();
-            if len > 0 {
-                self.bind_texture(0, id)?;
-                self.update_texture_params(id, params.wrap, params.filter, len as u32)?;
-
-                let mut dims = (
-                    params.dimensions.x as GLsizei,
-                    params.dimensions.y as GLsizei,
-                );
+            // The mipmaps of layers are stored one after another.
+            let levels = data.bytes.len() / params.layers() as usize;
+            if levels > 0 {
+                self.bind_texture(0, target, id)?;
+                self.update_texture_params(target, id, params.wrap, params.filter, levels as u32)?;
+
+                if params.kind.is_volume() {
+                    for level in 0..levels {
+                        self.allocate_texture_volume(params, level as GLint)?;
+                    }
+                }
+
+                for (i, v) in data.bytes.drain(..).enumerate() {
+                    let image = types::texture_image_target(params.kind, (i / levels) as u32);
+                    let level = i % levels;
+                    let dims = (
+                        (params.dimensions.x as GLsizei >> level).max(1),
+                        (params.dimensions.y as GLsizei >> level).max(1),
+                    );
 
-                if is_compression {
-                    for (i, v) in data.bytes.drain(..).enumerate() {
+                    if params.kind.is_volume() {
+                        gl::TexSubImage3D(
+                            image,
+                            level as GLint,
+                            0,
+                            0,
+                            (i / levels) as GLint,
+                            dims.0,
+                            dims.1,
+                            1,
+                            format,
+                            pixel_type,
+                            &v[0] as *const u8 as *const ::std::os::raw::c_void,
+                        );
+                    } else if is_compression {
                         gl::CompressedTexImage2D(
-                            gl::TEXTURE_2D,
-                            i as GLint,
+                            image,
+                            level as GLint,
                             internal_format,
                             dims.0,
                             dims.1,
@@ -376,15 +417,10 @@ impl Visitor for GLVisitor {
                             v.len() as GLint,
                             &v[0] as *const u8 as *const ::std::os::raw::c_void,
                         );
-
-                        dims.0 = (dims.0 / 2).max(1);
-                        dims.1 = (dims.1 / 2).max(1);
-                    }
-                } else {
-                    for (i, v) in data.bytes.drain(..).enumerate() {
+                    } else {
                         gl::TexImage2D(
-                            gl::TEXTURE_2D,
-                            i as GLint,
+                            image,
+                            level as GLint,
                             internal_format as GLint,
                             dims.0,
                             dims.1,
@@ -393,9 +429,6 @@ impl Visitor for GLVisitor {
                             pixel_type,
                             &v[0] as *const u8 as *const ::std::os::raw::c_void,
                         );
-
-                        dims.0 = (dims.0 / 2).max(1);
-                        dims.1 = (dims.1 / 2).max(1);
                     }
                 }
 
@@ -420,6 +453,7 @@ impl Visitor for GLVisitor {
     unsafe fn update_texture(
         &mut self,
         handle: TextureHandle,
+        layer: u32,
         area: math::Aabb2<u32>,
         data: &[u8],
     ) -> Result<()> {
@@ -432,6 +466,10 @@ impl Visitor for GLVisitor {
             bail!("Trying to update immutable texture.");
         }
 
+        if layer >= texture.params.layers() {
+            bail!("Trying to update texture layer {} out of bounds.", layer);
+        }
+
         if texture.params.format.is_compression() {
             bail!("Trying to update compressed texture.");
         }
@@ -446,38 +484,62 @@ impl Visitor for GLVisitor {
         let (internal_format, format, pixel_type) =
             types::texture_format(texture.params.format, &self.capabilities);
 
-        self.bind_texture(0, texture.id)?;
+        let params = texture.params;
+        let target = params.kind.into();
+        self.bind_texture(0, target, texture.id)?;
 
         if !texture.allocated {
-            self.update_texture_params(texture.id, texture.params.wrap, texture.params.filter, 1)?;
+            self.update_texture_params(target, texture.id, params.wrap, params.filter, 1)?;
 
-            gl::TexImage2D(
-                gl::TEXTURE_2D,
+            if params.kind.is_volume() {
+                self.allocate_texture_volume(params, 0)?;
+            } else {
+                for i in 0..params.layers() {
+                    gl::TexImage2D(
+                        types::texture_image_target(params.kind, i),
+                        0,
+                        internal_format as GLint,
+                        params.dimensions.x as GLsizei,
+                        params.dimensions.y as GLsizei,
+                        0,
+                        format,
+                        pixel_type,
+                        ::std::ptr::null(),
+                    );
+                }
+            }
+
+            self.textures.get_mut(handle).unwrap().allocated = true;
+        }
+
+        if params.kind.is_volume() {
+            gl::TexSubImage3D(
+                target,
                 0,
-                internal_format as GLint,
-                texture.params.dimensions.x as GLsizei,
-                texture.params.dimensions.y as GLsizei,
+                area.min.x as i32,
+                area.min.y as i32,
+                layer as i32,
+                area.dim().x as i32,
+                area.dim().y as i32,
+                1,
+                format,
+                pixel_type,
+                &data[0] as *const u8 as *const ::std::os::raw::c_void,
+            );
+        } else {
+            gl::TexSubImage2D(
+                types::texture_image_target(params.kind, layer),
                 0,
+                area.min.x as i32,
+                area.min.y as i32,
+                area.dim().x as i32,
+                area.dim().y as i32,
                 format,
                 pixel_type,
-                ::std::ptr::null(),
+                &data[0] as *const u8 as *const ::std::os::raw::c_void,
             );
-
-            self.textures.get_mut(handle).unwrap().allocated = true;
         }
 
-        gl::TexSubImage2D(
-            gl::TEXTURE_2D,
-            0,
-            area.min.x as i32,
-            area.min.y as i32,
-            area.dim().x as i32,
-            area.dim().y as i32,
-            format,
-            pixel_type,
-            &data[0] as *const u8 as *const ::std::os::raw::c_void,
-        );
-
         check()
     }
 
@@ -499,8 +561,8 @@ impl Visitor for GLVisitor {
             gl::GenTextures(1, &mut id);
             assert!(id != 0);
 
-            self.bind_texture(0, id)?;
-            self.update_texture_params(id, params.wrap, params.filter, 1)?;
+            self.bind_texture(0, gl::TEXTURE_2D, id)?;
+            self.update_texture_params(gl::TEXTURE_2D, id, params.wrap, params.filter, 1)?;
 
             let (internal_format, format, pixel_type) = params.format.into();
             gl::TexImage2D(
@@ -571,6 +633,11 @@ impl Visitor for GLVisitor {
         params: MeshParams,
         data: Option<MeshData>,
     ) -> Result<()> {
+        // Replaces the mesh which has been reloaded in place.
+        if self.meshes.get(handle).is_some() {
+            self.delete_mesh(handle)?;
+        }
+
         let vbo = self.create_buffer_intern(
             gl::ARRAY_BUFFER,
             params.hint,
@@ -716,6 +783,60 @@ impl Visitor for GLVisitor {
         self.set_viewport(vp)
     }
 
+    unsafe fn read_pixels(
+        &mut self,
+        target: ReadbackTarget,
+        area: math::Aabb2<u32>,
+    ) -> Result<Vec<u8>> {
+        let dim = area.dim();
+        let mut bytes = vec![0; (dim.x * dim.y * 4) as usize];
+
+        match target {
+            ReadbackTarget::Surface(handle) => {
+                let id = self
+                    .surfaces
+                    .get(handle)
+                    .ok_or_else(|| format_err!("{:?} is invalid.", handle))?
+                    .fbo
+                    .as_ref()
+                    .map(|v| v.id)
+                    .unwrap_or(0);
+
+                self.bind_framebuffer(id, false)?;
+                self.read_pixels_intern(area, &mut bytes)?;
+            }
+            ReadbackTarget::RenderTexture(handle) => {
+                let rt = *self
+                    .render_textures
+                    .get(handle)
+                    .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+                if !rt.params.format.is_color() {
+                    bail!("{:?} is not a color render texture.", handle);
+                }
+
+                // Attaches the render texture to a temporary framebuffer.
+                let mut id = 0;
+                gl::GenFramebuffers(1, &mut id);
+                assert!(id != 0);
+
+                self.bind_framebuffer(id, false)?;
+                let rsp = self
+                    .update_framebuffer_render_texture(rt.id, rt.params, 0)
+                    .and_then(|_| self.read_pixels_intern(area, &mut bytes));
+
+                self.bind_framebuffer(0, false)?;
+                gl::DeleteFramebuffers(1, &id);
+                check()?;
+                rsp?;
+            }
+        }
+
+        // The framebuffer has been changed, so the surface should be binded again.
+        self.mutables.borrow_mut().binded_surface = None;
+        Ok(bytes)
+    }
+
     unsafe fn draw(
         &mut self,
         shader: ShaderHandle,
@@ -748,11 +869,28 @@ impl Visitor for GLVisitor {
 
                     let location = shader.hash_uniform_location(field).unwrap();
                     match variable {
-                        UniformVariable::Texture(handle) => {
+                        UniformVariable::Texture(handle)
+                        | UniformVariable::CubeTexture(handle)
+                        | UniformVariable::Texture3D(handle)
+                        | UniformVariable::TextureArray(handle) => {
+                            let kind = match variable {
+                                UniformVariable::CubeTexture(_) => TextureKind::Cube,
+                                UniformVariable::Texture3D(_) => TextureKind::Texture3D,
+                                UniformVariable::TextureArray(_) => TextureKind::Array2D,
+                                _ => TextureKind::Texture2D,
+                            };
+
+                            let texture = match self.textures.get(handle) {
+                                Some(v) if v.params.kind != kind => {
+                                    bail!("{:?} is not a {:?} texture.", handle, kind);
+                                }
+                                Some(v) => v.id,
+                                None => 0,
+                            };
+
                             let v = UniformVariable::I32(index as i32);
-                            let texture = self.textures.get(handle).map(|v| v.id).unwrap_or(0);
                             self.bind_uniform_variable(location, &v)?;
-                            self.bind_texture(index, texture)?;
+                            self.bind_texture(index, kind.into(), texture)?;
                             index += 1;
                         }
                         UniformVariable::RenderTexture(handle) => {
@@ -764,9 +902,9 @@ impl Visitor for GLVisitor {
                                     bail!("The render buffer does not have a sampler.");
                                 }
 
-                                self.bind_texture(index, texture.id)?;
+                                self.bind_texture(index, gl::TEXTURE_2D, texture.id)?;
                             } else {
-                                self.bind_texture(index, 0)?;
+                                self.bind_texture(index, gl::TEXTURE_2D, 0)?;
                             }
 
                             index += 1;
@@ -910,6 +1048,9 @@ impl GLVisitor {
     ) -> Result<()> {
         match *variable {
             UniformVariable::Texture(_) => unreachable!(),
+            UniformVariable::CubeTexture(_) => unreachable!(),
+            UniformVariable::Texture3D(_) => unreachable!(),
+            UniformVariable::TextureArray(_) => unreachable!(),
             UniformVariable::RenderTexture(_) => unreachable!(),
             UniformVariable::I32(v) => gl::Uniform1i(location, v),
             UniformVariable::F32(v) => gl::Uniform1f(location, v),
@@ -940,7 +1081,7 @@ impl GLVisitor {
         check()
     }
 
-    unsafe fn bind_texture(&self, index: usize, id: GLuint) -> Result<()> {
+    unsafe fn bind_texture(&self, index: usize, target: GLenum, id: GLuint) -> Result<()> {
         // assert!(id != 0, "failed to bind texture with 0.");
 
         if index >= MAX_UNIFORM_TEXTURE_SLOTS {
@@ -953,9 +1094,9 @@ impl GLVisitor {
             gl::ActiveTexture(gl::TEXTURE0 + index as GLuint);
         }
 
-        if mutables.binded_textures[index] != Some(id) {
-            mutables.binded_textures[index] = Some(id);
-            gl::BindTexture(gl::TEXTURE_2D, id);
+        if mutables.binded_textures[index] != Some((target, id)) {
+            mutables.binded_textures[index] = Some((target, id));
+            gl::BindTexture(target, id);
         }
 
         check()
@@ -965,9 +1106,9 @@ impl GLVisitor {
         let mut mutables = self.mutables.borrow_mut();
 
         for (i, v) in mutables.binded_textures.iter_mut().enumerate() {
-            if v.is_some() {
+            if let Some((target, _)) = *v {
                 gl::ActiveTexture(gl::TEXTURE0 + i as GLuint);
-                gl::BindTexture(gl::TEXTURE_2D, 0);
+                gl::BindTexture(target, 0);
 
                 *v = None;
             }
@@ -1363,6 +1504,21 @@ impl GLVisitor {
         check()
     }
 
+    unsafe fn read_pixels_intern(&self, area: math::Aabb2<u32>, bytes: &mut [u8]) -> Result<()> {
+        let dim = area.dim();
+        gl::ReadPixels(
+            area.min.x as GLint,
+            area.min.y as GLint,
+            dim.x as GLsizei,
+            dim.y as GLsizei,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            bytes.as_mut_ptr() as *mut ::std::os::raw::c_void,
+        );
+
+        check()
+    }
+
     unsafe fn compile(&self, shader: GLenum, src: &str) -> Result<GLuint> {
         let shader = gl::CreateShader(shader);
         // Attempt to compile the shader
@@ -1378,14 +1534,14 @@ impl GLVisitor {
         if status != GLint::from(gl::TRUE) {
             let mut len = 0;
             gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
-            let mut buf = Vec::with_capacity(len as usize);
-            buf.set_len((len as usize) - 1); // subtract 1 to skip the trailing null character
+            let mut buf = vec![0u8; len as usize];
             gl::GetShaderInfoLog(
                 shader,
                 len,
                 ::std::ptr::null_mut(),
                 buf.as_mut_ptr() as *mut GLchar,
             );
+            buf.pop(); // skip the trailing null character
 
             bail!("{:?}\n{:?}", ::std::str::from_utf8(&buf).unwrap(), src);
         } else {
@@ -1407,14 +1563,14 @@ impl GLVisitor {
         if status != GLint::from(gl::TRUE) {
             let mut len: GLint = 0;
             gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut len);
-            let mut buf = Vec::with_capacity(len as usize);
-            buf.set_len((len as usize) - 1); // subtract 1 to skip the trailing null character
+            let mut buf = vec![0u8; len as usize];
             gl::GetProgramInfoLog(
                 program,
                 len,
                 ::std::ptr::null_mut(),
                 buf.as_mut_ptr() as *mut GLchar,
             );
+            buf.pop(); // skip the trailing null character
 
             bail!("{:?}", ::std::str::from_utf8(&buf).unwrap());
         } else {
@@ -1484,14 +1640,18 @@ impl GLVisitor {
 
     unsafe fn update_texture_params(
         &self,
+        target: GLenum,
         id: GLuint,
         wrap: TextureWrap,
         filter: TextureFilter,
         levels: u32,
     ) -> Result<GLuint> {
         let wrap: GLenum = wrap.into();
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap as GLint);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap as GLint);
+        gl::TexParameteri(target, gl::TEXTURE_WRAP_S, wrap as GLint);
+        gl::TexParameteri(target, gl::TEXTURE_WRAP_T, wrap as GLint);
+        if target == gl::TEXTURE_3D {
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_R, wrap as GLint);
+        }
 
         match filter {
             TextureFilter::Nearest => {
@@ -1501,8 +1661,8 @@ impl GLVisitor {
                     gl::NEAREST
                 };
 
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+                gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+                gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
             }
             TextureFilter::Linear => {
                 let min_filter = if levels > 1 {
@@ -1511,24 +1671,46 @@ impl GLVisitor {
                     gl::LINEAR
                 };
 
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+                gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+                gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
             }
         }
 
         if levels > 1 {
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_BASE_LEVEL, 0);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, (levels - 1) as GLint);
+            gl::TexParameteri(target, gl::TEXTURE_BASE_LEVEL, 0);
+            gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, (levels - 1) as GLint);
         }
 
         Ok(id)
     }
 
+    // Allocates the storage of a mipmap level of 3D texture or texture array, the layers
+    // are filled with `TexSubImage3D` later.
+    unsafe fn allocate_texture_volume(&self, params: TextureParams, level: GLint) -> Result<()> {
+        let (internal_format, format, pixel_type) =
+            types::texture_format(params.format, &self.capabilities);
+
+        gl::TexImage3D(
+            params.kind.into(),
+            level,
+            internal_format as GLint,
+            (params.dimensions.x as GLsizei >> level).max(1),
+            (params.dimensions.y as GLsizei >> level).max(1),
+            params.layers() as GLsizei,
+            0,
+            format,
+            pixel_type,
+            ::std::ptr::null(),
+        );
+
+        Ok(())
+    }
+
     unsafe fn delete_texture_intern(&mut self, id: GLuint) -> Result<()> {
         let mut mutables = self.mutables.borrow_mut();
 
         for v in mutables.binded_textures.iter_mut() {
-            if *v == Some(id) {
+            if v.map(|v| v.1) == Some(id) {
                 *v = None;
             }
         }
@@ -1,4 +1,5 @@
 use super::super::assets::prelude::*;
+use super::super::readback::ReadbackTarget;
 use super::{UniformVar, Visitor};
 
 use errors::*;
@@ -47,6 +48,7 @@ impl Visitor for HeadlessVisitor {
     unsafe fn update_texture(
         &mut self,
         _: TextureHandle,
+        _: u32,
         _: math::Aabb2<u32>,
         _: &[u8],
     ) -> Result<()> {
@@ -112,6 +114,11 @@ impl Visitor for HeadlessVisitor {
         Ok(())
     }
 
+    unsafe fn read_pixels(&mut self, _: ReadbackTarget, area: math::Aabb2<u32>) -> Result<Vec<u8>> {
+        let dim = area.dim();
+        Ok(vec![0; (dim.x * dim.y * 4) as usize])
+    }
+
     unsafe fn flush(&mut self) -> Result<()> {
         Ok(())
     }
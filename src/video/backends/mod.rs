@@ -4,8 +4,10 @@
 pub mod frame;
 pub mod gl;
 pub mod headless;
+pub mod software;
 
 use super::assets::prelude::*;
+use super::readback::ReadbackTarget;
 
 use errors::*;
 use math;
@@ -36,9 +38,12 @@ pub trait Visitor {
         bytes: Option<TextureData>,
     ) -> Result<()>;
 
+    /// Updates a subregion of texture, the `layer` is the face index of cube map, the depth
+    /// slice of 3D texture or the layer of texture array, and always zero for 2D texture.
     unsafe fn update_texture(
         &mut self,
         handle: TextureHandle,
+        layer: u32,
         area: math::Aabb2<u32>,
         bytes: &[u8],
     ) -> Result<()>;
@@ -91,6 +96,14 @@ pub trait Visitor {
 
     unsafe fn update_surface_viewport(&mut self, vp: SurfaceViewport) -> Result<()>;
 
+    /// Reads the pixels in `area` of target as tightly packed RGBA8, the rows are stored from
+    /// the lower left corner. It will be called after all the draw calls of frame.
+    unsafe fn read_pixels(
+        &mut self,
+        target: ReadbackTarget,
+        area: math::Aabb2<u32>,
+    ) -> Result<Vec<u8>>;
+
     /// Blocks until all execution is complete. Such effects include all changes to render state, all
     /// changes to connection state, and all changes to the frame buffer contents.
     unsafe fn flush(&mut self) -> Result<()>;
@@ -0,0 +1,1614 @@
+//! A software rasterizer which draws triangles into CPU framebuffers.
+//!
+//! It's slow, but it runs everywhere and produces pixel-exact results, which makes it
+//! suitable for rendering tests on machines without GPU. The pixels of the default
+//! framebuffer and render textures could be inspected with `SoftwareDevice`.
+//!
+//! Since GLSL sources could not be executed on CPU, every shader is driven by a small
+//! built-in shading model, unless a `Program` written in Rust has been registered with
+//! `SoftwareDevice::set_program`. The built-in shading model:
+//!
+//! 1. Transforms the `Position` attribute by the `u_MVPMatrix` uniform, or the first
+//!    `Matrix4f` uniform of the draw call if there is no `u_MVPMatrix`;
+//! 2. Outputs the `Color0` attribute (white if not declared in shader), multiplied by
+//!    the first 2D texture uniform sampled at `Texcoord0`.
+//!
+//! Cube maps, 3D textures and texture arrays could only be sampled by `Program` with
+//! `Uniforms::sample_cube`, `Uniforms::sample_3d` and `Uniforms::sample_layer`.
+//!
+//! Only triangles are rasterized, the draw calls of points and lines are accepted but
+//! leave the framebuffer untouched.
+
+use std::ptr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use errors::*;
+use math;
+use math::Matrix;
+use utils::hash::{FastHashMap, FastHashSet};
+use utils::hash_value::HashValue;
+
+use super::super::assets::mesh::VertexAttribute;
+use super::super::assets::prelude::*;
+use super::super::readback::ReadbackTarget;
+use super::super::MAX_VERTEX_ATTRIBUTES;
+use super::{UniformVar, Visitor};
+
+/// Maximum number of interpolated values between vertex and fragment shader.
+pub const MAX_VARYINGS: usize = 16;
+
+/// The values which are interpolated across the primitive.
+pub type Varyings = [f32; MAX_VARYINGS];
+
+/// Transforms a vertex into clip space, and writes the varyings.
+pub type VertexShader =
+    dyn Fn(&Uniforms, &Attributes, &mut Varyings) -> math::Vector4<f32> + Send + Sync;
+
+/// Shades a fragment with the interpolated varyings, returns `None` to discard it.
+pub type FragmentShader = dyn Fn(&Uniforms, &Varyings) -> Option<math::Color<f32>> + Send + Sync;
+
+/// A pair of vertex and fragment shaders written in Rust.
+pub struct Program {
+    vs: Box<VertexShader>,
+    fs: Box<FragmentShader>,
+}
+
+impl Program {
+    pub fn new<V, F>(vs: V, fs: F) -> Self
+    where
+        V: Fn(&Uniforms, &Attributes, &mut Varyings) -> math::Vector4<f32> + Send + Sync + 'static,
+        F: Fn(&Uniforms, &Varyings) -> Option<math::Color<f32>> + Send + Sync + 'static,
+    {
+        Program {
+            vs: Box::new(vs),
+            fs: Box::new(fs),
+        }
+    }
+}
+
+/// The attributes of a vertex. Like OpenGL, the missing components are filled
+/// with `(0, 0, 0, 1)`.
+#[derive(Debug, Copy, Clone)]
+pub struct Attributes {
+    values: [math::Vector4<f32>; MAX_VERTEX_ATTRIBUTES],
+    mask: u32,
+}
+
+impl Attributes {
+    /// Gets the value of attribute.
+    #[inline]
+    pub fn get(&self, name: Attribute) -> math::Vector4<f32> {
+        self.values[name as usize]
+    }
+
+    /// Returns true if the attribute is declared in shader and provided by mesh.
+    #[inline]
+    pub fn contains(&self, name: Attribute) -> bool {
+        self.mask & (1 << name as u32) != 0
+    }
+}
+
+/// The uniform variables of the draw call, and the textures they referenced.
+pub struct Uniforms<'a> {
+    vars: &'a [UniformVar],
+    textures: &'a FastHashMap<TextureHandle, Texture>,
+    render_textures: &'a FastHashMap<RenderTextureHandle, RenderTexture>,
+}
+
+impl<'a> Uniforms<'a> {
+    /// Gets the uniform variable of the draw call.
+    pub fn get<T>(&self, field: T) -> Option<UniformVariable>
+    where
+        T: Into<HashValue<str>>,
+    {
+        let field = field.into();
+        self.vars.iter().find(|v| v.0 == field).map(|v| v.1)
+    }
+
+    /// Samples the texture which is bound to `field`, returns `(0, 0, 0, 1)` if
+    /// there is no texture.
+    pub fn sample<T>(&self, field: T, texcoord: math::Vector2<f32>) -> math::Color<f32>
+    where
+        T: Into<HashValue<str>>,
+    {
+        match self.get(field) {
+            Some(v) => self.sample_variable(v, texcoord),
+            None => math::Color::new(0.0, 0.0, 0.0, 1.0),
+        }
+    }
+
+    /// Samples the cube map which is bound to `field` with a direction vector, returns
+    /// `(0, 0, 0, 1)` if there is no cube map.
+    pub fn sample_cube<T>(&self, field: T, direction: math::Vector3<f32>) -> math::Color<f32>
+    where
+        T: Into<HashValue<str>>,
+    {
+        if let Some(UniformVariable::CubeTexture(handle)) = self.get(field) {
+            if let Some(texture) = self.textures.get(&handle) {
+                if texture.params.kind == TextureKind::Cube {
+                    let (face, texcoord) = cube_texcoord(direction);
+                    let params = &texture.params;
+                    let sampler = Sampler {
+                        dimensions: params.dimensions,
+                        wrap: TextureWrap::Clamp,
+                        filter: params.filter,
+                    };
+
+                    return sampler.sample(&texture.layers[face.layer() as usize], texcoord);
+                }
+            }
+        }
+
+        math::Color::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    /// Samples the 3D texture which is bound to `field`, returns `(0, 0, 0, 1)` if there
+    /// is no 3D texture. The slices are interpolated if the filter is linear.
+    pub fn sample_3d<T>(&self, field: T, texcoord: math::Vector3<f32>) -> math::Color<f32>
+    where
+        T: Into<HashValue<str>>,
+    {
+        if let Some(UniformVariable::Texture3D(handle)) = self.get(field) {
+            if let Some(texture) = self.textures.get(&handle) {
+                if texture.params.kind == TextureKind::Texture3D {
+                    let params = &texture.params;
+                    let sampler = Sampler {
+                        dimensions: params.dimensions,
+                        wrap: params.wrap,
+                        filter: params.filter,
+                    };
+
+                    let depth = params.depth as i32;
+                    let slice = |z: i32| {
+                        let layer = wrap(params.wrap, z, depth) as usize;
+                        sampler.sample(&texture.layers[layer], texcoord.truncate())
+                    };
+
+                    let z = texcoord.z * depth as f32;
+                    return match params.filter {
+                        TextureFilter::Nearest => slice(z.floor() as i32),
+                        TextureFilter::Linear => {
+                            let z = z - 0.5;
+                            let z0 = z.floor();
+                            let (a, b) = (slice(z0 as i32), slice(z0 as i32 + 1));
+                            let f = z - z0;
+                            math::Color::new(
+                                a.r + (b.r - a.r) * f,
+                                a.g + (b.g - a.g) * f,
+                                a.b + (b.b - a.b) * f,
+                                a.a + (b.a - a.a) * f,
+                            )
+                        }
+                    };
+                }
+            }
+        }
+
+        math::Color::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    /// Samples a layer of the texture array which is bound to `field`, returns `(0, 0, 0,
+    /// 1)` if there is no texture array. The layer is clamped to the last one.
+    pub fn sample_layer<T>(
+        &self,
+        field: T,
+        texcoord: math::Vector2<f32>,
+        layer: u32,
+    ) -> math::Color<f32>
+    where
+        T: Into<HashValue<str>>,
+    {
+        if let Some(UniformVariable::TextureArray(handle)) = self.get(field) {
+            if let Some(texture) = self.textures.get(&handle) {
+                if texture.params.kind == TextureKind::Array2D {
+                    let params = &texture.params;
+                    let sampler = Sampler {
+                        dimensions: params.dimensions,
+                        wrap: params.wrap,
+                        filter: params.filter,
+                    };
+
+                    let layer = layer.min(params.depth - 1) as usize;
+                    return sampler.sample(&texture.layers[layer], texcoord);
+                }
+            }
+        }
+
+        math::Color::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    fn sample_variable(
+        &self,
+        v: UniformVariable,
+        texcoord: math::Vector2<f32>,
+    ) -> math::Color<f32> {
+        match v {
+            UniformVariable::Texture(handle) => {
+                if let Some(texture) = self.textures.get(&handle) {
+                    let params = &texture.params;
+                    let sampler = Sampler {
+                        dimensions: params.dimensions,
+                        wrap: params.wrap,
+                        filter: params.filter,
+                    };
+
+                    return sampler.sample(&texture.layers[0], texcoord);
+                }
+            }
+            UniformVariable::RenderTexture(handle) => {
+                if let Some(texture) = self.render_textures.get(&handle) {
+                    let params = &texture.params;
+                    let sampler = Sampler {
+                        dimensions: params.dimensions,
+                        wrap: params.wrap,
+                        filter: params.filter,
+                    };
+
+                    if params.format.is_color() {
+                        return sampler.sample(&texture.color, texcoord);
+                    }
+
+                    let d = sampler.sample_depth(&texture.depth, texcoord);
+                    return math::Color::new(d, d, d, 1.0);
+                }
+            }
+            _ => {}
+        }
+
+        math::Color::new(0.0, 0.0, 0.0, 1.0)
+    }
+}
+
+/// The shared states of software rasterizer. It owns the default framebuffer, and
+/// could be used to inspect the rendering results.
+pub struct SoftwareDevice {
+    state: Mutex<State>,
+}
+
+impl SoftwareDevice {
+    /// Creates a new `SoftwareDevice` with the dimensions of default framebuffer.
+    pub fn new(dimensions: math::Vector2<u32>) -> Arc<Self> {
+        let len = (dimensions.x * dimensions.y) as usize;
+
+        let state = State {
+            dimensions: dimensions,
+            color: vec![0; len * 4],
+            depth: vec![1.0; len],
+            programs: FastHashMap::default(),
+            surfaces: FastHashMap::default(),
+            shaders: FastHashMap::default(),
+            textures: FastHashMap::default(),
+            render_textures: FastHashMap::default(),
+            meshes: FastHashMap::default(),
+            binded_surface: None,
+            binded_frame_surfaces: FastHashSet::default(),
+            scissor: SurfaceScissor::Disable,
+            view: SurfaceViewport {
+                position: math::Vector2::new(0, 0),
+                size: dimensions,
+            },
+        };
+
+        Arc::new(SoftwareDevice {
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Gets the dimensions of default framebuffer.
+    pub fn dimensions(&self) -> math::Vector2<u32> {
+        self.state().dimensions
+    }
+
+    /// Gets the RGBA8 pixels of default framebuffer. Like `glReadPixels`, the rows are
+    /// stored from the lower left corner.
+    pub fn pixels(&self) -> Vec<u8> {
+        self.state().color.clone()
+    }
+
+    /// Gets the RGBA8 color of a pixel in default framebuffer, relative to the lower
+    /// left corner.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let state = self.state();
+        assert!(x < state.dimensions.x && y < state.dimensions.y);
+
+        let i = ((y * state.dimensions.x + x) * 4) as usize;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&state.color[i..i + 4]);
+        pixel
+    }
+
+    /// Gets the RGBA8 pixels of a color render texture. The rows are stored from the
+    /// lower left corner.
+    pub fn render_texture_pixels(&self, handle: RenderTextureHandle) -> Option<Vec<u8>> {
+        let state = self.state();
+        match state.render_textures.get(&handle) {
+            Some(texture) if texture.params.format.is_color() => Some(texture.color.clone()),
+            _ => None,
+        }
+    }
+
+    /// Drives the shader with `Program` instead of the built-in shading model.
+    pub fn set_program(&self, handle: ShaderHandle, program: Program) {
+        self.state().programs.insert(handle, Arc::new(program));
+    }
+
+    /// Removes the `Program` of shader.
+    pub fn remove_program(&self, handle: ShaderHandle) {
+        self.state().programs.remove(&handle);
+    }
+
+    fn state(&self) -> MutexGuard<State> {
+        self.state.lock().unwrap()
+    }
+}
+
+pub struct SoftwareVisitor {
+    device: Arc<SoftwareDevice>,
+}
+
+impl SoftwareVisitor {
+    pub fn new(device: Arc<SoftwareDevice>) -> Self {
+        SoftwareVisitor { device: device }
+    }
+}
+
+impl Visitor for SoftwareVisitor {
+    unsafe fn create_surface(
+        &mut self,
+        handle: SurfaceHandle,
+        params: SurfaceParams,
+    ) -> Result<()> {
+        let mut state = self.device.state();
+
+        let mut dimensions = None;
+        for (i, v) in params.colors.iter().enumerate() {
+            if let Some(v) = *v {
+                let texture = state
+                    .render_textures
+                    .get(&v)
+                    .ok_or_else(|| format_err!("{:?} is invalid.", v))?;
+
+                if !texture.params.format.is_color() {
+                    bail!("Incompitable(mismatch format) attachments!");
+                }
+
+                if i > 0 && dimensions != Some(texture.params.dimensions) {
+                    bail!("Incompitable(mismatch dimensons) attachments!");
+                }
+
+                dimensions = Some(texture.params.dimensions);
+            }
+        }
+
+        if let Some(v) = params.depth_stencil {
+            let texture = state
+                .render_textures
+                .get(&v)
+                .ok_or_else(|| format_err!("{:?} is invalid.", v))?;
+
+            if texture.params.format.is_color() {
+                bail!("Incompitable(mismatch format) attachments!");
+            }
+
+            if dimensions.is_some() && dimensions != Some(texture.params.dimensions) {
+                bail!("Incompitable(mismatch dimensons) attachments!");
+            }
+        }
+
+        state.surfaces.insert(handle, params);
+        Ok(())
+    }
+
+    unsafe fn delete_surface(&mut self, handle: SurfaceHandle) -> Result<()> {
+        let mut state = self.device.state();
+        state
+            .surfaces
+            .remove(&handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+        if state.binded_surface == Some(handle) {
+            state.binded_surface = None;
+        }
+
+        Ok(())
+    }
+
+    unsafe fn create_shader(
+        &mut self,
+        handle: ShaderHandle,
+        params: ShaderParams,
+        _: &str,
+        _: &str,
+    ) -> Result<()> {
+        self.device.state().shaders.insert(handle, params);
+        Ok(())
+    }
+
+    unsafe fn delete_shader(&mut self, handle: ShaderHandle) -> Result<()> {
+        let mut state = self.device.state();
+        state.programs.remove(&handle);
+        state
+            .shaders
+            .remove(&handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+        Ok(())
+    }
+
+    unsafe fn create_texture(
+        &mut self,
+        handle: TextureHandle,
+        params: TextureParams,
+        data: Option<TextureData>,
+    ) -> Result<()> {
+        let components = components(params.format)?;
+        let len = (params.dimensions.x * params.dimensions.y) as usize;
+        let layers = params.layers() as usize;
+        let mut pixels = vec![vec![0; len * 4]; layers];
+
+        if let Some(data) = data {
+            // Only the largest mipmap of each layer is used.
+            let levels = data.bytes.len() / layers;
+            if levels > 0 {
+                let area = math::Aabb2::new(
+                    math::Point2::new(0, 0),
+                    math::Point2::new(params.dimensions.x, params.dimensions.y),
+                );
+
+                for (i, v) in pixels.iter_mut().enumerate() {
+                    let bytes = &data.bytes[i * levels];
+                    copy_pixels(v, params.dimensions, area, bytes, components);
+                }
+            }
+        }
+
+        let texture = Texture {
+            params: params,
+            layers: pixels,
+        };
+
+        self.device.state().textures.insert(handle, texture);
+        Ok(())
+    }
+
+    unsafe fn update_texture(
+        &mut self,
+        handle: TextureHandle,
+        layer: u32,
+        area: math::Aabb2<u32>,
+        data: &[u8],
+    ) -> Result<()> {
+        let mut state = self.device.state();
+        let texture = state
+            .textures
+            .get_mut(&handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+        if texture.params.hint == TextureHint::Immutable {
+            bail!("Trying to update immutable texture.");
+        }
+
+        if layer >= texture.params.layers() {
+            bail!("Trying to update texture layer {} out of bounds.", layer);
+        }
+
+        let components = components(texture.params.format)?;
+        let dimensions = texture.params.dimensions;
+        if area.max.x > dimensions.x
+            || area.max.y > dimensions.y
+            || data.len() < (area.volume() * u32::from(components)) as usize
+        {
+            bail!("Trying to update texture data out of bounds.");
+        }
+
+        let pixels = &mut texture.layers[layer as usize];
+        copy_pixels(pixels, dimensions, area, data, components);
+        Ok(())
+    }
+
+    unsafe fn delete_texture(&mut self, handle: TextureHandle) -> Result<()> {
+        self.device
+            .state()
+            .textures
+            .remove(&handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+        Ok(())
+    }
+
+    unsafe fn create_render_texture(
+        &mut self,
+        handle: RenderTextureHandle,
+        params: RenderTextureParams,
+    ) -> Result<()> {
+        let len = (params.dimensions.x * params.dimensions.y) as usize;
+        let texture = if params.format.is_color() {
+            RenderTexture {
+                params: params,
+                color: vec![0; len * 4],
+                depth: Vec::new(),
+            }
+        } else {
+            RenderTexture {
+                params: params,
+                color: Vec::new(),
+                depth: vec![1.0; len],
+            }
+        };
+
+        self.device.state().render_textures.insert(handle, texture);
+        Ok(())
+    }
+
+    unsafe fn delete_render_texture(&mut self, handle: RenderTextureHandle) -> Result<()> {
+        self.device
+            .state()
+            .render_textures
+            .remove(&handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+        Ok(())
+    }
+
+    unsafe fn create_mesh(
+        &mut self,
+        handle: MeshHandle,
+        params: MeshParams,
+        data: Option<MeshData>,
+    ) -> Result<()> {
+        let mut vptr = vec![0; params.vertex_buffer_len()];
+        let mut iptr = vec![0; params.index_buffer_len()];
+
+        if let Some(data) = data {
+            vptr[0..data.vptr.len()].copy_from_slice(&data.vptr);
+            iptr[0..data.iptr.len()].copy_from_slice(&data.iptr);
+        }
+
+        let mesh = Mesh {
+            params: params,
+            vptr: vptr,
+            iptr: iptr,
+        };
+
+        self.device.state().meshes.insert(handle, mesh);
+        Ok(())
+    }
+
+    unsafe fn update_vertex_buffer(
+        &mut self,
+        handle: MeshHandle,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<()> {
+        let mut state = self.device.state();
+        let mesh = state
+            .meshes
+            .get_mut(&handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+        if mesh.params.hint == MeshHint::Immutable {
+            bail!("Trying to update immutable buffer");
+        }
+
+        if offset + data.len() > mesh.vptr.len() {
+            bail!("Trying to update buffer data out of bounds.");
+        }
+
+        mesh.vptr[offset..offset + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    unsafe fn update_index_buffer(
+        &mut self,
+        handle: MeshHandle,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<()> {
+        let mut state = self.device.state();
+        let mesh = state
+            .meshes
+            .get_mut(&handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+        if mesh.params.hint == MeshHint::Immutable {
+            bail!("Trying to update immutable buffer");
+        }
+
+        if offset + data.len() > mesh.iptr.len() {
+            bail!("Trying to update buffer data out of bounds.");
+        }
+
+        mesh.iptr[offset..offset + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    unsafe fn delete_mesh(&mut self, handle: MeshHandle) -> Result<()> {
+        self.device
+            .state()
+            .meshes
+            .remove(&handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+        Ok(())
+    }
+
+    unsafe fn bind(&mut self, id: SurfaceHandle, _: math::Vector2<u32>) -> Result<()> {
+        let mut state = self.device.state();
+        if state.binded_surface == Some(id) {
+            return Ok(());
+        }
+
+        let params = *state
+            .surfaces
+            .get(&id)
+            .ok_or_else(|| format_err!("{:?} is invalid.", id))?;
+
+        // Reset the viewport and scissor box.
+        state.view = SurfaceViewport {
+            position: math::Vector2::new(0, 0),
+            size: state.target_dimensions(&params),
+        };
+
+        state.scissor = SurfaceScissor::Disable;
+
+        if !state.binded_frame_surfaces.contains(&id) {
+            let (mut color, mut depth) = state.take_target(&params);
+
+            if let Some(v) = params.clear_color {
+                if let Some(ref mut color) = color {
+                    let v: [u8; 4] = v.into();
+                    for pixel in color.chunks_mut(4) {
+                        pixel.copy_from_slice(&v);
+                    }
+                }
+            }
+
+            if let Some(v) = params.clear_depth {
+                if let Some(ref mut depth) = depth {
+                    for d in depth.iter_mut() {
+                        *d = v;
+                    }
+                }
+            }
+
+            state.restore_target(&params, color, depth);
+            state.binded_frame_surfaces.insert(id);
+        }
+
+        state.binded_surface = Some(id);
+        Ok(())
+    }
+
+    unsafe fn update_surface_scissor(&mut self, scissor: SurfaceScissor) -> Result<()> {
+        self.device.state().scissor = scissor;
+        Ok(())
+    }
+
+    unsafe fn update_surface_viewport(&mut self, vp: SurfaceViewport) -> Result<()> {
+        self.device.state().view = vp;
+        Ok(())
+    }
+
+    unsafe fn read_pixels(
+        &mut self,
+        target: ReadbackTarget,
+        area: math::Aabb2<u32>,
+    ) -> Result<Vec<u8>> {
+        self.device.state().read_pixels(target, area)
+    }
+
+    unsafe fn draw(
+        &mut self,
+        shader: ShaderHandle,
+        mesh: MeshHandle,
+        mesh_index: MeshIndex,
+        uniforms: &[UniformVar],
+    ) -> Result<u32> {
+        let mut state = self.device.state();
+
+        let surface = state
+            .binded_surface
+            .and_then(|v| state.surfaces.get(&v).cloned())
+            .ok_or_else(|| format_err!("Can not draw without binded surface."))?;
+
+        let dimensions = state.target_dimensions(&surface);
+        let (mut color, mut depth) = state.take_target(&surface);
+
+        let result = {
+            let mut target = Target {
+                dimensions: dimensions,
+                color: color.as_mut().map(|v| &mut v[..]),
+                depth: depth.as_mut().map(|v| &mut v[..]),
+            };
+
+            state.draw(&mut target, shader, mesh, mesh_index, uniforms)
+        };
+
+        state.restore_target(&surface, color, depth);
+        result
+    }
+
+    unsafe fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn advance(&mut self) -> Result<()> {
+        let mut state = self.device.state();
+        state.binded_frame_surfaces.clear();
+        state.binded_surface = None;
+        Ok(())
+    }
+}
+
+struct Texture {
+    params: TextureParams,
+    layers: Vec<Vec<u8>>,
+}
+
+struct RenderTexture {
+    params: RenderTextureParams,
+    color: Vec<u8>,
+    depth: Vec<f32>,
+}
+
+struct Mesh {
+    params: MeshParams,
+    vptr: Vec<u8>,
+    iptr: Vec<u8>,
+}
+
+struct State {
+    dimensions: math::Vector2<u32>,
+    color: Vec<u8>,
+    depth: Vec<f32>,
+
+    programs: FastHashMap<ShaderHandle, Arc<Program>>,
+    surfaces: FastHashMap<SurfaceHandle, SurfaceParams>,
+    shaders: FastHashMap<ShaderHandle, ShaderParams>,
+    textures: FastHashMap<TextureHandle, Texture>,
+    render_textures: FastHashMap<RenderTextureHandle, RenderTexture>,
+    meshes: FastHashMap<MeshHandle, Mesh>,
+
+    binded_surface: Option<SurfaceHandle>,
+    binded_frame_surfaces: FastHashSet<SurfaceHandle>,
+    scissor: SurfaceScissor,
+    view: SurfaceViewport,
+}
+
+struct Target<'a> {
+    dimensions: math::Vector2<u32>,
+    color: Option<&'a mut [u8]>,
+    depth: Option<&'a mut [f32]>,
+}
+
+#[derive(Clone, Copy)]
+struct ClipVertex {
+    position: math::Vector4<f32>,
+    varyings: Varyings,
+}
+
+#[derive(Clone, Copy)]
+struct WindowVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    inv_w: f32,
+    varyings: Varyings,
+}
+
+impl State {
+    fn is_default_target(params: &SurfaceParams) -> bool {
+        params.colors[0].is_none() && params.depth_stencil.is_none()
+    }
+
+    fn target_dimensions(&self, params: &SurfaceParams) -> math::Vector2<u32> {
+        if State::is_default_target(params) {
+            return self.dimensions;
+        }
+
+        params
+            .colors
+            .iter()
+            .filter_map(|v| *v)
+            .chain(params.depth_stencil)
+            .filter_map(|v| self.render_textures.get(&v))
+            .map(|v| v.params.dimensions)
+            .next()
+            .unwrap_or_else(|| math::Vector2::new(0, 0))
+    }
+
+    // Moves the buffers of render target out, so we could sample the textures while
+    // writing into the target.
+    fn take_target(&mut self, params: &SurfaceParams) -> (Option<Vec<u8>>, Option<Vec<f32>>) {
+        use std::mem::replace;
+
+        if State::is_default_target(params) {
+            return (
+                Some(replace(&mut self.color, Vec::new())),
+                Some(replace(&mut self.depth, Vec::new())),
+            );
+        }
+
+        let color = params.colors[0]
+            .and_then(|v| self.render_textures.get_mut(&v))
+            .map(|v| replace(&mut v.color, Vec::new()));
+
+        let depth = params
+            .depth_stencil
+            .and_then(|v| self.render_textures.get_mut(&v))
+            .map(|v| replace(&mut v.depth, Vec::new()));
+
+        (color, depth)
+    }
+
+    fn restore_target(
+        &mut self,
+        params: &SurfaceParams,
+        color: Option<Vec<u8>>,
+        depth: Option<Vec<f32>>,
+    ) {
+        if State::is_default_target(params) {
+            self.color = color.unwrap();
+            self.depth = depth.unwrap();
+            return;
+        }
+
+        if let Some(color) = color {
+            if let Some(v) = params.colors[0].and_then(|v| self.render_textures.get_mut(&v)) {
+                v.color = color;
+            }
+        }
+
+        if let Some(depth) = depth {
+            if let Some(v) = params
+                .depth_stencil
+                .and_then(|v| self.render_textures.get_mut(&v))
+            {
+                v.depth = depth;
+            }
+        }
+    }
+
+    fn read_pixels(&self, target: ReadbackTarget, area: math::Aabb2<u32>) -> Result<Vec<u8>> {
+        let texture = match target {
+            ReadbackTarget::Surface(handle) => {
+                let params = self
+                    .surfaces
+                    .get(&handle)
+                    .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+                if State::is_default_target(params) {
+                    return Ok(read_pixels(&self.color, self.dimensions, area));
+                }
+
+                params.colors[0]
+                    .ok_or_else(|| format_err!("{:?} has none color attachment.", handle))?
+            }
+            ReadbackTarget::RenderTexture(handle) => handle,
+        };
+
+        match self.render_textures.get(&texture) {
+            Some(v) if v.params.format.is_color() => {
+                Ok(read_pixels(&v.color, v.params.dimensions, area))
+            }
+            Some(_) => bail!("{:?} is not a color render texture.", texture),
+            None => bail!("{:?} is invalid.", texture),
+        }
+    }
+
+    fn draw(
+        &self,
+        target: &mut Target,
+        shader: ShaderHandle,
+        mesh: MeshHandle,
+        mesh_index: MeshIndex,
+        vars: &[UniformVar],
+    ) -> Result<u32> {
+        let params = self
+            .shaders
+            .get(&shader)
+            .ok_or_else(|| format_err!("{:?} is invalid.", shader))?;
+
+        for &(field, variable) in vars {
+            if let Some(tp) = params.uniforms.variable_type(field) {
+                if tp != variable.variable_type() {
+                    let name = params.uniforms.variable_name(field).unwrap();
+                    bail!(
+                        "The uniform {} needs a {:?} instead of {:?}.",
+                        name,
+                        tp,
+                        variable.variable_type(),
+                    );
+                }
+
+                match variable {
+                    UniformVariable::RenderTexture(handle) => {
+                        if let Some(texture) = self.render_textures.get(&handle) {
+                            if !texture.params.sampler {
+                                bail!("The render buffer does not have a sampler.");
+                            }
+                        }
+                    }
+                    UniformVariable::Texture(handle)
+                    | UniformVariable::CubeTexture(handle)
+                    | UniformVariable::Texture3D(handle)
+                    | UniformVariable::TextureArray(handle) => {
+                        let kind = match variable {
+                            UniformVariable::CubeTexture(_) => TextureKind::Cube,
+                            UniformVariable::Texture3D(_) => TextureKind::Texture3D,
+                            UniformVariable::TextureArray(_) => TextureKind::Array2D,
+                            _ => TextureKind::Texture2D,
+                        };
+
+                        if let Some(texture) = self.textures.get(&handle) {
+                            if texture.params.kind != kind {
+                                bail!("{:?} is not a {:?} texture.", handle, kind);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            } else {
+                bail!("Undefined uniform field {:?}.", field);
+            }
+        }
+
+        let mesh = self
+            .meshes
+            .get(&mesh)
+            .ok_or_else(|| format_err!("{:?} is invalid.", mesh))?;
+
+        let (from, len) = match mesh_index {
+            MeshIndex::Ptr(from, len) => {
+                if (from + len) > mesh.params.num_idxes {
+                    bail!("MeshIndex is out of bounds");
+                }
+
+                (from, len)
+            }
+            MeshIndex::SubMesh(index) => {
+                let num = mesh.params.sub_mesh_offsets.len();
+                let from = *mesh
+                    .params
+                    .sub_mesh_offsets
+                    .get(index)
+                    .ok_or_else(|| format_err!("MeshIndex is out of bounds"))?;
+
+                let to = if index == (num - 1) {
+                    mesh.params.num_idxes
+                } else {
+                    mesh.params.sub_mesh_offsets[index + 1]
+                };
+
+                (from, (to - from))
+            }
+            MeshIndex::All => (0, mesh.params.num_idxes),
+        };
+
+        let uniforms = Uniforms {
+            vars: vars,
+            textures: &self.textures,
+            render_textures: &self.render_textures,
+        };
+
+        let program = self.programs.get(&shader);
+
+        // Runs vertex shader.
+        let mut vertices = Vec::with_capacity(len);
+        for i in from..(from + len) {
+            let index = mesh.index(i);
+            if index >= mesh.params.num_verts {
+                bail!("Vertex index {} is out of bounds.", index);
+            }
+
+            let attributes = mesh.fetch(&params.attributes, index);
+            let mut varyings = [0.0; MAX_VARYINGS];
+            let position = match program {
+                Some(program) => (program.vs)(&uniforms, &attributes, &mut varyings),
+                None => builtin_vs(&uniforms, &attributes, &mut varyings),
+            };
+
+            vertices.push(ClipVertex {
+                position: position,
+                varyings: varyings,
+            });
+        }
+
+        // Assembles and rasterizes triangles.
+        let mut raster = Rasterizer {
+            target: target,
+            state: &params.state,
+            view: self.view,
+            scissor: self.scissor,
+            uniforms: &uniforms,
+            program: program.map(|v| &**v),
+        };
+
+        match mesh.params.primitive {
+            MeshPrimitive::Triangles => {
+                for v in vertices.chunks(3) {
+                    if v.len() == 3 {
+                        raster.triangle(&v[0], &v[1], &v[2]);
+                    }
+                }
+            }
+            MeshPrimitive::TriangleStrip => {
+                for i in 2..vertices.len() {
+                    if i % 2 == 0 {
+                        raster.triangle(&vertices[i - 2], &vertices[i - 1], &vertices[i]);
+                    } else {
+                        raster.triangle(&vertices[i - 1], &vertices[i - 2], &vertices[i]);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Ok(mesh.params.primitive.assemble(len as u32))
+    }
+}
+
+impl Mesh {
+    fn index(&self, i: usize) -> usize {
+        unsafe {
+            match self.params.index_format {
+                IndexFormat::U16 => {
+                    ptr::read_unaligned(self.iptr.as_ptr().offset(i as isize * 2) as *const u16)
+                        as usize
+                }
+                IndexFormat::U32 => {
+                    ptr::read_unaligned(self.iptr.as_ptr().offset(i as isize * 4) as *const u32)
+                        as usize
+                }
+            }
+        }
+    }
+
+    fn fetch(&self, layout: &AttributeLayout, index: usize) -> Attributes {
+        let mut attributes = Attributes {
+            values: [math::Vector4::new(0.0, 0.0, 0.0, 1.0); MAX_VERTEX_ATTRIBUTES],
+            mask: 0,
+        };
+
+        let stride = self.params.layout.stride() as usize;
+        for (name, _, _) in layout.iter() {
+            let element = self.params.layout.element(name);
+            let offset = self.params.layout.offset(name);
+
+            if let (Some(element), Some(offset)) = (element, offset) {
+                let mut p = index * stride + offset as usize;
+                let value = &mut attributes.values[name as usize];
+                for i in 0..element.size as usize {
+                    let (v, len) = component(element, &self.vptr[p..]);
+                    value[i] = v;
+                    p += len;
+                }
+
+                attributes.mask |= 1 << name as u32;
+            }
+        }
+
+        attributes
+    }
+}
+
+fn component(element: VertexAttribute, bytes: &[u8]) -> (f32, usize) {
+    unsafe {
+        let p = bytes.as_ptr();
+        let n = element.normalized;
+        match element.format {
+            VertexFormat::Float => (ptr::read_unaligned(p as *const f32), 4),
+            VertexFormat::Byte => {
+                let v = f32::from(*p as i8);
+                (if n { (v / 127.0).max(-1.0) } else { v }, 1)
+            }
+            VertexFormat::UByte => {
+                let v = f32::from(*p);
+                (if n { v / 255.0 } else { v }, 1)
+            }
+            VertexFormat::Short => {
+                let v = f32::from(ptr::read_unaligned(p as *const i16));
+                (if n { (v / 32767.0).max(-1.0) } else { v }, 2)
+            }
+            VertexFormat::UShort => {
+                let v = f32::from(ptr::read_unaligned(p as *const u16));
+                (if n { v / 65535.0 } else { v }, 2)
+            }
+        }
+    }
+}
+
+fn builtin_vs(
+    uniforms: &Uniforms,
+    attributes: &Attributes,
+    varyings: &mut Varyings,
+) -> math::Vector4<f32> {
+    let mvp = match uniforms.get("u_MVPMatrix") {
+        Some(UniformVariable::Matrix4f(v, transpose)) => Some((v, transpose)),
+        _ => uniforms
+            .vars
+            .iter()
+            .filter_map(|v| match v.1 {
+                UniformVariable::Matrix4f(v, transpose) => Some((v, transpose)),
+                _ => None,
+            })
+            .next(),
+    };
+
+    let color = if attributes.contains(Attribute::Color0) {
+        attributes.get(Attribute::Color0)
+    } else {
+        math::Vector4::new(1.0, 1.0, 1.0, 1.0)
+    };
+
+    let color: [f32; 4] = color.into();
+    let texcoord = attributes.get(Attribute::Texcoord0);
+    varyings[0..4].copy_from_slice(&color);
+    varyings[4..6].copy_from_slice(&[texcoord.x, texcoord.y]);
+
+    let position = attributes.get(Attribute::Position);
+    match mvp {
+        Some((v, false)) => math::Matrix4::from(v) * position,
+        Some((v, true)) => math::Matrix4::from(v).transpose() * position,
+        None => position,
+    }
+}
+
+fn builtin_fs(uniforms: &Uniforms, varyings: &Varyings) -> math::Color<f32> {
+    let mut color = math::Color::new(varyings[0], varyings[1], varyings[2], varyings[3]);
+    let texture = uniforms.vars.iter().find(|v| match v.1 {
+        UniformVariable::Texture(_) | UniformVariable::RenderTexture(_) => true,
+        _ => false,
+    });
+
+    if let Some(texture) = texture {
+        let texcoord = math::Vector2::new(varyings[4], varyings[5]);
+        let v = uniforms.sample_variable(texture.1, texcoord);
+        color = math::Color::new(color.r * v.r, color.g * v.g, color.b * v.b, color.a * v.a);
+    }
+
+    color
+}
+
+struct Rasterizer<'a, 'b: 'a> {
+    target: &'a mut Target<'b>,
+    state: &'a RenderState,
+    view: SurfaceViewport,
+    scissor: SurfaceScissor,
+    uniforms: &'a Uniforms<'a>,
+    program: Option<&'a Program>,
+}
+
+impl<'a, 'b> Rasterizer<'a, 'b> {
+    fn triangle(&mut self, v0: &ClipVertex, v1: &ClipVertex, v2: &ClipVertex) {
+        // Clips the triangle against the near and far planes, the other planes are
+        // handled by the bounding box of viewport.
+        let mut polygon = vec![*v0, *v1, *v2];
+        polygon = clip(&polygon, |v| v.position.z + v.position.w);
+        polygon = clip(&polygon, |v| v.position.w - v.position.z);
+
+        if polygon.len() < 3 {
+            return;
+        }
+
+        let vertices: Vec<_> = polygon.iter().map(|v| self.to_window(v)).collect();
+        for i in 2..vertices.len() {
+            self.rasterize(&vertices[0], &vertices[i - 1], &vertices[i]);
+        }
+    }
+
+    fn to_window(&self, v: &ClipVertex) -> WindowVertex {
+        let inv_w = 1.0 / v.position.w;
+        let (x, y, z) = (
+            v.position.x * inv_w,
+            v.position.y * inv_w,
+            v.position.z * inv_w,
+        );
+
+        WindowVertex {
+            x: self.view.position.x as f32 + (x + 1.0) * 0.5 * self.view.size.x as f32,
+            y: self.view.position.y as f32 + (y + 1.0) * 0.5 * self.view.size.y as f32,
+            z: (z + 1.0) * 0.5,
+            inv_w: inv_w,
+            varyings: v.varyings,
+        }
+    }
+
+    fn rasterize(&mut self, v0: &WindowVertex, v1: &WindowVertex, v2: &WindowVertex) {
+        let area = edge(v0, v1, v2.x, v2.y);
+        if area == 0.0 || !area.is_finite() {
+            return;
+        }
+
+        let ccw = area > 0.0;
+        let front = ccw == (self.state.front_face_order == FrontFaceOrder::CounterClockwise);
+        match self.state.cull_face {
+            CullFace::Front if front => return,
+            CullFace::Back if !front => return,
+            _ => {}
+        }
+
+        // Makes the triangle counter-clockwise.
+        let (v1, v2, area) = if ccw { (v1, v2, area) } else { (v2, v1, -area) };
+
+        let (min, max) = self.bounds();
+        let x0 = (v0.x.min(v1.x).min(v2.x).floor() as i32).max(min.x);
+        let y0 = (v0.y.min(v1.y).min(v2.y).floor() as i32).max(min.y);
+        let x1 = (v0.x.max(v1.x).max(v2.x).ceil() as i32).min(max.x);
+        let y1 = (v0.y.max(v1.y).max(v2.y).ceil() as i32).min(max.y);
+
+        let bias = [
+            is_top_left(v1, v2),
+            is_top_left(v2, v0),
+            is_top_left(v0, v1),
+        ];
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let w = [
+                    edge(v1, v2, px, py),
+                    edge(v2, v0, px, py),
+                    edge(v0, v1, px, py),
+                ];
+
+                let inside = (0..3).all(|i| w[i] > 0.0 || (w[i] == 0.0 && bias[i]));
+                if inside {
+                    let b = [w[0] / area, w[1] / area, w[2] / area];
+                    self.fragment(x as usize, y as usize, [v0, v1, v2], b);
+                }
+            }
+        }
+    }
+
+    fn bounds(&self) -> (math::Vector2<i32>, math::Vector2<i32>) {
+        let d = self.target.dimensions;
+        let vp = self.view;
+
+        let mut min = math::Vector2::new(vp.position.x.max(0), vp.position.y.max(0));
+        let mut max = math::Vector2::new(
+            (vp.position.x + vp.size.x as i32).min(d.x as i32),
+            (vp.position.y + vp.size.y as i32).min(d.y as i32),
+        );
+
+        if let SurfaceScissor::Enable { position, size } = self.scissor {
+            min.x = min.x.max(position.x);
+            min.y = min.y.max(position.y);
+            max.x = max.x.min(position.x + size.x as i32);
+            max.y = max.y.min(position.y + size.y as i32);
+        }
+
+        (min, max)
+    }
+
+    fn fragment(&mut self, x: usize, y: usize, v: [&WindowVertex; 3], b: [f32; 3]) {
+        let index = y * self.target.dimensions.x as usize + x;
+        let z = b[0] * v[0].z + b[1] * v[1].z + b[2] * v[2].z;
+
+        // Like OpenGL, the depth buffer is not touched if the depth test is disabled.
+        let depth_test = self.state.depth_test != Comparison::Always || self.state.depth_write;
+        if depth_test {
+            if let Some(ref depth) = self.target.depth {
+                if !compare(self.state.depth_test, z, depth[index]) {
+                    return;
+                }
+            }
+        }
+
+        // Interpolates varyings with perspective correction.
+        let w = [b[0] * v[0].inv_w, b[1] * v[1].inv_w, b[2] * v[2].inv_w];
+        let sum = w[0] + w[1] + w[2];
+        let mut varyings = [0.0; MAX_VARYINGS];
+        for (i, value) in varyings.iter_mut().enumerate() {
+            *value =
+                (w[0] * v[0].varyings[i] + w[1] * v[1].varyings[i] + w[2] * v[2].varyings[i]) / sum;
+        }
+
+        let src = match self.program {
+            Some(program) => match (program.fs)(self.uniforms, &varyings) {
+                Some(color) => color,
+                None => return,
+            },
+            None => builtin_fs(self.uniforms, &varyings),
+        };
+
+        if depth_test && self.state.depth_write {
+            if let Some(ref mut depth) = self.target.depth {
+                depth[index] = z;
+            }
+        }
+
+        if let Some(ref mut color) = self.target.color {
+            let pixel = &mut color[index * 4..index * 4 + 4];
+            let dst = math::Color::new(
+                f32::from(pixel[0]) / 255.0,
+                f32::from(pixel[1]) / 255.0,
+                f32::from(pixel[2]) / 255.0,
+                f32::from(pixel[3]) / 255.0,
+            );
+
+            let v = match self.state.color_blend {
+                Some((equation, sf, df)) => blend(equation, sf, df, src, dst),
+                None => src,
+            };
+
+            let mask = self.state.color_write;
+            let v = [(mask.0, v.r), (mask.1, v.g), (mask.2, v.b), (mask.3, v.a)];
+            for (i, &(write, v)) in v.iter().enumerate() {
+                if write {
+                    pixel[i] = (v.max(0.0).min(1.0) * 255.0).round() as u8;
+                }
+            }
+        }
+    }
+}
+
+fn edge(a: &WindowVertex, b: &WindowVertex, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+// The pixels exactly on the shared edge of triangles are owned by only one of them.
+fn is_top_left(a: &WindowVertex, b: &WindowVertex) -> bool {
+    (a.y == b.y && b.x < a.x) || b.y < a.y
+}
+
+fn clip<F>(polygon: &[ClipVertex], distance: F) -> Vec<ClipVertex>
+where
+    F: Fn(&ClipVertex) -> f32,
+{
+    let mut output = Vec::with_capacity(polygon.len() + 1);
+    for i in 0..polygon.len() {
+        let a = &polygon[i];
+        let b = &polygon[(i + 1) % polygon.len()];
+        let (da, db) = (distance(a), distance(b));
+
+        if da >= 0.0 {
+            output.push(*a);
+        }
+
+        if (da >= 0.0) != (db >= 0.0) {
+            let t = da / (da - db);
+            let mut v = *a;
+            v.position = a.position + (b.position - a.position) * t;
+            for (i, value) in v.varyings.iter_mut().enumerate() {
+                *value = a.varyings[i] + (b.varyings[i] - a.varyings[i]) * t;
+            }
+
+            output.push(v);
+        }
+    }
+
+    output
+}
+
+fn compare(cmp: Comparison, a: f32, b: f32) -> bool {
+    match cmp {
+        Comparison::Never => false,
+        Comparison::Less => a < b,
+        Comparison::LessOrEqual => a <= b,
+        Comparison::Greater => a > b,
+        Comparison::GreaterOrEqual => a >= b,
+        Comparison::Equal => a == b,
+        Comparison::NotEqual => a != b,
+        Comparison::Always => true,
+    }
+}
+
+fn blend(
+    equation: Equation,
+    sf: BlendFactor,
+    df: BlendFactor,
+    src: math::Color<f32>,
+    dst: math::Color<f32>,
+) -> math::Color<f32> {
+    let factor = |f: BlendFactor| -> [f32; 4] {
+        let value = |v: BlendValue| match v {
+            BlendValue::SourceColor => [src.r, src.g, src.b, src.a],
+            BlendValue::SourceAlpha => [src.a; 4],
+            BlendValue::DestinationColor => [dst.r, dst.g, dst.b, dst.a],
+            BlendValue::DestinationAlpha => [dst.a; 4],
+        };
+
+        match f {
+            BlendFactor::Zero => [0.0; 4],
+            BlendFactor::One => [1.0; 4],
+            BlendFactor::Value(v) => value(v),
+            BlendFactor::OneMinusValue(v) => {
+                let v = value(v);
+                [1.0 - v[0], 1.0 - v[1], 1.0 - v[2], 1.0 - v[3]]
+            }
+        }
+    };
+
+    let (sf, df) = (factor(sf), factor(df));
+    let s = [src.r * sf[0], src.g * sf[1], src.b * sf[2], src.a * sf[3]];
+    let d = [dst.r * df[0], dst.g * df[1], dst.b * df[2], dst.a * df[3]];
+
+    let mut v = [0.0; 4];
+    for i in 0..4 {
+        v[i] = match equation {
+            Equation::Add => s[i] + d[i],
+            Equation::Subtract => s[i] - d[i],
+            Equation::ReverseSubtract => d[i] - s[i],
+        };
+    }
+
+    math::Color::new(v[0], v[1], v[2], v[3])
+}
+
+struct Sampler {
+    dimensions: math::Vector2<u32>,
+    wrap: TextureWrap,
+    filter: TextureFilter,
+}
+
+impl Sampler {
+    fn sample(&self, pixels: &[u8], texcoord: math::Vector2<f32>) -> math::Color<f32> {
+        if pixels.is_empty() || self.dimensions.x == 0 || self.dimensions.y == 0 {
+            return math::Color::new(0.0, 0.0, 0.0, 1.0);
+        }
+
+        let texel = |x: i32, y: i32| -> [f32; 4] {
+            let i = self.index(x, y) * 4;
+            [
+                f32::from(pixels[i]) / 255.0,
+                f32::from(pixels[i + 1]) / 255.0,
+                f32::from(pixels[i + 2]) / 255.0,
+                f32::from(pixels[i + 3]) / 255.0,
+            ]
+        };
+
+        let v = self.filter(texcoord, texel);
+        math::Color::new(v[0], v[1], v[2], v[3])
+    }
+
+    fn sample_depth(&self, depth: &[f32], texcoord: math::Vector2<f32>) -> f32 {
+        if depth.is_empty() || self.dimensions.x == 0 || self.dimensions.y == 0 {
+            return 0.0;
+        }
+
+        let texel = |x: i32, y: i32| -> [f32; 4] { [depth[self.index(x, y)]; 4] };
+        self.filter(texcoord, texel)[0]
+    }
+
+    fn filter<F>(&self, texcoord: math::Vector2<f32>, texel: F) -> [f32; 4]
+    where
+        F: Fn(i32, i32) -> [f32; 4],
+    {
+        let x = texcoord.x * self.dimensions.x as f32;
+        let y = texcoord.y * self.dimensions.y as f32;
+
+        match self.filter {
+            TextureFilter::Nearest => texel(x.floor() as i32, y.floor() as i32),
+            TextureFilter::Linear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i32, y0 as i32);
+
+                let (a, b) = (texel(x0, y0), texel(x0 + 1, y0));
+                let (c, d) = (texel(x0, y0 + 1), texel(x0 + 1, y0 + 1));
+
+                let mut v = [0.0; 4];
+                for i in 0..4 {
+                    let top = a[i] + (b[i] - a[i]) * fx;
+                    let bottom = c[i] + (d[i] - c[i]) * fx;
+                    v[i] = top + (bottom - top) * fy;
+                }
+
+                v
+            }
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        let x = wrap(self.wrap, x, self.dimensions.x as i32);
+        let y = wrap(self.wrap, y, self.dimensions.y as i32);
+        (y * self.dimensions.x as i32 + x) as usize
+    }
+}
+
+// Selects the face of cube map and the texture coordinates on it, as specified by OpenGL.
+fn cube_texcoord(v: math::Vector3<f32>) -> (CubeFace, math::Vector2<f32>) {
+    let (x, y, z) = (v.x.abs(), v.y.abs(), v.z.abs());
+
+    let (face, sc, tc, ma) = if x >= y && x >= z {
+        if v.x >= 0.0 {
+            (CubeFace::PositiveX, -v.z, -v.y, x)
+        } else {
+            (CubeFace::NegativeX, v.z, -v.y, x)
+        }
+    } else if y >= z {
+        if v.y >= 0.0 {
+            (CubeFace::PositiveY, v.x, v.z, y)
+        } else {
+            (CubeFace::NegativeY, v.x, -v.z, y)
+        }
+    } else if v.z >= 0.0 {
+        (CubeFace::PositiveZ, v.x, -v.y, z)
+    } else {
+        (CubeFace::NegativeZ, -v.x, -v.y, z)
+    };
+
+    if ma <= 0.0 {
+        return (face, math::Vector2::new(0.5, 0.5));
+    }
+
+    let s = (sc / ma + 1.0) * 0.5;
+    let t = (tc / ma + 1.0) * 0.5;
+    (face, math::Vector2::new(s, t))
+}
+
+fn wrap(wrap: TextureWrap, v: i32, len: i32) -> i32 {
+    match wrap {
+        TextureWrap::Repeat => ((v % len) + len) % len,
+        TextureWrap::Clamp => v.max(0).min(len - 1),
+        TextureWrap::Mirror => {
+            let v = ((v % (2 * len)) + 2 * len) % (2 * len);
+            if v < len {
+                v
+            } else {
+                2 * len - 1 - v
+            }
+        }
+        TextureWrap::MirrorClamp => {
+            let v = if v < 0 { -1 - v } else { v };
+            v.min(len - 1)
+        }
+    }
+}
+
+fn components(format: TextureFormat) -> Result<u8> {
+    match format {
+        TextureFormat::R8 => Ok(1),
+        TextureFormat::RG8 => Ok(2),
+        TextureFormat::RGB8 => Ok(3),
+        TextureFormat::RGBA8 => Ok(4),
+        _ => bail!("{:?} is not supported by software rasterizer.", format),
+    }
+}
+
+// Copies the tightly packed `bytes` into the `area` of RGBA8 pixels.
+fn copy_pixels(
+    pixels: &mut [u8],
+    dimensions: math::Vector2<u32>,
+    area: math::Aabb2<u32>,
+    bytes: &[u8],
+    components: u8,
+) {
+    let components = components as usize;
+    let dim = area.dim();
+
+    for y in 0..dim.y as usize {
+        for x in 0..dim.x as usize {
+            let src = (y * dim.x as usize + x) * components;
+            if src + components > bytes.len() {
+                return;
+            }
+
+            let dst =
+                ((area.min.y as usize + y) * dimensions.x as usize + area.min.x as usize + x) * 4;
+
+            let mut v = [0, 0, 0, 255];
+            v[0..components].copy_from_slice(&bytes[src..src + components]);
+            pixels[dst..dst + 4].copy_from_slice(&v);
+        }
+    }
+}
+
+// Copies the `area` of RGBA8 pixels into tightly packed bytes, the pixels out of bounds
+// are zeros.
+fn read_pixels(pixels: &[u8], dimensions: math::Vector2<u32>, area: math::Aabb2<u32>) -> Vec<u8> {
+    let dim = area.dim();
+    let mut bytes = vec![0; (dim.x * dim.y * 4) as usize];
+
+    for y in 0..dim.y {
+        for x in 0..dim.x {
+            let (sx, sy) = (area.min.x + x, area.min.y + y);
+            if sx >= dimensions.x || sy >= dimensions.y {
+                continue;
+            }
+
+            let src = ((sy * dimensions.x + sx) * 4) as usize;
+            let dst = ((y * dim.x + x) * 4) as usize;
+            bytes[dst..dst + 4].copy_from_slice(&pixels[src..src + 4]);
+        }
+    }
+
+    bytes
+}
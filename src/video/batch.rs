@@ -109,7 +109,36 @@ impl Batch {
     pub fn update_texture(&mut self, id: TextureHandle, area: math::Aabb2<u32>, bytes: &[u8]) {
         let bufs = &mut self.bufs;
         let ptr = bufs.extend_from_slice(bytes);
-        self.cmds.push(Command::UpdateTexture(id, area, ptr));
+        self.cmds.push(Command::UpdateTexture(id, 0, area, ptr));
+    }
+
+    /// Update a contiguous subregion of a face of an existing cube map.
+    #[inline]
+    pub fn update_texture_face(
+        &mut self,
+        id: TextureHandle,
+        face: CubeFace,
+        area: math::Aabb2<u32>,
+        bytes: &[u8],
+    ) {
+        let bufs = &mut self.bufs;
+        let ptr = bufs.extend_from_slice(bytes);
+        self.cmds.push(Command::UpdateTexture(id, face.layer(), area, ptr));
+    }
+
+    /// Update a contiguous subregion of a layer of an existing texture object, e.g. a slice
+    /// of 3D texture or a layer of texture array.
+    #[inline]
+    pub fn update_texture_layer(
+        &mut self,
+        id: TextureHandle,
+        layer: u32,
+        area: math::Aabb2<u32>,
+        bytes: &[u8],
+    ) {
+        let bufs = &mut self.bufs;
+        let ptr = bufs.extend_from_slice(bytes);
+        self.cmds.push(Command::UpdateTexture(id, layer, area, ptr));
     }
 
     /// Update a subset of dynamic vertex buffer. Use `offset` specifies the offset
@@ -150,9 +179,9 @@ impl Batch {
                     frame.cmds.push(cmd);
                 }
 
-                Command::UpdateTexture(id, area, ptr) => {
+                Command::UpdateTexture(id, layer, area, ptr) => {
                     let ptr = frame.bufs.extend_from_slice(self.bufs.as_slice(ptr));
-                    frame.cmds.push(Command::UpdateTexture(id, area, ptr));
+                    frame.cmds.push(Command::UpdateTexture(id, layer, area, ptr));
                 }
 
                 Command::UpdateVertexBuffer(id, offset, ptr) => {
@@ -12,8 +12,12 @@ pub enum Error {
     SampleRenderBuffer,
     #[fail(display = "Failed to create surface, errors:\n{}\n", _0)]
     SurfaceInvalid(String),
+    #[fail(display = "Failed to create texture, errors:\n{}\n", _0)]
+    TextureInvalid(String),
     #[fail(display = "Attribute({}) is undefined.", _0)]
     AttributeUndefined(String),
+    #[fail(display = "Failed to read back pixels, errors:\n{}\n", _0)]
+    ReadbackInvalid(String),
 }
 
 pub type Result<T> = ::std::result::Result<T, Error>;
@@ -163,8 +163,74 @@
 //!
 //! #### Compressed Texture Format
 //!
-//! _TODO_: Cube texture.
-//! _TODO_: 3D texture.
+//! The compressed formats like PVRTC, ETC2 and S3TC are uploaded as they are, it fails to
+//! create textures with formats that are not supported by the device.
+//!
+//! #### Cube Texture
+//!
+//! A cube map consists of six square faces, which are addressed by `CubeFace`. It's sampled
+//! with a direction vector in shader, which makes it the building block of skyboxes and
+//! reflections.
+//!
+//! ```rust
+//! use crayon::video::prelude::*;
+//! use crayon::math;
+//! let video = VideoSystem::headless(None).shared();
+//!
+//! let mut params = TextureParams::default();
+//! params.kind = TextureKind::Cube;
+//! params.hint = TextureHint::Dynamic;
+//! params.dimensions = (1, 1).into();
+//!
+//! // The mipmaps of faces are stored one after another, in the order of `CubeFace::ALL`.
+//! let bytes = CubeFace::ALL.iter().map(|_| vec![255; 4].into_boxed_slice()).collect();
+//! let texture = video.create_texture(params, TextureData { bytes: bytes }).unwrap();
+//!
+//! // Updates one of the faces.
+//! let area = math::Aabb2::new(math::Point2::new(0, 0), math::Point2::new(1, 1));
+//! video.update_texture_face(texture, CubeFace::PositiveY, area, &[0; 4]).unwrap();
+//!
+//! // Declares the sampler of cube map in shader, and binds it in draw calls with
+//! // `UniformVariable::CubeTexture(texture)`.
+//! let uniforms = UniformVariableLayout::build()
+//!     .with("u_Skybox", UniformVariableType::CubeTexture)
+//!     .finish();
+//! ```
+//!
+//! #### 3D Texture and Texture Array
+//!
+//! A 3D texture consists of `depth` slices, it's sampled with three texture coordinates and
+//! could be used as lookup tables of color grading. A texture array consists of `depth`
+//! layers with the same dimensions, which makes it possible to sample different images, like
+//! splat layers of terrain and pages of sprite atlas, without re-binding textures.
+//!
+//! ```rust
+//! use crayon::video::prelude::*;
+//! use crayon::math;
+//! let video = VideoSystem::headless(None).shared();
+//!
+//! let mut params = TextureParams::default();
+//! params.kind = TextureKind::Array2D;
+//! params.hint = TextureHint::Dynamic;
+//! params.dimensions = (1, 1).into();
+//! params.depth = 4;
+//!
+//! // The mipmaps of layers are stored one after another. Notes that 3D textures have
+//! // no mipmaps, so there is exactly one image for each slice.
+//! let bytes = (0..4).map(|_| vec![255; 4].into_boxed_slice()).collect();
+//! let texture = video.create_texture(params, TextureData { bytes: bytes }).unwrap();
+//!
+//! // Updates one of the layers.
+//! let area = math::Aabb2::new(math::Point2::new(0, 0), math::Point2::new(1, 1));
+//! video.update_texture_layer(texture, 2, area, &[0; 4]).unwrap();
+//!
+//! // Declares the sampler of texture array in shader, and binds it in draw calls with
+//! // `UniformVariable::TextureArray(texture)`. 3D textures are bound with
+//! // `UniformVariable::Texture3D(texture)` in the same way.
+//! let uniforms = UniformVariableLayout::build()
+//!     .with("u_Layers", UniformVariableType::TextureArray)
+//!     .finish();
+//! ```
 //!
 //! ### Mesh Object
 //!
@@ -214,6 +280,55 @@
 //!
 //! _TODO_: Batch
 //! _TODO_: OrderDrawBatch
+//!
+//! # Readback
+//!
+//! The pixels of surfaces and color render textures could be read back asynchronously, which
+//! is useful for screenshots and picking. The request is serviced after all the draw calls
+//! of the frame it's submitted in.
+//!
+//! ```rust
+//! use crayon::application::window::Window;
+//! use crayon::video::prelude::*;
+//! use crayon::math;
+//! let mut video = VideoSystem::headless(None);
+//!
+//! let surface = video.shared().create_surface(SurfaceParams::default()).unwrap();
+//! let area = math::Aabb2::new(math::Point2::new(0, 0), math::Point2::new(2, 2));
+//! let readback = video.shared().read_pixels(surface, area).unwrap();
+//!
+//! video.swap_frames();
+//! video.advance(&Window::headless()).unwrap();
+//!
+//! // The pixels are tightly packed RGBA8.
+//! assert!(readback.is_ready());
+//! assert_eq!(readback.take().unwrap().len(), 2 * 2 * 4);
+//! ```
+//!
+//! # Software Rasterizer
+//!
+//! The `VideoSystem` created with `VideoSystem::software` rasterizes triangles on CPU instead
+//! of submitting them to OpenGL. It's useful to test the rendering results on machines
+//! without GPU, the framebuffers could be inspected with the `SoftwareDevice` after
+//! `advance`.
+//!
+//! ```rust
+//! use crayon::application::window::Window;
+//! use crayon::video::prelude::*;
+//! use crayon::video::software::SoftwareDevice;
+//!
+//! let device = SoftwareDevice::new((4, 4).into());
+//! let mut video = VideoSystem::software(None, device.clone());
+//!
+//! let mut params = SurfaceParams::default();
+//! params.set_clear(crayon::math::Color::white(), None, None);
+//! let surface = video.shared().create_surface(params).unwrap();
+//! video.shared().update_scissor(surface, SurfaceScissor::Disable);
+//!
+//! video.swap_frames();
+//! video.advance(&Window::headless()).unwrap();
+//! assert_eq!(device.pixel(0, 0), [255, 255, 255, 255]);
+//! ```
 
 /// Maximum number of attributes in vertex layout.
 pub const MAX_VERTEX_ATTRIBUTES: usize = 12;
@@ -228,12 +343,16 @@ pub const MAX_UNIFORM_TEXTURE_SLOTS: usize = 8;
 pub mod assets;
 pub mod batch;
 pub mod errors;
+pub mod readback;
+pub mod trace;
 
 mod backends;
+pub use self::backends::software;
 
 pub mod prelude {
     pub use super::assets::prelude::*;
     pub use super::batch::{Batch, DrawCall, OrderDrawBatch};
+    pub use super::readback::{Readback, ReadbackTarget};
     pub use super::{VideoFrameInfo, VideoSystem, VideoSystemShared};
 }
 
@@ -243,8 +362,9 @@ use uuid::Uuid;
 
 use application::window::Window;
 use math;
+use res::format::Upgrade;
 use res::prelude::{Location, ResourceSystemShared};
-use res::registry::Registry;
+use res::registry::{Registry, RegistryEntry, Res};
 use utils::ObjectPool;
 
 use self::assets::prelude::*;
@@ -253,6 +373,7 @@ use self::backends::gl::visitor::GLVisitor;
 use self::backends::Visitor;
 use self::batch::DrawCall;
 use self::errors::*;
+use self::readback::{Readback, ReadbackTarget};
 
 /// The information of video module during last frame.
 #[derive(Debug, Copy, Clone, Default)]
@@ -272,21 +393,26 @@ pub struct VideoSystem {
     frames: Arc<DoubleFrame>,
     shared: Arc<VideoSystemShared>,
     last_dimensions: math::Vector2<u32>,
+    // Whether the default framebuffer follows the dimensions of window.
+    windowed: bool,
+    recording: Option<trace::Trace>,
 }
 
 impl VideoSystem {
     /// Create a new `VideoSystem` with one `Window` context.
     pub fn new(window: &Window, res: Arc<ResourceSystemShared>) -> ::errors::Result<Self> {
         let frames = Arc::new(DoubleFrame::with_capacity(64 * 1024));
-        let shared = VideoSystemShared::new(frames.clone(), res);
+        let shared = VideoSystemShared::new(frames.clone(), res, Some(window.dimensions()));
         let visitor = unsafe { Box::new(GLVisitor::new()?) };
 
         Ok(VideoSystem {
             last_dimensions: window.dimensions(),
+            windowed: true,
             visitor: visitor,
 
             frames: frames,
             shared: Arc::new(shared),
+            recording: None,
         })
     }
 
@@ -301,14 +427,41 @@ impl VideoSystem {
         });
 
         let frames = Arc::new(DoubleFrame::with_capacity(0));
-        let shared = VideoSystemShared::new(frames.clone(), res);
+        let shared = VideoSystemShared::new(frames.clone(), res, None);
         let visitor = backends::headless::HeadlessVisitor::new();
 
         VideoSystem {
             last_dimensions: (0, 0).into(),
+            windowed: false,
             visitor: Box::new(visitor),
             frames: frames,
             shared: Arc::new(shared),
+            recording: None,
+        }
+    }
+
+    /// Creates a new `VideoSystem` with the software rasterizer, the rendering results
+    /// could be inspected with `device` after `advance`.
+    pub fn software<T>(res: T, device: Arc<software::SoftwareDevice>) -> Self
+    where
+        T: Into<Option<Arc<ResourceSystemShared>>>,
+    {
+        let res = res.into().unwrap_or_else(|| {
+            let sched = ::sched::ScheduleSystem::new(1, None, None);
+            ::res::ResourceSystem::new(sched.shared()).unwrap().shared()
+        });
+
+        let frames = Arc::new(DoubleFrame::with_capacity(64 * 1024));
+        let shared = VideoSystemShared::new(frames.clone(), res, Some(device.dimensions()));
+        let visitor = software::SoftwareVisitor::new(device);
+
+        VideoSystem {
+            last_dimensions: (0, 0).into(),
+            windowed: false,
+            visitor: Box::new(visitor),
+            frames: frames,
+            shared: Arc::new(shared),
+            recording: None,
         }
     }
 
@@ -337,12 +490,59 @@ impl VideoSystem {
         if dimensions != self.last_dimensions {
             self.last_dimensions = dimensions;
             window.resize(dimensions);
+
+            if self.windowed {
+                *self.shared.dimensions.write().unwrap() = Some(dimensions);
+            }
         }
 
-        let (dc, tris) = self
-            .frames
-            .back()
-            .dispatch(self.visitor.as_mut(), dimensions)?;
+        let (dc, tris) = if let Some(ref mut trace) = self.recording {
+            let mut frame = trace::TraceFrame::new(dimensions);
+            let rv = {
+                let mut recorder = trace::Recorder::new(self.visitor.as_mut(), &mut frame);
+                self.frames.back().dispatch(&mut recorder, dimensions)?
+            };
+
+            trace.frames.push(frame);
+            rv
+        } else {
+            self.frames
+                .back()
+                .dispatch(self.visitor.as_mut(), dimensions)?
+        };
+
+        let info = self.frame_info(dc, tris, time::Instant::now() - ts);
+        Ok(info)
+    }
+
+    /// Starts recording the commands dispatched to backend into a `Trace`. Notes that the
+    /// resources created before recording are not captured.
+    pub fn start_recording(&mut self) {
+        if self.recording.is_none() {
+            self.recording = Some(trace::Trace::new());
+        }
+    }
+
+    /// Stops recording and returns the recorded `Trace`, if any.
+    pub fn stop_recording(&mut self) -> Option<trace::Trace> {
+        self.recording.take()
+    }
+
+    /// Replays a recorded frame with the backend of this `VideoSystem`.
+    ///
+    /// The replayed commands refer to resources by the handles at recording time, so it's
+    /// recommended to use a dedicated `VideoSystem` which has nothing created through its
+    /// `VideoSystemShared`.
+    pub fn replay(&mut self, frame: &trace::TraceFrame) -> ::errors::Result<VideoFrameInfo> {
+        use std::time;
+
+        let ts = time::Instant::now();
+        let (dc, tris) = trace::replay(frame, self.visitor.as_mut())?;
+        let info = self.frame_info(dc, tris, time::Instant::now() - ts);
+        Ok(info)
+    }
+
+    fn frame_info(&self, dc: u32, tris: u32, duration: Duration) -> VideoFrameInfo {
         let mut info = VideoFrameInfo::default();
 
         {
@@ -355,8 +555,8 @@ impl VideoSystem {
             info.triangles = tris;
         }
 
-        info.duration = time::Instant::now() - ts;
-        Ok(info)
+        info.duration = duration;
+        info
     }
 }
 
@@ -372,11 +572,17 @@ pub struct VideoSystemShared {
     meshes: MeshRegistry,
     textures: TextureRegistry,
     render_textures: RwLock<ObjectPool<RenderTextureHandle, RenderTextureParams>>,
+    // The dimensions of default framebuffer, headless backend has none.
+    dimensions: RwLock<Option<math::Vector2<u32>>>,
 }
 
 impl VideoSystemShared {
     /// Create a new `VideoSystem` with one `Window` context.
-    fn new(frames: Arc<DoubleFrame>, res: Arc<ResourceSystemShared>) -> Self {
+    fn new(
+        frames: Arc<DoubleFrame>,
+        res: Arc<ResourceSystemShared>,
+        dimensions: Option<math::Vector2<u32>>,
+    ) -> Self {
         use self::assets::mesh_loader::MeshLoader;
         use self::assets::texture_loader::TextureLoader;
 
@@ -391,6 +597,7 @@ impl VideoSystemShared {
             meshes: meshes,
             textures: textures,
             render_textures: RwLock::new(ObjectPool::new()),
+            dimensions: RwLock::new(dimensions),
         }
     }
 
@@ -572,6 +779,25 @@ impl VideoSystemShared {
     pub fn delete_mesh(&self, handle: MeshHandle) {
         self.meshes.delete(handle);
     }
+
+    /// Enumerates all the mesh objects, which is useful for debugging and leak reports.
+    #[inline]
+    pub fn mesh_entries(&self) -> Vec<RegistryEntry<MeshHandle>> {
+        self.meshes.entries()
+    }
+
+    /// Registers an upgrade which converts the mesh payload of `version` into `version + 1`.
+    #[inline]
+    pub fn register_mesh_upgrade(&self, version: u32, upgrade: Upgrade) -> ::errors::Result<()> {
+        self.meshes.register_upgrade(version, upgrade)
+    }
+
+    /// Takes over the ownership of mesh object, which will be deleted when the returned
+    /// `Res` and all its clones are dropped.
+    #[inline]
+    pub fn own_mesh(&self, handle: MeshHandle) -> Res<MeshHandle> {
+        self.meshes.own(handle)
+    }
 }
 
 impl VideoSystemShared {
@@ -610,20 +836,70 @@ impl VideoSystemShared {
         handle: TextureHandle,
         area: math::Aabb2<u32>,
         data: &[u8],
+    ) -> ::errors::Result<()> {
+        self.update_texture_layer(handle, 0, area, data)
+    }
+
+    /// Update a contiguous subregion of a face of an existing cube map.
+    pub fn update_texture_face(
+        &self,
+        handle: TextureHandle,
+        face: CubeFace,
+        area: math::Aabb2<u32>,
+        data: &[u8],
+    ) -> ::errors::Result<()> {
+        self.update_texture_layer(handle, face.layer(), area, data)
+    }
+
+    /// Update a contiguous subregion of a layer of an existing texture object, e.g. a slice
+    /// of 3D texture or a layer of texture array.
+    pub fn update_texture_layer(
+        &self,
+        handle: TextureHandle,
+        layer: u32,
+        area: math::Aabb2<u32>,
+        data: &[u8],
     ) -> ::errors::Result<()> {
         self.textures
-            .get(handle, |_| {
+            .get(handle, |params| -> ::errors::Result<()> {
+                if layer >= params.layers() {
+                    bail!(
+                        "Layer {} is out of the {} layers of {:?}.",
+                        layer,
+                        params.layers(),
+                        handle
+                    );
+                }
+
                 let mut frame = self.frames.front();
                 let ptr = frame.bufs.extend_from_slice(data);
-                let cmd = Command::UpdateTexture(handle, area, ptr);
+                let cmd = Command::UpdateTexture(handle, layer, area, ptr);
                 frame.cmds.push(cmd);
-            }).ok_or_else(|| format_err!("{:?}", handle))
+                Ok(())
+            }).unwrap_or_else(|| Err(format_err!("{:?}", handle)))
     }
 
     /// Delete the texture object.
     pub fn delete_texture(&self, handle: TextureHandle) {
         self.textures.delete(handle);
     }
+
+    /// Enumerates all the texture objects, which is useful for debugging and leak reports.
+    pub fn texture_entries(&self) -> Vec<RegistryEntry<TextureHandle>> {
+        self.textures.entries()
+    }
+
+    /// Registers an upgrade which converts the texture payload of `version` into
+    /// `version + 1`.
+    pub fn register_texture_upgrade(&self, version: u32, upgrade: Upgrade) -> ::errors::Result<()> {
+        self.textures.register_upgrade(version, upgrade)
+    }
+
+    /// Takes over the ownership of texture object, which will be deleted when the returned
+    /// `Res` and all its clones are dropped.
+    pub fn own_texture(&self, handle: TextureHandle) -> Res<TextureHandle> {
+        self.textures.own(handle)
+    }
 }
 
 impl VideoSystemShared {
@@ -655,3 +931,68 @@ impl VideoSystemShared {
         }
     }
 }
+
+impl VideoSystemShared {
+    /// Reads the pixels in `area` of surface or color render texture asynchronously. The
+    /// request is serviced by the backend after all the draw calls of current frame, and
+    /// the pixels are tightly packed RGBA8 with rows stored from the lower left corner.
+    ///
+    /// Returns `Error::OutOfBounds` if `area` exceeds the dimensions of the render texture,
+    /// or the default framebuffer as of the last `VideoSystem::advance`.
+    pub fn read_pixels<T>(&self, target: T, area: math::Aabb2<u32>) -> Result<Arc<Readback>>
+    where
+        T: Into<ReadbackTarget>,
+    {
+        let target = target.into();
+        let texture = match target {
+            ReadbackTarget::Surface(handle) => {
+                let params = self
+                    .surface(handle)
+                    .ok_or_else(|| Error::HandleInvalid(format!("{:?}", handle)))?;
+
+                match (params.colors[0], params.depth_stencil) {
+                    (Some(texture), _) => Some(texture),
+                    (None, None) => None,
+                    (None, Some(_)) => {
+                        return Err(Error::ReadbackInvalid(
+                            "Surface has none color attachment.".into(),
+                        ));
+                    }
+                }
+            }
+            ReadbackTarget::RenderTexture(handle) => Some(handle),
+        };
+
+        let dimensions = if let Some(texture) = texture {
+            let params = self
+                .render_texture(texture)
+                .ok_or_else(|| Error::HandleInvalid(format!("{:?}", texture)))?;
+
+            if !params.format.is_color() {
+                return Err(Error::ReadbackInvalid(format!(
+                    "{:?} is not a color render texture.",
+                    texture
+                )));
+            }
+
+            Some(params.dimensions)
+        } else {
+            *self.dimensions.read().unwrap()
+        };
+
+        if let Some(dimensions) = dimensions {
+            if area.max.x > dimensions.x || area.max.y > dimensions.y {
+                return Err(Error::OutOfBounds);
+            }
+        }
+
+        let readback = Arc::new(Readback::new());
+
+        {
+            let cmd = Command::ReadPixels(target, area, readback.clone());
+            self.frames.front().cmds.push(cmd);
+        }
+
+        Ok(readback)
+    }
+}
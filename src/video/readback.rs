@@ -0,0 +1,114 @@
+//! Asynchronous readback of pixels from surfaces and render textures.
+
+use std::sync::{Condvar, Mutex};
+
+use errors::*;
+use sched::latch::{LatchProbe, LatchWaitProbe};
+
+use super::assets::prelude::*;
+
+/// The source of pixels when reading back.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReadbackTarget {
+    /// Reads the first color attachment of surface, or the default framebuffer if none
+    /// attachment is assigned.
+    Surface(SurfaceHandle),
+    /// Reads a color render texture.
+    RenderTexture(RenderTextureHandle),
+}
+
+impl From<SurfaceHandle> for ReadbackTarget {
+    fn from(handle: SurfaceHandle) -> Self {
+        ReadbackTarget::Surface(handle)
+    }
+}
+
+impl From<RenderTextureHandle> for ReadbackTarget {
+    fn from(handle: RenderTextureHandle) -> Self {
+        ReadbackTarget::RenderTexture(handle)
+    }
+}
+
+#[derive(Debug)]
+enum ReadbackState {
+    NotReady,
+    Ok(Result<Vec<u8>>),
+    Taken,
+}
+
+/// The pending pixels of `VideoSystemShared::read_pixels`, which will be fulfilled by the
+/// backend at the end of frame.
+///
+/// The pixels are tightly packed RGBA8, and like `glReadPixels`, the rows are stored from
+/// the lower left corner of the area.
+#[derive(Debug)]
+pub struct Readback {
+    m: Mutex<ReadbackState>,
+    v: Condvar,
+}
+
+impl Readback {
+    #[inline]
+    pub fn new() -> Self {
+        Readback {
+            m: Mutex::new(ReadbackState::NotReady),
+            v: Condvar::new(),
+        }
+    }
+
+    /// Checks if the pixels are available.
+    #[inline]
+    pub fn is_ready(&self) -> bool {
+        self.is_set()
+    }
+
+    #[inline]
+    pub(crate) fn set(&self, v: Result<Vec<u8>>) {
+        {
+            let mut guard = self.m.lock().unwrap();
+            *guard = ReadbackState::Ok(v);
+        }
+
+        self.v.notify_all();
+    }
+
+    /// Takes the pixels out, blocks current thread until the readback is fulfilled.
+    ///
+    /// Notes that it will never return if it's called before `VideoSystem::advance` on the
+    /// same thread, use `is_ready` to poll the readback instead.
+    pub fn take(&self) -> Result<Vec<u8>> {
+        self.wait();
+
+        let mut guard = self.m.lock().unwrap();
+        match ::std::mem::replace(&mut *guard, ReadbackState::Taken) {
+            ReadbackState::Ok(v) => v,
+            _ => bail!("The pixels of readback have been taken."),
+        }
+    }
+}
+
+impl Default for Readback {
+    fn default() -> Self {
+        Readback::new()
+    }
+}
+
+impl LatchProbe for Readback {
+    fn is_set(&self) -> bool {
+        let guard = self.m.lock().unwrap();
+        if let ReadbackState::NotReady = *guard {
+            false
+        } else {
+            true
+        }
+    }
+}
+
+impl LatchWaitProbe for Readback {
+    fn wait(&self) {
+        let mut guard = self.m.lock().unwrap();
+        while let ReadbackState::NotReady = *guard {
+            guard = self.v.wait(guard).unwrap();
+        }
+    }
+}
@@ -0,0 +1,389 @@
+//! Records the commands dispatched to video backend into a serializable `Trace`, which could
+//! be replayed later by any `VideoSystem`.
+//!
+//! A trace contains everything the backend received, including the payloads of resources and
+//! the uniform variables of draw calls, so it's suitable for offline reproductions of rendering
+//! bugs and the regression tests with headless or software backends. Notes that the resources
+//! created before recording are missing in the trace.
+//!
+//! ```rust
+//! use crayon::application::window::Window;
+//! use crayon::video::prelude::*;
+//! use crayon::video::trace::Trace;
+//!
+//! let mut video = VideoSystem::headless(None);
+//! video.start_recording();
+//!
+//! let surface = video.shared().create_surface(SurfaceParams::default()).unwrap();
+//! video.shared().update_scissor(surface, SurfaceScissor::Disable);
+//! video.swap_frames();
+//! video.advance(&Window::headless()).unwrap();
+//!
+//! let mut bytes = Vec::new();
+//! let trace = video.stop_recording().unwrap();
+//! trace.save_to(&mut bytes).unwrap();
+//!
+//! // Feeds the trace into another video system.
+//! let trace = Trace::load_from(&mut &bytes[..]).unwrap();
+//! let mut replayer = VideoSystem::headless(None);
+//! for frame in &trace.frames {
+//!     replayer.replay(frame).unwrap();
+//! }
+//! ```
+
+use std::io::{Read, Write};
+
+use bincode;
+use errors::*;
+use math;
+use res::format::Format;
+use utils::hash_value::HashValue;
+
+use super::assets::prelude::*;
+use super::backends::{UniformVar, Visitor};
+use super::readback::ReadbackTarget;
+
+/// The binary format of traces.
+pub fn format() -> Format {
+    Format::new("Trace", *b"VTRC", 1)
+}
+
+/// The serializable copy of a command, with the payloads in frame buffer resolved.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum TraceCommand {
+    Bind(SurfaceHandle),
+    Draw(
+        ShaderHandle,
+        MeshHandle,
+        MeshIndex,
+        Vec<(HashValue<str>, UniformVariable)>,
+    ),
+    UpdateScissor(SurfaceScissor),
+    UpdateViewport(SurfaceViewport),
+
+    CreateSurface(SurfaceHandle, SurfaceParams),
+    DeleteSurface(SurfaceHandle),
+
+    CreateShader(ShaderHandle, ShaderParams, String, String),
+    DeleteShader(ShaderHandle),
+
+    CreateTexture(TextureHandle, TextureParams, Option<TextureData>),
+    UpdateTexture(TextureHandle, u32, math::Aabb2<u32>, Vec<u8>),
+    DeleteTexture(TextureHandle),
+
+    CreateRenderTexture(RenderTextureHandle, RenderTextureParams),
+    DeleteRenderTexture(RenderTextureHandle),
+
+    CreateMesh(MeshHandle, MeshParams, Option<MeshData>),
+    UpdateVertexBuffer(MeshHandle, usize, Vec<u8>),
+    UpdateIndexBuffer(MeshHandle, usize, Vec<u8>),
+    DeleteMesh(MeshHandle),
+}
+
+/// The commands which are dispatched in one frame.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TraceFrame {
+    /// The dimensions of window when dispatching.
+    pub dimensions: math::Vector2<u32>,
+    pub cmds: Vec<TraceCommand>,
+}
+
+impl TraceFrame {
+    pub fn new(dimensions: math::Vector2<u32>) -> Self {
+        TraceFrame {
+            dimensions: dimensions,
+            cmds: Vec::new(),
+        }
+    }
+}
+
+/// A sequence of recorded frames.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Trace {
+    pub frames: Vec<TraceFrame>,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Trace { frames: Vec::new() }
+    }
+
+    /// Reads a trace from file.
+    pub fn load_from(file: &mut dyn Read) -> Result<Trace> {
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let payload = format().decode(&bytes)?;
+        Ok(bincode::deserialize(&payload)?)
+    }
+
+    /// Writes the trace into file.
+    pub fn save_to(&self, mut file: &mut dyn Write) -> Result<()> {
+        file.write_all(&format().magic())?;
+        bincode::serialize_into(&mut file, self)?;
+        Ok(())
+    }
+}
+
+/// A `Visitor` which records every command into `TraceFrame` before forwarding it.
+pub(crate) struct Recorder<'a> {
+    visitor: &'a mut dyn Visitor,
+    frame: &'a mut TraceFrame,
+}
+
+impl<'a> Recorder<'a> {
+    pub fn new(visitor: &'a mut dyn Visitor, frame: &'a mut TraceFrame) -> Self {
+        Recorder {
+            visitor: visitor,
+            frame: frame,
+        }
+    }
+}
+
+impl<'a> Visitor for Recorder<'a> {
+    unsafe fn create_surface(
+        &mut self,
+        handle: SurfaceHandle,
+        params: SurfaceParams,
+    ) -> Result<()> {
+        let cmd = TraceCommand::CreateSurface(handle, params);
+        self.frame.cmds.push(cmd);
+        self.visitor.create_surface(handle, params)
+    }
+
+    unsafe fn delete_surface(&mut self, handle: SurfaceHandle) -> Result<()> {
+        self.frame.cmds.push(TraceCommand::DeleteSurface(handle));
+        self.visitor.delete_surface(handle)
+    }
+
+    unsafe fn create_shader(
+        &mut self,
+        handle: ShaderHandle,
+        params: ShaderParams,
+        vs: &str,
+        fs: &str,
+    ) -> Result<()> {
+        let cmd = TraceCommand::CreateShader(handle, params.clone(), vs.into(), fs.into());
+        self.frame.cmds.push(cmd);
+        self.visitor.create_shader(handle, params, vs, fs)
+    }
+
+    unsafe fn delete_shader(&mut self, handle: ShaderHandle) -> Result<()> {
+        self.frame.cmds.push(TraceCommand::DeleteShader(handle));
+        self.visitor.delete_shader(handle)
+    }
+
+    unsafe fn create_texture(
+        &mut self,
+        handle: TextureHandle,
+        params: TextureParams,
+        data: Option<TextureData>,
+    ) -> Result<()> {
+        let cmd = TraceCommand::CreateTexture(handle, params, data.clone());
+        self.frame.cmds.push(cmd);
+        self.visitor.create_texture(handle, params, data)
+    }
+
+    unsafe fn update_texture(
+        &mut self,
+        handle: TextureHandle,
+        layer: u32,
+        area: math::Aabb2<u32>,
+        bytes: &[u8],
+    ) -> Result<()> {
+        let cmd = TraceCommand::UpdateTexture(handle, layer, area, bytes.to_vec());
+        self.frame.cmds.push(cmd);
+        self.visitor.update_texture(handle, layer, area, bytes)
+    }
+
+    unsafe fn delete_texture(&mut self, handle: TextureHandle) -> Result<()> {
+        self.frame.cmds.push(TraceCommand::DeleteTexture(handle));
+        self.visitor.delete_texture(handle)
+    }
+
+    unsafe fn create_render_texture(
+        &mut self,
+        handle: RenderTextureHandle,
+        params: RenderTextureParams,
+    ) -> Result<()> {
+        let cmd = TraceCommand::CreateRenderTexture(handle, params);
+        self.frame.cmds.push(cmd);
+        self.visitor.create_render_texture(handle, params)
+    }
+
+    unsafe fn delete_render_texture(&mut self, handle: RenderTextureHandle) -> Result<()> {
+        let cmd = TraceCommand::DeleteRenderTexture(handle);
+        self.frame.cmds.push(cmd);
+        self.visitor.delete_render_texture(handle)
+    }
+
+    unsafe fn create_mesh(
+        &mut self,
+        handle: MeshHandle,
+        params: MeshParams,
+        data: Option<MeshData>,
+    ) -> Result<()> {
+        let cmd = TraceCommand::CreateMesh(handle, params.clone(), data.clone());
+        self.frame.cmds.push(cmd);
+        self.visitor.create_mesh(handle, params, data)
+    }
+
+    unsafe fn update_vertex_buffer(
+        &mut self,
+        handle: MeshHandle,
+        offset: usize,
+        bytes: &[u8],
+    ) -> Result<()> {
+        let cmd = TraceCommand::UpdateVertexBuffer(handle, offset, bytes.to_vec());
+        self.frame.cmds.push(cmd);
+        self.visitor.update_vertex_buffer(handle, offset, bytes)
+    }
+
+    unsafe fn update_index_buffer(
+        &mut self,
+        handle: MeshHandle,
+        offset: usize,
+        bytes: &[u8],
+    ) -> Result<()> {
+        let cmd = TraceCommand::UpdateIndexBuffer(handle, offset, bytes.to_vec());
+        self.frame.cmds.push(cmd);
+        self.visitor.update_index_buffer(handle, offset, bytes)
+    }
+
+    unsafe fn delete_mesh(&mut self, handle: MeshHandle) -> Result<()> {
+        self.frame.cmds.push(TraceCommand::DeleteMesh(handle));
+        self.visitor.delete_mesh(handle)
+    }
+
+    unsafe fn bind(
+        &mut self,
+        surface: SurfaceHandle,
+        dimensions: math::Vector2<u32>,
+    ) -> Result<()> {
+        self.frame.cmds.push(TraceCommand::Bind(surface));
+        self.visitor.bind(surface, dimensions)
+    }
+
+    unsafe fn draw(
+        &mut self,
+        shader: ShaderHandle,
+        mesh: MeshHandle,
+        mesh_index: MeshIndex,
+        vars: &[UniformVar],
+    ) -> Result<u32> {
+        let cmd = TraceCommand::Draw(shader, mesh, mesh_index, vars.to_vec());
+        self.frame.cmds.push(cmd);
+        self.visitor.draw(shader, mesh, mesh_index, vars)
+    }
+
+    unsafe fn update_surface_scissor(&mut self, scissor: SurfaceScissor) -> Result<()> {
+        self.frame.cmds.push(TraceCommand::UpdateScissor(scissor));
+        self.visitor.update_surface_scissor(scissor)
+    }
+
+    unsafe fn update_surface_viewport(&mut self, vp: SurfaceViewport) -> Result<()> {
+        self.frame.cmds.push(TraceCommand::UpdateViewport(vp));
+        self.visitor.update_surface_viewport(vp)
+    }
+
+    unsafe fn read_pixels(
+        &mut self,
+        target: ReadbackTarget,
+        area: math::Aabb2<u32>,
+    ) -> Result<Vec<u8>> {
+        self.visitor.read_pixels(target, area)
+    }
+
+    unsafe fn flush(&mut self) -> Result<()> {
+        self.visitor.flush()
+    }
+
+    unsafe fn advance(&mut self) -> Result<()> {
+        self.visitor.advance()
+    }
+}
+
+/// Feeds the commands of a recorded frame into the backend, likes `Frame::dispatch`.
+pub(crate) fn replay(frame: &TraceFrame, visitor: &mut dyn Visitor) -> Result<(u32, u32)> {
+    unsafe {
+        visitor.advance()?;
+
+        let dimensions = frame.dimensions;
+        let (mut dc, mut tris) = (0, 0);
+        for v in &frame.cmds {
+            match *v {
+                TraceCommand::Bind(surface) => {
+                    visitor.bind(surface, dimensions)?;
+                }
+
+                TraceCommand::Draw(shader, mesh, mesh_index, ref vars) => {
+                    dc += 1;
+                    tris += visitor.draw(shader, mesh, mesh_index, vars)?;
+                }
+
+                TraceCommand::UpdateScissor(scissor) => {
+                    visitor.update_surface_scissor(scissor)?;
+                }
+
+                TraceCommand::UpdateViewport(view) => {
+                    visitor.update_surface_viewport(view)?;
+                }
+
+                TraceCommand::CreateSurface(handle, params) => {
+                    visitor.create_surface(handle, params)?;
+                }
+
+                TraceCommand::DeleteSurface(handle) => {
+                    visitor.delete_surface(handle)?;
+                }
+
+                TraceCommand::CreateShader(handle, ref params, ref vs, ref fs) => {
+                    visitor.create_shader(handle, params.clone(), vs, fs)?;
+                }
+
+                TraceCommand::DeleteShader(handle) => {
+                    visitor.delete_shader(handle)?;
+                }
+
+                TraceCommand::CreateTexture(handle, params, ref data) => {
+                    visitor.create_texture(handle, params, data.clone())?;
+                }
+
+                TraceCommand::UpdateTexture(handle, layer, area, ref data) => {
+                    visitor.update_texture(handle, layer, area, data)?;
+                }
+
+                TraceCommand::DeleteTexture(handle) => {
+                    visitor.delete_texture(handle)?;
+                }
+
+                TraceCommand::CreateRenderTexture(handle, params) => {
+                    visitor.create_render_texture(handle, params)?;
+                }
+
+                TraceCommand::DeleteRenderTexture(handle) => {
+                    visitor.delete_render_texture(handle)?;
+                }
+
+                TraceCommand::CreateMesh(handle, ref params, ref data) => {
+                    visitor.create_mesh(handle, params.clone(), data.clone())?;
+                }
+
+                TraceCommand::UpdateVertexBuffer(handle, offset, ref data) => {
+                    visitor.update_vertex_buffer(handle, offset, data)?;
+                }
+
+                TraceCommand::UpdateIndexBuffer(handle, offset, ref data) => {
+                    visitor.update_index_buffer(handle, offset, data)?;
+                }
+
+                TraceCommand::DeleteMesh(handle) => {
+                    visitor.delete_mesh(handle)?;
+                }
+            }
+        }
+
+        visitor.flush()?;
+        Ok((dc, tris))
+    }
+}
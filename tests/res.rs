@@ -1,12 +1,26 @@
 extern crate crayon;
 extern crate rand;
 
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
+use crayon::errors::*;
+use crayon::res::format::Format;
 use crayon::res::prelude::*;
+use crayon::res::registry::{Register, Registry};
+use crayon::res::vfs::manifest::{self, Manifest};
+use crayon::res::vfs::package;
+use crayon::res::vfs::VFS;
+use crayon::res::Loader;
+use crayon::sched::latch::LatchProbe;
+use crayon::utils::Handle;
+use crayon::uuid::Uuid;
 
 fn testbed() -> Arc<ResourceSystemShared> {
     let dir = ::std::env::current_dir()
@@ -56,3 +70,1546 @@ fn from_unknown_vfs() {
 
     video.create_texture_from("unknown:crate.bmp").unwrap();
 }
+
+fn tempdir(name: &str) -> PathBuf {
+    let dir = ::std::env::temp_dir().join(format!("crayon-{}-{}", name, ::std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_manifest(dir: &Path, items: &[(&str, Uuid)]) {
+    let mut manifest = Manifest::new();
+    for &(filename, uuid) in items {
+        manifest.add(filename, uuid, &[]).unwrap();
+    }
+
+    let mut file = fs::File::create(dir.join(manifest::NAME)).unwrap();
+    manifest.save_to(&mut file).unwrap();
+}
+
+#[test]
+fn manifest() {
+    let u1 = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let u2 = Uuid::parse_str("427EB273E77446B8B7010ACE1B7652EE").unwrap();
+    let u3 = Uuid::parse_str("6A8B4D2E9C1F4B7A8E3D5C2B1A0F9E8D").unwrap();
+
+    let mut manifest = Manifest::new();
+    manifest.add("a.bin", u1, &[]).unwrap();
+    assert!(manifest.add("b.bin", u2, &[u3]).is_err());
+    manifest.add("b.bin", u2, &[u1]).unwrap();
+    assert!(manifest.add("a.bin", u3, &[]).is_err());
+    assert!(manifest.add("c.bin", u1, &[]).is_err());
+
+    let mut bytes = Vec::new();
+    manifest.save_to(&mut bytes).unwrap();
+    assert_eq!(&bytes[0..8], &manifest::format().magic()[..]);
+
+    let manifest = Manifest::load_from(&mut &bytes[..]).unwrap();
+    assert_eq!(manifest.redirect("a.bin"), Some(u1));
+    assert_eq!(manifest.redirect("b.bin"), Some(u2));
+    assert_eq!(manifest.redirect("c.bin"), None);
+    assert!(manifest.contains(u2));
+    assert!(!manifest.contains(u3));
+    assert_eq!(
+        manifest.dependencies(u2).unwrap().collect::<Vec<_>>(),
+        vec![u1]
+    );
+}
+
+struct Record(Arc<Mutex<Vec<Vec<u8>>>>);
+
+impl Loader for Record {
+    fn load(&self, bytes: &[u8]) -> Result<()> {
+        self.0.lock().unwrap().push(bytes.to_vec());
+        Ok(())
+    }
+}
+
+#[test]
+fn modified_since() {
+    let dir = tempdir("modified_since");
+    fs::write(dir.join("file"), b"").unwrap();
+
+    let ts = SystemTime::now();
+    let vfs = Directory::new(dir.clone()).unwrap();
+    assert!(!vfs.modified_since("file".as_ref(), ts));
+    assert!(!vfs.modified_since("missing".as_ref(), ts));
+
+    thread::sleep(Duration::from_millis(50));
+    fs::write(dir.join("file"), b"modified").unwrap();
+    assert!(vfs.modified_since("file".as_ref(), ts));
+}
+
+#[test]
+fn hot_reload() {
+    let dir = tempdir("hot_reload");
+    let uuid = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    write_manifest(&dir, &[("crate.bin", uuid)]);
+
+    let path = dir.join(format!("{:X}", uuid.to_simple()));
+    fs::write(&path, b"v1").unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("res", Directory::new(dir.clone()).unwrap())
+        .unwrap();
+    res.set_hot_reload(Duration::from_secs(0));
+
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let shared = res.shared();
+    shared
+        .load_from_uuid(Record(records.clone()), uuid)
+        .unwrap();
+    shared.wait_until(uuid).unwrap();
+
+    res.advance();
+    shared.wait_until(uuid).unwrap();
+    assert_eq!(*records.lock().unwrap(), vec![b"v1".to_vec()]);
+
+    thread::sleep(Duration::from_millis(50));
+    fs::write(&path, b"v2").unwrap();
+
+    res.advance();
+    shared.wait_until(uuid).unwrap();
+    assert_eq!(
+        *records.lock().unwrap(),
+        vec![b"v1".to_vec(), b"v2".to_vec()]
+    );
+}
+
+// Rejects the texts starting with `!` when attaching, and records the detached values.
+#[derive(Clone)]
+struct Checked(Arc<Mutex<Vec<String>>>);
+
+impl Register for Checked {
+    type Handle = Handle;
+    type Intermediate = String;
+    type Value = String;
+
+    fn load(&self, _: Self::Handle, bytes: &[u8]) -> Result<Self::Intermediate> {
+        Ok(String::from_utf8(bytes.to_vec())?)
+    }
+
+    fn attach(&self, _: Self::Handle, item: Self::Intermediate) -> Result<Self::Value> {
+        if item.starts_with('!') {
+            let err = io::Error::new(io::ErrorKind::InvalidData, item);
+            return Err(err.into());
+        }
+
+        Ok(item)
+    }
+
+    fn detach(&self, _: Self::Handle, value: Self::Value) {
+        self.0.lock().unwrap().push(value);
+    }
+}
+
+#[test]
+fn hot_reload_registry() {
+    let dir = tempdir("hot_reload_registry");
+    let uuid = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    write_manifest(&dir, &[("crate.txt", uuid)]);
+
+    let path = dir.join(format!("{:X}", uuid.to_simple()));
+    fs::write(&path, b"v1").unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("res", Directory::new(dir.clone()).unwrap())
+        .unwrap();
+    res.set_hot_reload(Duration::from_secs(0));
+
+    let detached = Arc::new(Mutex::new(Vec::new()));
+    let shared = res.shared();
+    let registry = Registry::new(shared.clone(), Checked(detached.clone()));
+    let handle = registry.create_from("res:crate.txt").unwrap();
+    registry.wait_until(handle).unwrap();
+
+    res.advance();
+    shared.wait_until(uuid).unwrap();
+    assert_eq!(registry.get(handle, |v| v.clone()).unwrap(), "v1");
+
+    // The value is swapped in place, and the previous one is detached.
+    thread::sleep(Duration::from_millis(50));
+    fs::write(&path, b"v2").unwrap();
+
+    res.advance();
+    let _ = shared.wait_until(uuid);
+    assert_eq!(registry.get(handle, |v| v.clone()).unwrap(), "v2");
+    assert_eq!(registry.create_from("res:crate.txt").unwrap(), handle);
+    assert_eq!(*detached.lock().unwrap(), vec!["v1".to_owned()]);
+
+    // Keeps the previous value if we failed to reload it.
+    thread::sleep(Duration::from_millis(50));
+    fs::write(&path, b"!v3").unwrap();
+
+    res.advance();
+    let _ = shared.wait_until(uuid);
+    assert_eq!(registry.get(handle, |v| v.clone()).unwrap(), "v2");
+    assert!(registry.wait_until(handle).is_ok());
+    assert_eq!(*detached.lock().unwrap(), vec!["v1".to_owned()]);
+
+    // The registries of the same resource are watched separately.
+    thread::sleep(Duration::from_millis(50));
+    fs::write(&path, b"v4").unwrap();
+
+    let others = Registry::new(shared.clone(), Checked(detached.clone()));
+    let other = others.create_from("res:crate.txt").unwrap();
+    others.wait_until(other).unwrap();
+    registry.delete(handle);
+
+    thread::sleep(Duration::from_millis(50));
+    fs::write(&path, b"v5").unwrap();
+
+    res.advance();
+    let _ = shared.wait_until(uuid);
+    assert_eq!(others.get(other, |v| v.clone()).unwrap(), "v5");
+}
+
+#[test]
+fn package() {
+    let dir = tempdir("package");
+    let u1 = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let u2 = Uuid::parse_str("427EB273E77446B8B7010ACE1B7652EE").unwrap();
+    write_manifest(&dir, &[("a.bin", u1), ("b.bin", u2)]);
+    fs::write(dir.join(format!("{:X}", u1.to_simple())), b"hello").unwrap();
+    fs::write(dir.join(format!("{:X}", u2.to_simple())), b"world!").unwrap();
+
+    let archive = tempdir("package_archive").join("archive.pack");
+    {
+        let src = Directory::new(dir.clone()).unwrap();
+        let mut file = fs::File::create(&archive).unwrap();
+        Package::pack(&src, &mut file).unwrap();
+    }
+
+    let pack = Package::new(archive.clone()).unwrap();
+    assert!(pack.exists(manifest::NAME.as_ref()));
+    assert!(!pack.exists("a.bin".as_ref()));
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("pack", pack).unwrap();
+
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let shared = res.shared();
+    for &v in &["pack:b.bin", "pack:a.bin"] {
+        let location = Location::new(v).unwrap();
+        shared.load_from(Record(records.clone()), location).unwrap();
+        shared
+            .wait_until(shared.redirect(location).unwrap())
+            .unwrap();
+    }
+
+    assert_eq!(
+        *records.lock().unwrap(),
+        vec![b"world!".to_vec(), b"hello".to_vec()]
+    );
+
+    // The packages of unknown versions are rejected.
+    let mut bytes = fs::read(&archive).unwrap();
+    let magic = package::format().magic();
+    assert_eq!(&bytes[..magic.len()], &magic[..]);
+    bytes[magic.len() - 1] += 1;
+    fs::write(&archive, &bytes).unwrap();
+    let err = Package::new(archive.clone()).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "[Package] Unsupported version 2, expected 1."
+    );
+
+    fs::write(&archive, b"garbage").unwrap();
+    assert!(Package::new(archive).is_err());
+}
+
+#[test]
+fn compressed() {
+    use crayon::res::vfs::compressed::{self, Codec};
+
+    let dir = tempdir("compressed");
+    let u1 = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let u2 = Uuid::parse_str("427EB273E77446B8B7010ACE1B7652EE").unwrap();
+    write_manifest(&dir, &[("a.bin", u1), ("b.bin", u2)]);
+
+    let bytes = vec![7u8; 4096];
+    let encoded = compressed::compress(Codec::Deflate, &bytes);
+    assert!(encoded.len() < bytes.len());
+    assert_eq!(compressed::decompress(&encoded).unwrap(), bytes);
+
+    fs::write(dir.join(format!("{:X}", u1.to_simple())), &encoded).unwrap();
+    fs::write(dir.join(format!("{:X}", u2.to_simple())), b"plain").unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("res", Compressed::new(Directory::new(dir.clone()).unwrap()))
+        .unwrap();
+
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let shared = res.shared();
+    for &v in &[u1, u2] {
+        shared.load_from_uuid(Record(records.clone()), v).unwrap();
+        shared.wait_until(v).unwrap();
+    }
+
+    assert_eq!(*records.lock().unwrap(), vec![bytes, b"plain".to_vec()]);
+
+    let mut corrupted = encoded.clone();
+    corrupted.truncate(encoded.len() / 2);
+    assert!(compressed::decompress(&corrupted).is_err());
+
+    // The length in header must match the uncompressed bytes exactly.
+    for &len in &[4095u64, 4097, u64::max_value()] {
+        let mut forged = encoded.clone();
+        forged[9..compressed::HEADER_LEN].copy_from_slice(&len.to_le_bytes());
+        assert!(compressed::decompress(&forged).is_err());
+    }
+
+    let empty = compressed::compress(Codec::Deflate, &[]);
+    assert!(compressed::decompress(&empty).unwrap().is_empty());
+
+    // Files are written through without compression.
+    let vfs = Compressed::new(Directory::new(dir.clone()).unwrap());
+    vfs.writable()
+        .unwrap()
+        .write("c.bin".as_ref(), b"plain")
+        .unwrap();
+    assert_eq!(fs::read(dir.join("c.bin")).unwrap(), b"plain");
+}
+
+#[derive(Clone)]
+struct Text;
+
+impl Register for Text {
+    type Handle = Handle;
+    type Intermediate = String;
+    type Value = String;
+
+    fn load(&self, _: Self::Handle, bytes: &[u8]) -> Result<Self::Intermediate> {
+        Ok(String::from_utf8(bytes.to_vec())?)
+    }
+
+    fn attach(&self, _: Self::Handle, item: Self::Intermediate) -> Result<Self::Value> {
+        Ok(item)
+    }
+
+    fn detach(&self, _: Self::Handle, _: Self::Value) {}
+
+    fn size(&self, value: &Self::Value) -> Option<usize> {
+        Some(value.len())
+    }
+}
+
+#[test]
+fn memory_fs() {
+    let u1 = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let u2 = Uuid::parse_str("427EB273E77446B8B7010ACE1B7652EE").unwrap();
+
+    let fs = MemoryFS::new();
+    fs.insert("foo", u1, "hello").unwrap();
+    assert!(fs.insert("foo", u2, "world").is_err());
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("mem", fs.clone()).unwrap();
+
+    let registry = Registry::new(res.shared(), Text);
+    let h1 = registry.create_from("mem:foo").unwrap();
+    registry.wait_until(h1).unwrap();
+    assert_eq!(registry.get(h1, |v| v.clone()).unwrap(), "hello");
+    assert!(registry.create_from("mem:bar").is_err());
+
+    // Content generated at runtime.
+    fs.insert("bar", u2, "world").unwrap();
+    let h2 = registry.create_from("mem:bar").unwrap();
+    registry.wait_until(h2).unwrap();
+    assert_eq!(registry.get(h2, |v| v.clone()).unwrap(), "world");
+    assert_eq!(registry.uuid(h2), Some(u2));
+}
+
+struct Counted(MemoryFS, Arc<AtomicUsize>);
+
+impl VFS for Counted {
+    fn read_to_end(&self, location: &Path, buf: &mut Vec<u8>) -> Result<usize> {
+        self.0.read_to_end(location, buf)
+    }
+
+    fn is_dir(&self, location: &Path) -> bool {
+        self.0.is_dir(location)
+    }
+
+    fn exists(&self, location: &Path) -> bool {
+        self.0.exists(location)
+    }
+
+    fn modified_since(&self, location: &Path, ts: SystemTime) -> bool {
+        self.1.fetch_add(1, Ordering::SeqCst);
+        self.0.modified_since(location, ts)
+    }
+}
+
+#[test]
+fn refresh_on_miss() {
+    let uuid = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let fs = MemoryFS::new();
+    let checks = Arc::new(AtomicUsize::new(0));
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("mem", Counted(fs.clone(), checks.clone()))
+        .unwrap();
+    let shared = res.shared();
+
+    // Misses check the modification of manifest at most once per interval.
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let location = Location::new("mem:foo").unwrap();
+    for _ in 0..16 {
+        assert_eq!(shared.redirect(location), None);
+        let record = Record(records.clone());
+        assert!(shared.load_from_uuid(record, uuid).is_err());
+    }
+
+    assert_eq!(checks.load(Ordering::SeqCst), 1);
+
+    fs.insert("foo", uuid, "hello").unwrap();
+    assert_eq!(shared.redirect(location), None);
+
+    thread::sleep(Duration::from_millis(1100));
+    assert_eq!(shared.redirect(location), Some(uuid));
+    assert_eq!(checks.load(Ordering::SeqCst), 2);
+}
+
+fn serve(dir: PathBuf) -> String {
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+
+            let mut req = Vec::new();
+            let mut buf = [0; 512];
+            while !req.ends_with(b"\r\n\r\n") {
+                let len = stream.read(&mut buf).unwrap();
+                req.extend_from_slice(&buf[..len]);
+            }
+
+            let req = String::from_utf8(req).unwrap();
+            let path = req.split_whitespace().nth(1).unwrap();
+            let path = path.trim_start_matches("/res/");
+
+            match fs::read(dir.join(path)) {
+                Ok(bytes) => {
+                    if path == manifest::NAME {
+                        // Sends manifest in chunked transfer encoding.
+                        write!(
+                            stream,
+                            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n"
+                        )
+                        .unwrap();
+                        for chunk in bytes.chunks(7) {
+                            write!(stream, "{:x}\r\n", chunk.len()).unwrap();
+                            stream.write_all(chunk).unwrap();
+                            write!(stream, "\r\n").unwrap();
+                        }
+                        write!(stream, "0\r\n\r\n").unwrap();
+                    } else {
+                        write!(
+                            stream,
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+                            bytes.len()
+                        )
+                        .unwrap();
+                        stream.write_all(&bytes).unwrap();
+                    }
+                }
+                Err(_) => {
+                    write!(
+                        stream,
+                        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    });
+
+    format!("http://{}/res/", addr)
+}
+
+#[test]
+fn http() {
+    let dir = tempdir("http");
+    let u1 = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let u2 = Uuid::parse_str("427EB273E77446B8B7010ACE1B7652EE").unwrap();
+    write_manifest(&dir, &[("a.bin", u1), ("b.bin", u2)]);
+    fs::write(dir.join(format!("{:X}", u1.to_simple())), b"hello").unwrap();
+
+    assert!(Http::new("ftp://localhost/").is_err());
+
+    let url = serve(dir.clone());
+    let cache = tempdir("http_cache");
+    let http = Http::with_cache(url.as_str(), cache.clone()).unwrap();
+    assert!(http.exists(manifest::NAME.as_ref()));
+    assert!(!http.exists(format!("{:X}", u2.to_simple()).as_ref()));
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("http", http).unwrap();
+
+    let registry = Registry::new(res.shared(), Text);
+    let h1 = registry.create_from("http:a.bin").unwrap();
+    registry.wait_until(h1).unwrap();
+    assert_eq!(registry.get(h1, |v| v.clone()).unwrap(), "hello");
+
+    let h2 = registry.create_from("http:b.bin").unwrap();
+    assert!(registry.wait_until(h2).is_err());
+
+    // Resources are written through into the cache directory.
+    let cached = cache.join(format!("{:X}", u1.to_simple()));
+    assert_eq!(fs::read(cached).unwrap(), b"hello");
+    assert!(cache.join(manifest::NAME).exists());
+
+    // The temporary files of atomic writes are never left in cache.
+    assert_eq!(fs::read_dir(&cache).unwrap().count(), 2);
+
+    // The fetched bytes are still used if the cache could not be written.
+    let cache = tempdir("http_cache_blocked");
+    let blocked = cache.join(manifest::NAME);
+    fs::create_dir_all(blocked.join("dir")).unwrap();
+    let http = Http::with_cache(url.as_str(), cache.clone()).unwrap();
+
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("http", http).unwrap();
+
+    let registry = Registry::new(res.shared(), Text);
+    let h1 = registry.create_from("http:a.bin").unwrap();
+    registry.wait_until(h1).unwrap();
+    assert_eq!(registry.get(h1, |v| v.clone()).unwrap(), "hello");
+    assert!(blocked.is_dir());
+}
+
+#[test]
+fn layered_mounts() {
+    let u1 = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let u2 = Uuid::parse_str("427EB273E77446B8B7010ACE1B7652EE").unwrap();
+    let u3 = Uuid::parse_str("6A8B4D2E9C1F4B7A8E3D5C2B1A0F9E8D").unwrap();
+
+    let base = MemoryFS::new();
+    base.insert("a", u1, "base-a").unwrap();
+    base.insert("b", u2, "base-b").unwrap();
+
+    let patch = MemoryFS::new();
+    patch.insert("a", u3, "patch-a").unwrap();
+    patch.insert("b", u2, "patch-b").unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount_with_priority("res", patch.clone(), 1).unwrap();
+    res.mount("res", base.clone()).unwrap();
+    assert!(res.mount("res", MemoryFS::new()).is_err());
+
+    let shared = res.shared();
+    let load = |uuid| {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        shared
+            .load_from_uuid(Record(records.clone()), uuid)
+            .unwrap();
+        shared.wait_until(uuid).unwrap();
+        let mut records = records.lock().unwrap();
+        records.pop().unwrap()
+    };
+
+    // The drive with higher priority wins for both redirect and uuid lookup.
+    let location = Location::new("res:a").unwrap();
+    assert_eq!(shared.redirect(location), Some(u3));
+    assert_eq!(load(u3), b"patch-a");
+    assert_eq!(load(u2), b"patch-b");
+    assert_eq!(load(u1), b"base-a");
+
+    res.unmount_with_priority("res", 1).unwrap();
+    assert_eq!(shared.redirect(location), Some(u1));
+    assert_eq!(load(u2), b"base-b");
+    assert!(res.unmount_with_priority("res", 1).is_err());
+
+    res.mount_with_priority("res", patch, 1).unwrap();
+    assert_eq!(shared.redirect(location), Some(u3));
+
+    res.unmount("res").unwrap();
+    assert_eq!(shared.redirect(location), None);
+    assert!(res.unmount("res").is_err());
+}
+
+#[derive(Clone)]
+struct Scene(Arc<Registry<Handle, Text>>);
+
+impl Register for Scene {
+    type Handle = Handle;
+    type Intermediate = Vec<Handle>;
+    type Value = String;
+
+    fn load(&self, _: Self::Handle, bytes: &[u8]) -> Result<Self::Intermediate> {
+        let mut handles = Vec::new();
+        for v in String::from_utf8(bytes.to_vec())?.split(',') {
+            handles.push(self.0.create_from(format!("res:{}", v).as_str())?);
+        }
+
+        Ok(handles)
+    }
+
+    fn attach(&self, _: Self::Handle, item: Self::Intermediate) -> Result<Self::Value> {
+        let mut value = String::new();
+        for v in item {
+            let text = self
+                .0
+                .get(v, |v| v.clone())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Dependency is not ready."))?;
+            value.push_str(&text);
+        }
+
+        Ok(value)
+    }
+
+    fn detach(&self, _: Self::Handle, _: Self::Value) {}
+}
+
+#[test]
+fn dependencies() {
+    let dir = tempdir("dependencies");
+    let u1 = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let u2 = Uuid::parse_str("427EB273E77446B8B7010ACE1B7652EE").unwrap();
+    let u3 = Uuid::parse_str("6A8B4D2E9C1F4B7A8E3D5C2B1A0F9E8D").unwrap();
+
+    let mut manifest = Manifest::new();
+    manifest.add("a", u1, &[]).unwrap();
+    manifest.add("b", u2, &[]).unwrap();
+    manifest.add("scene", u3, &[u1, u2]).unwrap();
+    assert_eq!(
+        manifest.dependencies(u3).unwrap().collect::<Vec<_>>(),
+        vec![u1, u2]
+    );
+
+    let mut file = fs::File::create(dir.join(manifest::NAME)).unwrap();
+    manifest.save_to(&mut file).unwrap();
+    fs::write(dir.join(format!("{:X}", u1.to_simple())), b"hello ").unwrap();
+    fs::write(dir.join(format!("{:X}", u2.to_simple())), b"world").unwrap();
+    fs::write(dir.join(format!("{:X}", u3.to_simple())), b"a,b").unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(2, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("res", Directory::new(dir.clone()).unwrap())
+        .unwrap();
+
+    let texts = Arc::new(Registry::new(res.shared(), Text));
+    let scenes = Registry::new(res.shared(), Scene(texts.clone()));
+    for _ in 0..16 {
+        let h = scenes.create_from("res:scene").unwrap();
+        scenes.wait_until(h).unwrap();
+        assert_eq!(scenes.get(h, |v| v.clone()).unwrap(), "hello world");
+        scenes.delete(h);
+    }
+
+    // Circular dependencies: a -> scene -> a.
+    manifest.items[0].dependencies = manifest.buf.extend_from_slice(&[2usize]);
+    let chain = manifest.find_cycle(u1).unwrap();
+    assert_eq!(chain, vec![u1, u3, u1]);
+    assert!(manifest.find_cycle(u2).is_none());
+
+    let cycles = manifest.find_cycles();
+    assert_eq!(cycles.len(), 2);
+    assert_eq!(cycles[&u1], vec![u1, u3, u1]);
+    assert_eq!(cycles[&u3], vec![u1, u3, u1]);
+
+    let mut file = fs::File::create(dir.join(manifest::NAME)).unwrap();
+    manifest.save_to(&mut file).unwrap();
+
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("res", Directory::new(dir).unwrap()).unwrap();
+
+    let texts = Arc::new(Registry::new(res.shared(), Text));
+    let scenes = Registry::new(res.shared(), Scene(texts.clone()));
+    assert!(scenes.create_from("res:scene").is_err());
+    assert!(scenes.create_from("res:scene").is_err());
+    assert!(texts.create_from("res:b").is_ok());
+}
+
+#[test]
+fn dependencies_in_flight() {
+    let dir = tempdir("dependencies_in_flight");
+    let u1 = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let u2 = Uuid::parse_str("427EB273E77446B8B7010ACE1B7652EE").unwrap();
+    let u3 = Uuid::parse_str("6A8B4D2E9C1F4B7A8E3D5C2B1A0F9E8D").unwrap();
+
+    let mut manifest = Manifest::new();
+    manifest.add("a", u1, &[]).unwrap();
+    manifest.add("b", u2, &[]).unwrap();
+    manifest.add("scene", u3, &[u1, u2]).unwrap();
+
+    let mut file = fs::File::create(dir.join(manifest::NAME)).unwrap();
+    manifest.save_to(&mut file).unwrap();
+    fs::write(dir.join(format!("{:X}", u1.to_simple())), b"hello").unwrap();
+    fs::write(dir.join(format!("{:X}", u3.to_simple())), b"a,b").unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(2, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("res", Directory::new(dir).unwrap()).unwrap();
+    let shared = res.shared();
+
+    let (tx, rx) = mpsc::channel();
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let gate = Gate(Mutex::new(rx), Record(records.clone()));
+    shared.load_from_uuid(gate, u1).unwrap();
+
+    // The scene is finished after its dependencies, without blocking the other worker.
+    let promise = shared.load_from_uuid(Record(records.clone()), u3).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    assert!(!promise.is_set());
+    assert_eq!(*records.lock().unwrap(), vec![b"a,b".to_vec()]);
+
+    tx.send(()).unwrap();
+    shared.wait_until(u3).unwrap();
+    assert_eq!(
+        *records.lock().unwrap(),
+        vec![b"a,b".to_vec(), b"hello".to_vec()]
+    );
+
+    // The dependencies are requested along with the scene, even if its loader does not.
+    assert!(shared.wait_until(u2).is_err());
+}
+
+struct Gate(Mutex<mpsc::Receiver<()>>, Record);
+
+impl Loader for Gate {
+    fn load(&self, bytes: &[u8]) -> Result<()> {
+        self.0.lock().unwrap().recv().unwrap();
+        self.1.load(bytes)
+    }
+}
+
+struct Chain {
+    res: Arc<ResourceSystemShared>,
+    next: Vec<Uuid>,
+    errors: Arc<Mutex<Vec<String>>>,
+}
+
+impl Loader for Chain {
+    fn load(&self, _: &[u8]) -> Result<()> {
+        if let Some((&uuid, next)) = self.next.split_first() {
+            let chain = Chain {
+                res: self.res.clone(),
+                next: next.to_vec(),
+                errors: self.errors.clone(),
+            };
+
+            match self.res.load_from_uuid(chain, uuid) {
+                Ok(_) => self.res.wait_until(uuid)?,
+                Err(err) => self.errors.lock().unwrap().push(err.to_string()),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn concurrent_loads() {
+    let u1 = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let u2 = Uuid::parse_str("427EB273E77446B8B7010ACE1B7652EE").unwrap();
+
+    let fs = MemoryFS::new();
+    fs.insert("a", u1, "hello").unwrap();
+    fs.insert("b", u2, "world").unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("res", fs).unwrap();
+    let shared = res.shared();
+
+    // Requests of the same uuid share the in-flight loading.
+    let (tx, rx) = mpsc::channel();
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let gate = Gate(Mutex::new(rx), Record(records.clone()));
+    let p1 = shared.load_from_uuid(gate, u1).unwrap();
+    let p2 = shared.load_from_uuid(Record(records.clone()), u1).unwrap();
+    assert!(Arc::ptr_eq(&p1, &p2));
+
+    tx.send(()).unwrap();
+    shared.wait_until(u1).unwrap();
+    assert_eq!(
+        *records.lock().unwrap(),
+        vec![b"hello".to_vec(), b"hello".to_vec()]
+    );
+
+    // Recursive requests of an ancestor are reported with the chain.
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let chain = Chain {
+        res: shared.clone(),
+        next: vec![u2, u1],
+        errors: errors.clone(),
+    };
+
+    shared.load_from_uuid(chain, u1).unwrap();
+    shared.wait_until(u1).unwrap();
+    assert_eq!(
+        *errors.lock().unwrap(),
+        vec![format!(
+            "Circular reference found: {} -> {} -> {}.",
+            u1, u2, u1
+        )]
+    );
+}
+
+#[test]
+fn priorities() {
+    let uuids: Vec<_> = (0..6)
+        .map(|i| Uuid::parse_str(&format!("2943B9386A274730A50702A904F384D{}", i)).unwrap())
+        .collect();
+
+    let fs = MemoryFS::new();
+    for (i, &v) in uuids.iter().enumerate() {
+        fs.insert(format!("{}", i), v, vec![i as u8]).unwrap();
+    }
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("res", fs).unwrap();
+    let shared = res.shared();
+
+    // Blocks the only worker thread.
+    let (tx, rx) = mpsc::channel();
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let gate = Gate(Mutex::new(rx), Record(records.clone()));
+    shared.load_from_uuid(gate, uuids[0]).unwrap();
+    thread::sleep(Duration::from_millis(50));
+
+    let r = || Record(records.clone());
+    shared
+        .load_from_uuid_with_priority(r(), uuids[1], 0)
+        .unwrap();
+    shared
+        .load_from_uuid_with_priority(r(), uuids[2], 1)
+        .unwrap();
+    let cancelled = shared.load_from_uuid(r(), uuids[3]).unwrap();
+    shared.load_from_uuid(r(), uuids[4]).unwrap();
+
+    // Bumps the priority of queued request.
+    shared
+        .load_from_uuid_with_priority(r(), uuids[4], 2)
+        .unwrap();
+
+    assert!(shared.cancel(uuids[3]));
+    assert!(!shared.cancel(uuids[0]));
+    assert!(!shared.cancel(uuids[3]));
+
+    // Deleting a not-yet-loaded handle aborts the pending loading, unless it is shared with
+    // other requests.
+    let registry = Registry::new(shared.clone(), Text);
+    let h1 = registry.create_from_uuid(uuids[1]).unwrap();
+    registry.delete(h1);
+    assert!(registry.contains(h1));
+
+    let h2 = registry.create_from_uuid(uuids[5]).unwrap();
+    registry.delete(h2);
+    assert!(!registry.contains(h2));
+
+    // The cancelled entry is never revived by later requests.
+    let h3 = registry.create_from_uuid(uuids[5]).unwrap();
+    assert!(h3 != h2);
+
+    tx.send(()).unwrap();
+    for &v in &uuids {
+        shared.wait_until(v).unwrap();
+    }
+
+    assert!(!registry.contains(h1));
+    registry.wait_until(h3).unwrap();
+    assert_eq!(registry.get(h3, |v| v.clone()).unwrap(), "\u{5}");
+    assert!(cancelled.result().is_err());
+    assert_eq!(
+        *records.lock().unwrap(),
+        vec![vec![0], vec![4], vec![4], vec![2], vec![1]]
+    );
+}
+
+// Counts the reads of resources, except the manifest.
+struct Reads(MemoryFS, Arc<AtomicUsize>);
+
+impl VFS for Reads {
+    fn read_to_end(&self, location: &Path, buf: &mut Vec<u8>) -> Result<usize> {
+        if location != Path::new(manifest::NAME) {
+            self.1.fetch_add(1, Ordering::SeqCst);
+        }
+
+        self.0.read_to_end(location, buf)
+    }
+
+    fn is_dir(&self, location: &Path) -> bool {
+        self.0.is_dir(location)
+    }
+
+    fn exists(&self, location: &Path) -> bool {
+        self.0.exists(location)
+    }
+
+    fn modified_since(&self, location: &Path, ts: SystemTime) -> bool {
+        self.0.modified_since(location, ts)
+    }
+}
+
+#[test]
+fn requeue() {
+    let u1 = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let u2 = Uuid::parse_str("427EB273E77446B8B7010ACE1B7652EE").unwrap();
+
+    let fs = MemoryFS::new();
+    fs.insert("a", u1, "a").unwrap();
+    fs.insert("b", u2, "b").unwrap();
+    let reads = Arc::new(AtomicUsize::new(0));
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("res", Reads(fs, reads.clone())).unwrap();
+    let shared = res.shared();
+
+    // Blocks the only worker thread.
+    let (tx, rx) = mpsc::channel();
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let gate = Gate(Mutex::new(rx), Record(records.clone()));
+    shared.load_from_uuid(gate, u1).unwrap();
+    thread::sleep(Duration::from_millis(50));
+
+    // The job requeued with higher priority runs exactly once.
+    for i in 0..3 {
+        let record = Record(records.clone());
+        shared.load_from_uuid_with_priority(record, u2, i).unwrap();
+    }
+
+    tx.send(()).unwrap();
+    shared.wait_until(u2).unwrap();
+    thread::sleep(Duration::from_millis(50));
+
+    assert_eq!(reads.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        *records.lock().unwrap(),
+        vec![b"a".to_vec(), b"b".to_vec(), b"b".to_vec(), b"b".to_vec()]
+    );
+}
+
+struct Failing;
+
+impl Loader for Failing {
+    fn load(&self, _: &[u8]) -> Result<()> {
+        let err = io::Error::new(io::ErrorKind::InvalidData, "failing");
+        Err(err.into())
+    }
+}
+
+#[test]
+fn shared_failures() {
+    let uuid = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let fs = MemoryFS::new();
+    fs.insert("a", uuid, "hello").unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("mem", fs.clone()).unwrap();
+    let shared = res.shared();
+
+    let (tx, rx) = mpsc::channel();
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let gate = Gate(Mutex::new(rx), Record(records.clone()));
+    let p1 = shared.load_from_uuid(gate, uuid).unwrap();
+    let p2 = shared.load_from_uuid(Failing, uuid).unwrap();
+    assert!(Arc::ptr_eq(&p1, &p2));
+    tx.send(()).unwrap();
+
+    // Every waiter gets the error, even after the request has been removed.
+    assert!(shared.wait_until(uuid).is_err());
+    assert!(shared.wait_until(uuid).is_err());
+    assert!(p1.result().is_err());
+    assert!(p2.result().is_err());
+
+    // The error is cleared by a new request.
+    let record = Record(records.clone());
+    shared.load_from_uuid(record, uuid).unwrap();
+    shared.wait_until(uuid).unwrap();
+    assert!(shared.wait_until(uuid).is_ok());
+    assert_eq!(records.lock().unwrap().len(), 2);
+
+    // Only the errors of the latest failed resources are preserved.
+    let uuids: Vec<_> = (1..300)
+        .map(|i| Uuid::from_fields(i, 0, 0, &[0; 8]).unwrap())
+        .collect();
+
+    for &v in &uuids {
+        fs.insert(v.to_string(), v, "").unwrap();
+    }
+
+    for &v in &uuids {
+        shared.load_from_uuid(Failing, v).unwrap();
+        assert!(shared.wait_until(v).is_err());
+    }
+
+    assert!(shared.wait_until(uuids[0]).is_ok());
+    assert!(shared.wait_until(uuids[uuids.len() - 1]).is_err());
+}
+
+#[test]
+fn removed_before_loading() {
+    let u1 = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let u2 = Uuid::parse_str("427EB273E77446B8B7010ACE1B7652EE").unwrap();
+    let fs = MemoryFS::new();
+    fs.insert("a", u1, "hello").unwrap();
+    fs.insert("b", u2, "world").unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("mem", fs.clone()).unwrap();
+    let shared = res.shared();
+
+    // Blocks the only worker thread.
+    let (tx, rx) = mpsc::channel();
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let gate = Gate(Mutex::new(rx), Record(records.clone()));
+    shared.load_from_uuid(gate, u1).unwrap();
+    let promise = shared.load_from_uuid(Record(records.clone()), u2).unwrap();
+
+    // The manifest is refreshed before the queued request is executed.
+    fs.remove(u2).unwrap();
+    let location = Location::new("mem:missing").unwrap();
+    assert_eq!(shared.redirect(location), None);
+    let location = Location::new("mem:b").unwrap();
+    assert_eq!(shared.redirect(location), None);
+
+    tx.send(()).unwrap();
+    shared.wait_until(u1).unwrap();
+    assert!(shared.wait_until(u2).is_err());
+    assert!(promise.result().is_err());
+    assert_eq!(*records.lock().unwrap(), vec![b"hello".to_vec()]);
+}
+
+#[test]
+fn resource_group() {
+    let u1 = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let u2 = Uuid::parse_str("427EB273E77446B8B7010ACE1B7652EE").unwrap();
+    let u3 = Uuid::parse_str("6A8B4D2E9C1F4B7A8E3D5C2B1A0F9E8D").unwrap();
+
+    let fs = MemoryFS::new();
+    fs.insert("a", u1, "hello").unwrap();
+    fs.insert("b", u2, "world!").unwrap();
+    fs.insert("c", u3, vec![0xff, 0xfe]).unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(2, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("res", fs).unwrap();
+
+    let registry = Registry::new(res.shared(), Text);
+    let mut group = ResourceGroup::new(res.shared());
+    for &v in &["res:a", "res:b", "res:c"] {
+        group.submit_from(v, |v| registry.create_from(v)).unwrap();
+    }
+
+    assert!(group
+        .submit_from("res:d", |v| registry.create_from(v))
+        .is_err());
+    assert_eq!(group.len(), 3);
+
+    sched.shared().wait_until(&group);
+    assert_eq!(
+        group.progress(),
+        ResourceGroupProgress {
+            loaded: 3,
+            total: 3,
+            bytes: 13,
+        }
+    );
+
+    // Resources that have been loaded already.
+    group.submit(u1, |v| registry.create_from_uuid(v)).unwrap();
+    assert_eq!(group.progress().loaded, 4);
+
+    let errors = group.take_errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, u3);
+    assert!(group.take_errors().is_empty());
+}
+
+#[derive(Clone)]
+struct Versioned(Format);
+
+impl Register for Versioned {
+    type Handle = Handle;
+    type Intermediate = String;
+    type Value = String;
+
+    fn load(&self, _: Self::Handle, bytes: &[u8]) -> Result<Self::Intermediate> {
+        let payload = self.0.decode(bytes)?;
+        Ok(String::from_utf8(payload.to_vec())?)
+    }
+
+    fn attach(&self, _: Self::Handle, item: Self::Intermediate) -> Result<Self::Value> {
+        Ok(item)
+    }
+
+    fn detach(&self, _: Self::Handle, _: Self::Value) {}
+
+    fn format(&self) -> Option<&Format> {
+        Some(&self.0)
+    }
+}
+
+fn upgrade_v1(payload: &[u8]) -> Result<Vec<u8>> {
+    Ok(payload.to_ascii_uppercase())
+}
+
+#[test]
+fn register_upgrade() {
+    let uuid = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let mut bytes = Format::new("Versioned", *b"VERS", 1).magic().to_vec();
+    bytes.extend_from_slice(b"hello");
+
+    let fs = MemoryFS::new();
+    fs.insert("a", uuid, bytes).unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("mem", fs).unwrap();
+
+    let texts = Registry::new(res.shared(), Text);
+    assert!(texts.register_upgrade(1, upgrade_v1).is_err());
+
+    let format = Format::new("Versioned", *b"VERS", 2);
+    let registry = Registry::new(res.shared(), Versioned(format));
+    let handle = registry.create_from("mem:a").unwrap();
+    assert!(registry.wait_until(handle).is_err());
+
+    // Upgrades could be registered at runtime.
+    assert!(registry.register_upgrade(2, upgrade_v1).is_err());
+    registry.register_upgrade(1, upgrade_v1).unwrap();
+    registry.retry(handle).unwrap();
+    registry.wait_until(handle).unwrap();
+    assert_eq!(registry.get(handle, |v| v.clone()).unwrap(), "HELLO");
+}
+
+#[test]
+fn registry_state() {
+    use crayon::res::registry::ResourceState;
+
+    let u1 = Uuid::parse_str("2943B9386A274730A50702A904F384D5").unwrap();
+    let u2 = Uuid::parse_str("427EB273E77446B8B7010ACE1B7652EE").unwrap();
+
+    let fs = MemoryFS::new();
+    fs.insert("a", u1, vec![0xff, 0xfe]).unwrap();
+
+    let dir = tempdir("registry_state");
+    write_manifest(&dir, &[("missing", u2)]);
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("mem", fs.clone()).unwrap();
+    res.mount("dir", Directory::new(dir).unwrap()).unwrap();
+
+    let registry = Registry::new(res.shared(), Text);
+    let h1 = registry.create_from("mem:a").unwrap();
+    assert!(registry.wait_until(h1).is_err());
+
+    match registry.state(h1) {
+        Some(ResourceState::Failed(err)) => assert!(err.to_string().contains("utf-8")),
+        v => panic!("unexpected state {:?}", v),
+    }
+
+    // Retries after fixing the content.
+    fs.insert("a", u1, "hello").unwrap();
+    registry.retry(h1).unwrap();
+    registry.wait_until(h1).unwrap();
+    match registry.state(h1) {
+        Some(ResourceState::Ready) => {}
+        v => panic!("unexpected state {:?}", v),
+    }
+
+    assert!(registry.retry(h1).is_err());
+    assert_eq!(registry.get(h1, |v| v.clone()).unwrap(), "hello");
+
+    // Failures of reading are preserved as well.
+    let h2 = registry.create_from("dir:missing").unwrap();
+    assert!(registry.wait_until(h2).is_err());
+    match registry.state(h2) {
+        Some(ResourceState::Failed(_)) => {}
+        v => panic!("unexpected state {:?}", v),
+    }
+
+    registry.delete(h1);
+    assert!(registry.state(h1).is_none());
+}
+
+#[test]
+fn owned_handles() {
+    let u1 = Uuid::parse_str("9B4E1A5C3F2D4B6A8E7C1D2F3A4B5C6D").unwrap();
+
+    let fs = MemoryFS::new();
+    fs.insert("a", u1, "hello").unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("mem", fs).unwrap();
+
+    let registry = Registry::new(res.shared(), Text);
+    let h1 = registry.create_from("mem:a").unwrap();
+    registry.wait_until(h1).unwrap();
+
+    let r1 = registry.own(h1);
+    let r2 = r1.clone();
+    assert_eq!(r1, r2);
+    assert_eq!(r2.handle(), h1);
+
+    // Shares the same entry with plain handles.
+    let h2 = registry.create_from("mem:a").unwrap();
+    assert_eq!(h1, h2);
+
+    drop(r1);
+    registry.delete(h2);
+    assert!(registry.contains(h1));
+    assert_eq!(registry.get(r2.handle(), |v| v.clone()).unwrap(), "hello");
+
+    drop(r2);
+    assert!(!registry.contains(h1));
+}
+
+#[test]
+fn registry_entries() {
+    use crayon::res::registry::ResourceState;
+
+    let u1 = Uuid::parse_str("5F1C2B3A4D5E4F60818293A4B5C6D7E8").unwrap();
+    let u2 = Uuid::parse_str("6A2D3C4B5E6F4071929304B5C6D7E8F9").unwrap();
+
+    let fs = MemoryFS::new();
+    fs.insert("a", u1, "hello").unwrap();
+    fs.insert("b", u2, vec![0xff]).unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("mem", fs).unwrap();
+
+    let registry = Registry::new(res.shared(), Text);
+    let h1 = registry.create_from("mem:a").unwrap();
+    registry.create_from("mem:a").unwrap();
+    let h2 = registry.create_from("mem:b").unwrap();
+    let h3 = registry.create("runtime".to_owned()).unwrap();
+    registry.wait_until(h1).unwrap();
+    assert!(registry.wait_until(h2).is_err());
+
+    let mut entries = registry.entries();
+    entries.sort_by_key(|v| v.filename.clone());
+    assert_eq!(entries.len(), 3);
+
+    assert_eq!(entries[0].handle, h3);
+    assert_eq!(entries[0].uuid, None);
+    assert_eq!(entries[0].filename, None);
+    assert_eq!(entries[0].size, Some(7));
+
+    assert_eq!(entries[1].handle, h1);
+    assert_eq!(entries[1].uuid, Some(u1));
+    assert_eq!(entries[1].filename, Some("a".to_owned()));
+    assert_eq!(entries[1].rc, 2);
+    assert_eq!(entries[1].size, Some(5));
+    match entries[1].state {
+        ResourceState::Ready => {}
+        ref v => panic!("unexpected state {:?}", v),
+    }
+
+    assert_eq!(entries[2].filename, Some("b".to_owned()));
+    assert_eq!(entries[2].rc, 1);
+    assert_eq!(entries[2].size, None);
+    match entries[2].state {
+        ResourceState::Failed(_) => {}
+        ref v => panic!("unexpected state {:?}", v),
+    }
+}
+
+#[test]
+fn registry_budget() {
+    use crayon::res::registry::ResourceState;
+
+    let u1 = Uuid::parse_str("7B3E4D5C6F704182A3B4C5D6E7F80911").unwrap();
+    let u2 = Uuid::parse_str("8C4F5E6D708142938B4C5D6E7F809122").unwrap();
+    let u3 = Uuid::parse_str("9D506F7E819243A49C5D6E7F80912233").unwrap();
+
+    let fs = MemoryFS::new();
+    fs.insert("a", u1, "hello").unwrap();
+    fs.insert("b", u2, "abc").unwrap();
+    fs.insert("c", u3, "xyzw").unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("mem", fs.clone()).unwrap();
+    res.set_hot_reload(Duration::from_secs(0));
+
+    let registry = Registry::new(res.shared(), Text);
+    registry.set_budget(8);
+
+    let h1 = registry.create_from("mem:a").unwrap();
+    let h2 = registry.create_from("mem:b").unwrap();
+    let h3 = registry.create_from("mem:c").unwrap();
+    registry.wait_until(h1).unwrap();
+    registry.wait_until(h2).unwrap();
+    registry.wait_until(h3).unwrap();
+
+    registry.delete(h1);
+    registry.delete(h2);
+    assert_eq!(registry.cached(), 8);
+
+    // The unused resources in cache are invisible until they are revived.
+    assert!(!registry.contains(h1));
+    assert!(registry.get(h1, |v| v.clone()).is_none());
+    assert!(registry.state(h1).is_none());
+    assert_eq!(registry.len(), 1);
+
+    // Revives from cache without loading.
+    assert_eq!(registry.create_from("mem:a").unwrap(), h1);
+    assert!(registry.contains(h1));
+    match registry.state(h1) {
+        Some(ResourceState::Ready) => {}
+        v => panic!("unexpected state {:?}", v),
+    }
+    assert_eq!(registry.cached(), 3);
+    assert_eq!(registry.len(), 2);
+
+    // The size of resource in cache is updated after reloading.
+    thread::sleep(Duration::from_millis(50));
+    fs.insert("b", u2, "abcd").unwrap();
+    res.advance();
+    let _ = res.shared().wait_until(u2);
+    assert_eq!(registry.cached(), 4);
+
+    // Evicts the least-recently-used ones.
+    registry.delete(h1);
+    registry.delete(h3);
+    assert_eq!(registry.cached(), 4);
+    assert_eq!(registry.len(), 0);
+    assert_eq!(registry.create_from("mem:c").unwrap(), h3);
+    assert_eq!(registry.cached(), 0);
+    registry.delete(h3);
+
+    registry.set_budget(0);
+    assert_eq!(registry.cached(), 0);
+    assert!(!registry.contains(h3));
+    assert_eq!(registry.len(), 0);
+}
+
+#[test]
+fn sub_resources() {
+    let u1 = Uuid::parse_str("A1B2C3D4E5F64718293A4B5C6D7E8F90").unwrap();
+    let u2 = Uuid::parse_str("B2C3D4E5F6A74829304B5C6D7E8F90A1").unwrap();
+    let u3 = Uuid::parse_str("C3D4E5F6A7B84930415C6D7E8F90A1B2").unwrap();
+
+    let fs = MemoryFS::new();
+    fs.insert("models/ship.fbx", u1, "ship").unwrap();
+    fs.insert("models/ship.fbx#Hull", u2, "hull").unwrap();
+    fs.insert("models/ship.fbx#mesh/2", u3, "mesh").unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("res", fs).unwrap();
+
+    let res = res.shared();
+    assert_eq!(res.redirect("res:models/ship.fbx".into()), Some(u1));
+    assert_eq!(res.redirect("res:models/ship.fbx#Hull".into()), Some(u2));
+    assert_eq!(res.redirect("res:models/ship.fbx#mesh/2".into()), Some(u3));
+
+    // Sub-resources are never resolved with the file without fragment.
+    assert_eq!(res.redirect("res:models/ship.fbx#Deck".into()), None);
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let location = "res:models/ship.fbx#Deck".into();
+    assert!(res.load_from(Record(records.clone()), location).is_err());
+    assert!(records.lock().unwrap().is_empty());
+
+    let registry = Registry::new(res.clone(), Text);
+    let handle = registry.create_from("res:models/ship.fbx#Hull").unwrap();
+    registry.wait_until(handle).unwrap();
+    assert_eq!(registry.get(handle, |v| v.clone()).unwrap(), "hull");
+}
+
+#[test]
+fn list() {
+    let ids: Vec<_> = (0..5)
+        .map(|i| Uuid::parse_str(&format!("{:032X}", i + 1)).unwrap())
+        .collect();
+
+    let fs = MemoryFS::new();
+    fs.insert("levels/forest/a.prefab", ids[0], "").unwrap();
+    fs.insert("levels/forest/b.prefab", ids[1], "").unwrap();
+    fs.insert("levels/forest/textures/c.png", ids[2], "")
+        .unwrap();
+    fs.insert("levels/desert/d.prefab", ids[3], "").unwrap();
+
+    let overlay = MemoryFS::new();
+    overlay
+        .insert("levels/forest/a.prefab", ids[4], "")
+        .unwrap();
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("res", fs).unwrap();
+    res.mount_with_priority("res", overlay, 1).unwrap();
+    let res = res.shared();
+
+    let items = res.list("res", "levels/forest/");
+    assert_eq!(
+        items,
+        vec![
+            ("levels/forest/a.prefab".to_owned(), ids[4]),
+            ("levels/forest/b.prefab".to_owned(), ids[1]),
+            ("levels/forest/textures/c.png".to_owned(), ids[2]),
+        ]
+    );
+
+    let names = |pattern| -> Vec<String> {
+        res.list("res", pattern)
+            .into_iter()
+            .map(|(filename, _)| filename)
+            .collect()
+    };
+
+    assert_eq!(
+        names("levels/*/*.prefab"),
+        vec![
+            "levels/desert/d.prefab",
+            "levels/forest/a.prefab",
+            "levels/forest/b.prefab",
+        ]
+    );
+
+    assert_eq!(names("levels/forest/*"), names("levels/forest/?.prefab"));
+    assert_eq!(names("**.png"), vec!["levels/forest/textures/c.png"]);
+    assert_eq!(
+        names("levels/**/c.png"),
+        vec!["levels/forest/textures/c.png"]
+    );
+    assert!(names("levels/*.prefab").is_empty());
+    assert!(res.list("unknown", "").is_empty());
+}
+
+#[test]
+fn save() {
+    use crayon::res::vfs::WritableVFS;
+
+    let dir = tempdir("save");
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    // A directory without manifest could only be mounted as writable.
+    assert!(res.mount("save", Directory::new(&dir).unwrap()).is_err());
+    assert!(res.mount_writable("mem", MemoryFS::new()).is_err());
+    res.mount_writable("save", Directory::new(&dir).unwrap())
+        .unwrap();
+    res.mount("mem", MemoryFS::new()).unwrap();
+    let res = res.shared();
+
+    let location = Location::new("save:slots/1.sav").unwrap();
+    let promise = res.save(location, "hello").unwrap();
+    sched.shared().wait_until(promise.as_ref());
+    promise.result().unwrap();
+    assert_eq!(promise.bytes(), 5);
+
+    // The saved file is added into the manifest of the mount.
+    let uuid = res.redirect(location).unwrap();
+    let path = dir.join(format!("{:X}", uuid.to_simple()));
+    assert_eq!(fs::read(&path).unwrap(), b"hello");
+    assert!(dir.join(".MANIFEST").exists());
+
+    // Replaces the existing file without leaving temporary files.
+    let promise = res.save(location, vec![1, 2, 3]).unwrap();
+    sched.shared().wait_until(promise.as_ref());
+    promise.result().unwrap();
+    assert_eq!(res.redirect(location), Some(uuid));
+    assert_eq!(fs::read(&path).unwrap(), vec![1, 2, 3]);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+    let records = Arc::new(Mutex::new(Vec::new()));
+    res.load_from(Record(records.clone()), location).unwrap();
+    res.wait_until(uuid).unwrap();
+    assert_eq!(*records.lock().unwrap(), vec![vec![1, 2, 3]]);
+
+    assert!(res.save("mem:1.sav".into(), "hello").is_err());
+    assert!(res.save("save:1.sav#fragment".into(), "hello").is_err());
+
+    // The locations out of the root are rejected.
+    let outside = dir.parent().unwrap().join("crayon-save-escape.sav");
+    let escapes = [
+        "../crayon-save-escape.sav".to_owned(),
+        "slots/../../crayon-save-escape.sav".to_owned(),
+        outside.to_str().unwrap().to_owned(),
+    ];
+
+    for v in &escapes {
+        let location = format!("save:{}", v);
+        if let Ok(promise) = res.save(location.as_str().into(), "hello") {
+            sched.shared().wait_until(promise.as_ref());
+            assert!(promise.result().is_err());
+        }
+    }
+
+    assert!(!outside.exists());
+
+    let vfs = Directory::new(&dir).unwrap();
+    vfs.write("slots/1.sav".as_ref(), b"hello").unwrap();
+    vfs.rename("slots/1.sav".as_ref(), "slots/2.sav".as_ref())
+        .unwrap();
+    assert!(!vfs.exists("slots/1.sav".as_ref()));
+    vfs.remove("slots/2.sav".as_ref()).unwrap();
+    assert!(!vfs.exists("slots/2.sav".as_ref()));
+
+    let escape = Path::new("../crayon-save-escape.sav");
+    assert!(vfs.write(escape, b"hello").is_err());
+    assert!(vfs.rename("a.sav".as_ref(), escape).is_err());
+    assert!(vfs.remove(&outside).is_err());
+}
+
+#[test]
+fn integrity() {
+    use crayon::res::errors::Error as ResError;
+
+    let u1 = Uuid::parse_str("D4E5F6A7B8C94A5B6C7D8E9FA0B1C2D3").unwrap();
+    let u2 = Uuid::parse_str("E5F6A7B8C9DA4B6C7D8E9FA0B1C2D3E4").unwrap();
+
+    let dir = tempdir("integrity");
+    let mut manifest = Manifest::new();
+    manifest.add_with_integrity("a", u1, &[], b"hello").unwrap();
+    manifest.add_with_integrity("b", u2, &[], b"world").unwrap();
+
+    let mut file = fs::File::create(dir.join(manifest::NAME)).unwrap();
+    manifest.save_to(&mut file).unwrap();
+
+    let name = |uuid: Uuid| format!("{:X}", uuid.to_simple());
+    fs::write(dir.join(name(u1)), "hello").unwrap();
+    fs::write(dir.join(name(u2)), "wor1d").unwrap();
+
+    assert!(manifest.verify(u1, b"hello").is_ok());
+    assert!(manifest.verify(u1, b"hell").is_err());
+
+    let sched = crayon::sched::ScheduleSystem::new(1, None, None);
+    let mut res = ResourceSystem::new(sched.shared()).unwrap();
+    res.mount("res", Directory::new(dir).unwrap()).unwrap();
+    let res = res.shared();
+
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let promise = res.load_from_uuid(Record(records.clone()), u1).unwrap();
+    sched.shared().wait_until(promise.as_ref());
+    promise.result().unwrap();
+
+    // The corrupted contents are never passed to loaders.
+    let promise = res.load_from_uuid(Record(records.clone()), u2).unwrap();
+    sched.shared().wait_until(promise.as_ref());
+    let err = promise.result().unwrap_err();
+    match err.downcast_ref::<ResError>() {
+        Some(ResError::IntegrityViolated { uuid, filename, .. }) => {
+            assert_eq!(*uuid, u2);
+            assert_eq!(filename, "b");
+        }
+        v => panic!("unexpected error {:?}", v),
+    }
+
+    assert_eq!(*records.lock().unwrap(), vec![b"hello".to_vec()]);
+}
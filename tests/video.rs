@@ -0,0 +1,739 @@
+extern crate crayon;
+
+use std::sync::Arc;
+
+use crayon::application::window::Window;
+use crayon::math;
+use crayon::video::prelude::*;
+use crayon::video::software::{Program, SoftwareDevice};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct Vertex {
+    position: [f32; 3],
+    color: [u8; 4],
+}
+
+impl Vertex {
+    fn new(position: [f32; 3], color: [u8; 4]) -> Self {
+        Vertex {
+            position: position,
+            color: color,
+        }
+    }
+
+    fn layout() -> VertexLayout {
+        VertexLayout::build()
+            .with(Attribute::Position, VertexFormat::Float, 3, false)
+            .with(Attribute::Color0, VertexFormat::UByte, 4, true)
+            .finish()
+    }
+
+    fn attributes() -> AttributeLayout {
+        AttributeLayout::build()
+            .with(Attribute::Position, 3)
+            .with(Attribute::Color0, 4)
+            .finish()
+    }
+}
+
+fn testbed(dimensions: (u32, u32)) -> (VideoSystem, Arc<SoftwareDevice>) {
+    let device = SoftwareDevice::new(dimensions.into());
+    let video = VideoSystem::software(None, device.clone());
+    (video, device)
+}
+
+fn advance(video: &mut VideoSystem) {
+    video.swap_frames();
+    video.advance(&Window::headless()).unwrap();
+}
+
+fn mesh(video: &VideoSystemShared, verts: &[Vertex], idxes: &[u16]) -> MeshHandle {
+    let mut params = MeshParams::default();
+    params.num_verts = verts.len();
+    params.num_idxes = idxes.len();
+    params.layout = Vertex::layout();
+
+    let data = MeshData {
+        vptr: IndexFormat::encode(verts).into(),
+        iptr: IndexFormat::encode(idxes).into(),
+    };
+
+    video.create_mesh(params, data).unwrap()
+}
+
+fn quad(video: &VideoSystemShared, z: f32, color: [u8; 4]) -> MeshHandle {
+    let verts = [
+        Vertex::new([-1.0, -1.0, z], color),
+        Vertex::new([1.0, -1.0, z], color),
+        Vertex::new([1.0, 1.0, z], color),
+        Vertex::new([-1.0, 1.0, z], color),
+    ];
+
+    mesh(video, &verts, &[0, 1, 2, 0, 2, 3])
+}
+
+fn create_shader(video: &VideoSystemShared, state: RenderState) -> ShaderHandle {
+    let mut params = ShaderParams::default();
+    params.attributes = Vertex::attributes();
+    params.state = state;
+
+    let src = "software".to_owned();
+    video.create_shader(params, src.clone(), src).unwrap()
+}
+
+#[test]
+fn rasterize() {
+    let (mut video, device) = testbed((4, 4));
+    let shared = video.shared();
+
+    let surface = shared.create_surface(SurfaceParams::default()).unwrap();
+    let shader = create_shader(&shared, RenderState::default());
+    let verts = [
+        Vertex::new([-1.0, -1.0, 0.0], [255, 0, 0, 255]),
+        Vertex::new([1.0, -1.0, 0.0], [255, 0, 0, 255]),
+        Vertex::new([-1.0, 1.0, 0.0], [255, 0, 0, 255]),
+    ];
+
+    let triangle = mesh(&shared, &verts, &[0, 1, 2]);
+    shared.draw(surface, DrawCall::new(shader, triangle));
+    advance(&mut video);
+
+    assert_eq!(device.pixel(0, 0), [255, 0, 0, 255]);
+    assert_eq!(device.pixel(2, 0), [255, 0, 0, 255]);
+    assert_eq!(device.pixel(3, 3), [0, 0, 0, 255]);
+    assert_eq!(device.pixel(3, 1), [0, 0, 0, 255]);
+
+    // The pixels on the shared edge of two triangles should be covered exactly once.
+    let mut state = RenderState::default();
+    state.color_blend = Some((Equation::Add, BlendFactor::One, BlendFactor::One));
+    let shader = create_shader(&shared, state);
+    let quad = quad(&shared, 0.0, [64, 64, 64, 64]);
+
+    shared.draw(surface, DrawCall::new(shader, quad));
+    advance(&mut video);
+
+    for v in device.pixels().chunks(4) {
+        assert_eq!(v, [64, 64, 64, 255]);
+    }
+}
+
+#[test]
+fn render_state() {
+    let (mut video, device) = testbed((4, 4));
+    let shared = video.shared();
+
+    let surface = shared.create_surface(SurfaceParams::default()).unwrap();
+
+    let mut state = RenderState::default();
+    state.depth_test = Comparison::Less;
+    state.depth_write = true;
+    let shader = create_shader(&shared, state);
+
+    // The far quad is occluded by the near one, even if it's drawn later.
+    let near = quad(&shared, -0.5, [0, 255, 0, 255]);
+    let far = quad(&shared, 0.5, [0, 0, 255, 255]);
+    shared.draw(surface, DrawCall::new(shader, near));
+    shared.draw(surface, DrawCall::new(shader, far));
+    advance(&mut video);
+
+    for v in device.pixels().chunks(4) {
+        assert_eq!(v, [0, 255, 0, 255]);
+    }
+
+    // Clockwise triangles are culled as back faces.
+    let mut state = RenderState::default();
+    state.cull_face = CullFace::Back;
+    let shader = create_shader(&shared, state);
+
+    let verts = [
+        Vertex::new([-1.0, -1.0, 0.0], [255, 255, 255, 255]),
+        Vertex::new([-1.0, 1.0, 0.0], [255, 255, 255, 255]),
+        Vertex::new([1.0, -1.0, 0.0], [255, 255, 255, 255]),
+    ];
+
+    let triangle = mesh(&shared, &verts, &[0, 1, 2]);
+    shared.draw(surface, DrawCall::new(shader, triangle));
+    advance(&mut video);
+
+    for v in device.pixels().chunks(4) {
+        assert_eq!(v, [0, 0, 0, 255]);
+    }
+
+    // Only the pixels inside the scissor box are touched.
+    let white = quad(&shared, 0.0, [255, 255, 255, 255]);
+    let scissor = SurfaceScissor::Enable {
+        position: math::Vector2::new(1, 2),
+        size: math::Vector2::new(2, 1),
+    };
+
+    shared.update_scissor(surface, scissor);
+    shared.draw(surface, DrawCall::new(shader, white));
+    advance(&mut video);
+
+    for y in 0..4 {
+        for x in 0..4 {
+            let v = if y == 2 && (x == 1 || x == 2) { 255 } else { 0 };
+            assert_eq!(device.pixel(x, y), [v, v, v, 255]);
+        }
+    }
+}
+
+#[test]
+fn program() {
+    let (mut video, device) = testbed((4, 4));
+    let shared = video.shared();
+
+    let mut params = TextureParams::default();
+    params.filter = TextureFilter::Nearest;
+    params.dimensions = (2, 2).into();
+
+    let bytes = vec![
+        255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255,
+    ];
+
+    let data = TextureData {
+        bytes: vec![bytes.into_boxed_slice()],
+    };
+
+    let texture = shared.create_texture(params, data).unwrap();
+
+    let mut params = RenderTextureParams::default();
+    params.format = RenderTextureFormat::RGBA8;
+    params.dimensions = (4, 4).into();
+    let rt = shared.create_render_texture(params).unwrap();
+
+    let mut params = SurfaceParams::default();
+    params.set_attachments(&[rt], None).unwrap();
+    let surface = shared.create_surface(params).unwrap();
+
+    let mut params = ShaderParams::default();
+    params.attributes = Vertex::attributes();
+    params.uniforms = UniformVariableLayout::build()
+        .with("u_Texture", UniformVariableType::Texture)
+        .finish();
+
+    let src = "software".to_owned();
+    let shader = shared.create_shader(params, src.clone(), src).unwrap();
+
+    let program = Program::new(
+        |_, attributes, varyings| {
+            let position = attributes.get(Attribute::Position);
+            varyings[0] = (position.x + 1.0) * 0.5;
+            varyings[1] = (position.y + 1.0) * 0.5;
+            position
+        },
+        |uniforms, varyings| {
+            let texcoord = math::Vector2::new(varyings[0], varyings[1]);
+            Some(uniforms.sample("u_Texture", texcoord))
+        },
+    );
+
+    device.set_program(shader, program);
+
+    let quad = quad(&shared, 0.0, [0, 0, 0, 0]);
+    let mut dc = DrawCall::new(shader, quad);
+    dc.set_uniform_variable("u_Texture", texture);
+    shared.draw(surface, dc);
+    advance(&mut video);
+
+    // The default framebuffer is untouched.
+    assert_eq!(device.pixel(0, 0), [0, 0, 0, 0]);
+
+    let pixels = device.render_texture_pixels(rt).unwrap();
+    let pixel = |x: usize, y: usize| &pixels[(y * 4 + x) * 4..(y * 4 + x) * 4 + 4];
+    assert_eq!(pixel(0, 0), [255, 0, 0, 255]);
+    assert_eq!(pixel(3, 0), [0, 255, 0, 255]);
+    assert_eq!(pixel(1, 3), [0, 0, 255, 255]);
+    assert_eq!(pixel(2, 2), [255, 255, 255, 255]);
+}
+
+#[test]
+fn trace() {
+    use crayon::video::trace::Trace;
+
+    let (mut video, device) = testbed((4, 4));
+    video.start_recording();
+
+    let shared = video.shared();
+    let surface = shared.create_surface(SurfaceParams::default()).unwrap();
+    let shader = create_shader(&shared, RenderState::default());
+    let red = quad(&shared, 0.0, [255, 0, 0, 255]);
+    shared.draw(surface, DrawCall::new(shader, red));
+    advance(&mut video);
+
+    let verts = [
+        Vertex::new([-1.0, -1.0, 0.0], [0, 255, 0, 255]),
+        Vertex::new([1.0, -1.0, 0.0], [0, 255, 0, 255]),
+        Vertex::new([-1.0, 1.0, 0.0], [0, 255, 0, 255]),
+    ];
+
+    let triangle = mesh(&shared, &verts, &[0, 1, 2]);
+    shared.draw(surface, DrawCall::new(shader, triangle));
+    advance(&mut video);
+
+    let trace = video.stop_recording().unwrap();
+    assert_eq!(trace.frames.len(), 2);
+
+    let mut bytes = Vec::new();
+    trace.save_to(&mut bytes).unwrap();
+    let trace = Trace::load_from(&mut &bytes[..]).unwrap();
+
+    let (mut replayer, replayed) = testbed((4, 4));
+    let info = replayer.replay(&trace.frames[0]).unwrap();
+    assert_eq!(info.drawcall, 1);
+    assert_eq!(info.triangles, 2);
+    assert_eq!(replayed.pixel(3, 3), [255, 0, 0, 255]);
+
+    replayer.replay(&trace.frames[1]).unwrap();
+    assert_eq!(replayed.pixels(), device.pixels());
+    assert_eq!(replayed.pixel(0, 0), [0, 255, 0, 255]);
+    assert_eq!(replayed.pixel(3, 3), [0, 0, 0, 255]);
+
+    // The trace with incompatible header is rejected.
+    bytes[0] = 0;
+    assert!(Trace::load_from(&mut &bytes[..]).is_err());
+}
+
+#[test]
+fn read_pixels() {
+    let (mut video, _) = testbed((4, 4));
+    let shared = video.shared();
+
+    let mut params = RenderTextureParams::default();
+    params.format = RenderTextureFormat::RGBA8;
+    params.dimensions = (2, 2).into();
+    let rt = shared.create_render_texture(params).unwrap();
+
+    let mut params = SurfaceParams::default();
+    params.set_attachments(&[rt], None).unwrap();
+    params.set_clear(math::Color::white(), None, None);
+    let offscreen = shared.create_surface(params).unwrap();
+
+    let surface = shared.create_surface(SurfaceParams::default()).unwrap();
+    let shader = create_shader(&shared, RenderState::default());
+    let verts = [
+        Vertex::new([-1.0, -1.0, 0.0], [255, 0, 0, 255]),
+        Vertex::new([1.0, -1.0, 0.0], [255, 0, 0, 255]),
+        Vertex::new([-1.0, 1.0, 0.0], [255, 0, 0, 255]),
+    ];
+
+    let triangle = mesh(&shared, &verts, &[0, 1, 2]);
+    shared.draw(surface, DrawCall::new(shader, triangle));
+    shared.update_scissor(offscreen, SurfaceScissor::Disable);
+
+    // The requests are serviced after all the draw calls of this frame.
+    let area = math::Aabb2::new(math::Point2::new(0, 0), math::Point2::new(3, 1));
+    let bottom = shared.read_pixels(surface, area).unwrap();
+    let area = math::Aabb2::new(math::Point2::new(2, 3), math::Point2::new(4, 4));
+    let top = shared.read_pixels(surface, area).unwrap();
+    let area = math::Aabb2::new(math::Point2::new(1, 1), math::Point2::new(2, 2));
+    let texture = shared.read_pixels(rt, area).unwrap();
+
+    assert!(!bottom.is_ready());
+    advance(&mut video);
+    assert!(bottom.is_ready());
+
+    let mut red = Vec::new();
+    for _ in 0..3 {
+        red.extend_from_slice(&[255, 0, 0, 255]);
+    }
+
+    assert_eq!(bottom.take().unwrap(), red);
+    assert_eq!(top.take().unwrap(), vec![0, 0, 0, 255, 0, 0, 0, 255]);
+    assert_eq!(texture.take().unwrap(), vec![255, 255, 255, 255]);
+    assert!(texture.take().is_err());
+
+    // Depth render textures and the areas out of bounds are rejected.
+    let mut params = RenderTextureParams::default();
+    params.format = RenderTextureFormat::Depth16;
+    params.dimensions = (2, 2).into();
+    let depth = shared.create_render_texture(params).unwrap();
+
+    let area = math::Aabb2::new(math::Point2::new(0, 0), math::Point2::new(1, 1));
+    assert!(shared.read_pixels(depth, area).is_err());
+    let area = math::Aabb2::new(math::Point2::new(0, 0), math::Point2::new(3, 3));
+    assert!(shared.read_pixels(rt, area).is_err());
+    let area = math::Aabb2::new(math::Point2::new(2, 2), math::Point2::new(5, 4));
+    assert!(shared.read_pixels(surface, area).is_err());
+    let area = math::Aabb2::new(math::Point2::new(0, 0), math::Point2::new(4, 4));
+    assert!(shared.read_pixels(surface, area).is_ok());
+}
+
+#[test]
+fn read_pixels_failed_frame() {
+    let (mut video, _) = testbed((4, 4));
+    let shared = video.shared();
+
+    let surface = shared.create_surface(SurfaceParams::default()).unwrap();
+    let area = math::Aabb2::new(math::Point2::new(0, 0), math::Point2::new(1, 1));
+    let before = shared.read_pixels(surface, area).unwrap();
+
+    // Immutable textures could only be rejected by backend, which fails the whole frame.
+    let params = TextureParams::default();
+    let texture = shared.create_texture(params, None).unwrap();
+    shared.update_texture(texture, area, &[0; 4]).unwrap();
+    let after = shared.read_pixels(surface, area).unwrap();
+
+    video.swap_frames();
+    assert!(video.advance(&Window::headless()).is_err());
+    assert!(before.is_ready() && after.is_ready());
+    assert!(before.take().is_err());
+    assert!(after.take().is_err());
+
+    // The readbacks of frames which are never dispatched are fulfilled too.
+    let dropped = shared.read_pixels(surface, area).unwrap();
+    drop(shared);
+    drop(video);
+    assert!(dropped.is_ready());
+    assert!(dropped.take().is_err());
+}
+
+#[test]
+fn cube_map() {
+    let (mut video, device) = testbed((1, 1));
+    let shared = video.shared();
+
+    let colors = [
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [0, 0, 255, 255],
+        [255, 255, 0, 255],
+        [0, 255, 255, 255],
+        [255, 0, 255, 255],
+    ];
+
+    let mut params = TextureParams::default();
+    params.kind = TextureKind::Cube;
+    params.hint = TextureHint::Dynamic;
+    params.filter = TextureFilter::Nearest;
+    params.dimensions = (1, 1).into();
+
+    // The faces of cube map must be square, and provided together.
+    let data = TextureData {
+        bytes: vec![colors[0].to_vec().into_boxed_slice()],
+    };
+
+    assert!(shared.create_texture(params, data).is_err());
+    params.dimensions = (2, 1).into();
+    assert!(shared.create_texture(params, None).is_err());
+    params.dimensions = (1, 1).into();
+
+    let data = TextureData {
+        bytes: colors
+            .iter()
+            .map(|v| v.to_vec().into_boxed_slice())
+            .collect(),
+    };
+
+    let texture = shared.create_texture(params, data).unwrap();
+    let surface = shared.create_surface(SurfaceParams::default()).unwrap();
+
+    let mut params = ShaderParams::default();
+    params.attributes = Vertex::attributes();
+    params.uniforms = UniformVariableLayout::build()
+        .with("u_Skybox", UniformVariableType::CubeTexture)
+        .with("u_Direction", UniformVariableType::Vector3f)
+        .finish();
+
+    let src = "software".to_owned();
+    let shader = shared.create_shader(params, src.clone(), src).unwrap();
+
+    let program = Program::new(
+        |_, attributes, _| attributes.get(Attribute::Position),
+        |uniforms, _| {
+            let direction = match uniforms.get("u_Direction") {
+                Some(UniformVariable::Vector3f(v)) => v.into(),
+                _ => math::Vector3::new(0.0, 0.0, 0.0),
+            };
+
+            Some(uniforms.sample_cube("u_Skybox", direction))
+        },
+    );
+
+    device.set_program(shader, program);
+    let quad = quad(&shared, 0.0, [0, 0, 0, 0]);
+
+    let directions = [
+        [1.0, 0.2, -0.3],
+        [-1.0, 0.2, 0.3],
+        [0.2, 1.0, 0.3],
+        [0.2, -1.0, -0.3],
+        [0.2, 0.3, 1.0],
+        [-0.2, 0.3, -1.0],
+    ];
+
+    let draw = |video: &mut VideoSystem, direction: [f32; 3]| {
+        let mut dc = DrawCall::new(shader, quad);
+        dc.set_uniform_variable("u_Skybox", UniformVariable::CubeTexture(texture));
+        dc.set_uniform_variable("u_Direction", direction);
+        shared.draw(surface, dc);
+        advance(video);
+    };
+
+    for (face, direction) in CubeFace::ALL.iter().zip(directions.iter()) {
+        draw(&mut video, *direction);
+        assert_eq!(device.pixel(0, 0), colors[face.layer() as usize]);
+    }
+
+    // Updates one of the faces.
+    let area = math::Aabb2::new(math::Point2::new(0, 0), math::Point2::new(1, 1));
+    shared
+        .update_texture_face(texture, CubeFace::NegativeZ, area, &[9, 9, 9, 255])
+        .unwrap();
+
+    draw(&mut video, directions[5]);
+    assert_eq!(device.pixel(0, 0), [9, 9, 9, 255]);
+    draw(&mut video, directions[4]);
+    assert_eq!(device.pixel(0, 0), colors[4]);
+
+    // A cube map could not be bound as 2D texture.
+    let mut dc = DrawCall::new(shader, quad);
+    dc.set_uniform_variable("u_Skybox", texture);
+    shared.draw(surface, dc);
+    video.swap_frames();
+    assert!(video.advance(&Window::headless()).is_err());
+}
+
+#[test]
+fn texture_array() {
+    let (mut video, device) = testbed((1, 1));
+    let shared = video.shared();
+
+    let colors = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+
+    let mut params = TextureParams::default();
+    params.kind = TextureKind::Array2D;
+    params.hint = TextureHint::Dynamic;
+    params.filter = TextureFilter::Nearest;
+    params.dimensions = (1, 1).into();
+    params.depth = 0;
+
+    // A texture array has one layer at least, and the layers must be provided together.
+    assert!(shared.create_texture(params, None).is_err());
+    params.depth = 3;
+
+    let data = TextureData {
+        bytes: vec![colors[0].to_vec().into_boxed_slice()],
+    };
+
+    assert!(shared.create_texture(params, data).is_err());
+
+    let data = TextureData {
+        bytes: colors
+            .iter()
+            .map(|v| v.to_vec().into_boxed_slice())
+            .collect(),
+    };
+
+    let texture = shared.create_texture(params, data).unwrap();
+    let surface = shared.create_surface(SurfaceParams::default()).unwrap();
+
+    let mut params = ShaderParams::default();
+    params.attributes = Vertex::attributes();
+    params.uniforms = UniformVariableLayout::build()
+        .with("u_Layers", UniformVariableType::TextureArray)
+        .with("u_Layer", UniformVariableType::I32)
+        .finish();
+
+    let src = "software".to_owned();
+    let shader = shared.create_shader(params, src.clone(), src).unwrap();
+
+    let program = Program::new(
+        |_, attributes, _| attributes.get(Attribute::Position),
+        |uniforms, _| {
+            let layer = match uniforms.get("u_Layer") {
+                Some(UniformVariable::I32(v)) => v as u32,
+                _ => 0,
+            };
+
+            let texcoord = math::Vector2::new(0.5, 0.5);
+            Some(uniforms.sample_layer("u_Layers", texcoord, layer))
+        },
+    );
+
+    device.set_program(shader, program);
+    let quad = quad(&shared, 0.0, [0, 0, 0, 0]);
+
+    let draw = |video: &mut VideoSystem, layer: i32| {
+        let mut dc = DrawCall::new(shader, quad);
+        dc.set_uniform_variable("u_Layers", UniformVariable::TextureArray(texture));
+        dc.set_uniform_variable("u_Layer", layer);
+        shared.draw(surface, dc);
+        advance(video);
+    };
+
+    for (i, color) in colors.iter().enumerate() {
+        draw(&mut video, i as i32);
+        assert_eq!(device.pixel(0, 0), *color);
+    }
+
+    // The layer is clamped to the last one.
+    draw(&mut video, 7);
+    assert_eq!(device.pixel(0, 0), colors[2]);
+
+    // Updates one of the layers.
+    let area = math::Aabb2::new(math::Point2::new(0, 0), math::Point2::new(1, 1));
+    shared
+        .update_texture_layer(texture, 1, area, &[9, 9, 9, 255])
+        .unwrap();
+
+    draw(&mut video, 1);
+    assert_eq!(device.pixel(0, 0), [9, 9, 9, 255]);
+    draw(&mut video, 0);
+    assert_eq!(device.pixel(0, 0), colors[0]);
+
+    // The layer is validated before dispatching.
+    let rsp = shared.update_texture_layer(texture, 3, area, &[9, 9, 9, 255]);
+    assert!(rsp.is_err());
+
+    // A texture array could not be bound as 3D texture.
+    let mut dc = DrawCall::new(shader, quad);
+    dc.set_uniform_variable("u_Layers", UniformVariable::Texture3D(texture));
+    dc.set_uniform_variable("u_Layer", 0);
+    shared.draw(surface, dc);
+    video.swap_frames();
+    assert!(video.advance(&Window::headless()).is_err());
+}
+
+#[test]
+fn texture_3d() {
+    let (mut video, device) = testbed((1, 1));
+    let shared = video.shared();
+
+    let mut params = TextureParams::default();
+    params.kind = TextureKind::Texture3D;
+    params.filter = TextureFilter::Linear;
+    params.dimensions = (1, 1).into();
+    params.depth = 2;
+
+    // 3D textures have no mipmaps.
+    let data = TextureData {
+        bytes: (0..4).map(|_| vec![0; 4].into_boxed_slice()).collect(),
+    };
+
+    assert!(shared.create_texture(params, data).is_err());
+
+    let data = TextureData {
+        bytes: vec![
+            vec![0, 0, 0, 255].into_boxed_slice(),
+            vec![200, 100, 0, 255].into_boxed_slice(),
+        ],
+    };
+
+    let texture = shared.create_texture(params, data).unwrap();
+    let surface = shared.create_surface(SurfaceParams::default()).unwrap();
+
+    let mut params = ShaderParams::default();
+    params.attributes = Vertex::attributes();
+    params.uniforms = UniformVariableLayout::build()
+        .with("u_Volume", UniformVariableType::Texture3D)
+        .with("u_Depth", UniformVariableType::F32)
+        .finish();
+
+    let src = "software".to_owned();
+    let shader = shared.create_shader(params, src.clone(), src).unwrap();
+
+    let program = Program::new(
+        |_, attributes, _| attributes.get(Attribute::Position),
+        |uniforms, _| {
+            let z = match uniforms.get("u_Depth") {
+                Some(UniformVariable::F32(v)) => v,
+                _ => 0.0,
+            };
+
+            let texcoord = math::Vector3::new(0.5, 0.5, z);
+            Some(uniforms.sample_3d("u_Volume", texcoord))
+        },
+    );
+
+    device.set_program(shader, program);
+    let quad = quad(&shared, 0.0, [0, 0, 0, 0]);
+
+    let draw = |video: &mut VideoSystem, z: f32| {
+        let mut dc = DrawCall::new(shader, quad);
+        dc.set_uniform_variable("u_Volume", UniformVariable::Texture3D(texture));
+        dc.set_uniform_variable("u_Depth", z);
+        shared.draw(surface, dc);
+        advance(video);
+    };
+
+    // The slices are interpolated at the center of texels.
+    draw(&mut video, 0.25);
+    assert_eq!(device.pixel(0, 0), [0, 0, 0, 255]);
+    draw(&mut video, 0.75);
+    assert_eq!(device.pixel(0, 0), [200, 100, 0, 255]);
+    draw(&mut video, 0.5);
+    assert_eq!(device.pixel(0, 0), [100, 50, 0, 255]);
+}
+
+#[test]
+fn texture_format_v1() {
+    use crayon::bincode;
+    use crayon::res::format::Format;
+    use crayon::video::assets::texture_loader;
+
+    let mut bytes = Format::new("TextureLoader", *b"VTEX", 1).magic().to_vec();
+    let params = (
+        TextureHint::Dynamic,
+        TextureWrap::Repeat,
+        TextureFilter::Nearest,
+        TextureFormat::RGB8,
+        math::Vector2::new(2u32, 4u32),
+    );
+
+    let data = TextureData {
+        bytes: vec![vec![1, 2, 3].into_boxed_slice()],
+    };
+
+    bincode::serialize_into(&mut bytes, &params).unwrap();
+    bincode::serialize_into(&mut bytes, &data).unwrap();
+
+    let payload = texture_loader::format().decode(&bytes).unwrap();
+    let mut file = &payload[..];
+    let params: TextureParams = bincode::deserialize_from(&mut file).unwrap();
+    let data: TextureData = bincode::deserialize_from(&mut file).unwrap();
+
+    assert_eq!(params.kind, TextureKind::Texture2D);
+    assert_eq!(params.depth, 1);
+    assert_eq!(params.hint, TextureHint::Dynamic);
+    assert_eq!(params.wrap, TextureWrap::Repeat);
+    assert_eq!(params.format, TextureFormat::RGB8);
+    assert_eq!(params.dimensions, math::Vector2::new(2, 4));
+    assert_eq!(&data.bytes[0][..], &[1, 2, 3]);
+}
+
+#[test]
+fn texture_format_v2() {
+    use crayon::bincode;
+    use crayon::res::format::Format;
+    use crayon::video::assets::texture_loader;
+
+    let mut bytes = Format::new("TextureLoader", *b"VTEX", 2).magic().to_vec();
+    let params = (
+        TextureHint::Immutable,
+        TextureWrap::Clamp,
+        TextureFilter::Linear,
+        TextureFormat::RGBA8,
+        math::Vector2::new(1u32, 1u32),
+        TextureKind::Cube,
+    );
+
+    let data = TextureData {
+        bytes: (0..6).map(|i| vec![i; 4].into_boxed_slice()).collect(),
+    };
+
+    bincode::serialize_into(&mut bytes, &params).unwrap();
+    bincode::serialize_into(&mut bytes, &data).unwrap();
+
+    let payload = texture_loader::format().decode(&bytes).unwrap();
+    let mut file = &payload[..];
+    let params: TextureParams = bincode::deserialize_from(&mut file).unwrap();
+    let data: TextureData = bincode::deserialize_from(&mut file).unwrap();
+
+    assert_eq!(params.kind, TextureKind::Cube);
+    assert_eq!(params.depth, 1);
+    assert_eq!(params.layers(), 6);
+    assert_eq!(params.dimensions, math::Vector2::new(1, 1));
+    assert_eq!(&data.bytes[5][..], &[5, 5, 5, 5]);
+}